@@ -0,0 +1,112 @@
+//! # Global Options
+//!
+//! Options shared by every command: the market store and the Bitcoin network.
+
+use crate::{
+    output::{CliError, OutputFormat},
+    store,
+};
+use anyhow::{bail, Result};
+use bitcoin::{Network, ScriptBuf};
+use clap::Args;
+use markstr_core::{
+    network::{network_name, parse_network, parse_signet_challenge, require_covenants},
+    MarketError, PredictionMarket,
+};
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct Config {
+    /// Market store: a directory, or a SQLite database (.db), defaults to $MARKSTR_STORE or ~/.markstr
+    #[arg(long, global = true)]
+    store: Option<PathBuf>,
+    /// Bitcoin network: mainnet, testnet, testnet4, signet or regtest (default: signet,
+    /// or the network of the market)
+    #[arg(long, global = true)]
+    network: Option<String>,
+    /// Challenge script (hex) of a custom signet
+    #[arg(long, global = true)]
+    signet_challenge: Option<String>,
+    /// Allow networks where CTV and CSFS are not known to be active
+    #[arg(long, global = true)]
+    allow_inactive_opcodes: bool,
+    /// Output format: colored text, or one JSON document
+    #[arg(long, global = true, value_enum, default_value_t)]
+    output: OutputFormat,
+}
+
+impl Config {
+    /// Path of the market store
+    pub fn store_path(&self) -> PathBuf {
+        self.store.clone().unwrap_or_else(store::default_store_path)
+    }
+
+    /// Selected output format
+    pub fn output(&self) -> OutputFormat {
+        self.output
+    }
+
+    /// The selected network and custom signet challenge, signet by default
+    pub fn network(&self) -> Result<(Network, Option<ScriptBuf>)> {
+        let challenge = self
+            .signet_challenge
+            .as_deref()
+            .map(parse_signet_challenge)
+            .transpose()?;
+        let network = self
+            .network
+            .as_deref()
+            .map(parse_network)
+            .transpose()?
+            .unwrap_or(Network::Signet);
+        if challenge.is_some() && network != Network::Signet {
+            bail!(CliError::InvalidArgument(
+                "--signet-challenge requires --network signet".to_string()
+            ));
+        }
+        Ok((network, challenge))
+    }
+
+    /// Refuses a network where CTV and CSFS are not known to be active,
+    /// unless overridden with --allow-inactive-opcodes
+    pub fn require_covenants(&self, network: Network, challenge: Option<&ScriptBuf>) -> Result<()> {
+        if self.allow_inactive_opcodes {
+            return Ok(());
+        }
+        require_covenants(network, challenge.map(ScriptBuf::as_script)).map_err(|e| {
+            let message = format!("{e}, pass --allow-inactive-opcodes to proceed anyway");
+            anyhow::Error::new(e).context(message)
+        })
+    }
+
+    /// Checks that a market is on the network given with --network, if any
+    pub fn check_market(&self, market: &PredictionMarket) -> Result<()> {
+        if self.network.is_none() && self.signet_challenge.is_none() {
+            return Ok(());
+        }
+        let (network, challenge) = self.network()?;
+        if network != market.network || challenge != market.signet_challenge {
+            bail!(MarketError::Network(format!(
+                "Market {} is on {}, not on the selected {}",
+                market.market_id,
+                describe(market.network, market.signet_challenge.as_ref()),
+                describe(network, challenge.as_ref())
+            )));
+        }
+        Ok(())
+    }
+
+    /// Refuses to move funds of a market on a network where CTV and CSFS are
+    /// not known to be active, unless overridden
+    pub fn require_market_covenants(&self, market: &PredictionMarket) -> Result<()> {
+        self.require_covenants(market.network, market.signet_challenge.as_ref())
+    }
+}
+
+/// Name of a network, custom signets included
+pub fn describe(network: Network, challenge: Option<&ScriptBuf>) -> String {
+    match challenge {
+        Some(challenge) => format!("custom signet ({})", challenge.to_hex_string()),
+        None => network_name(network).to_string(),
+    }
+}
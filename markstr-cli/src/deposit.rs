@@ -0,0 +1,380 @@
+//! # Deposit Commands
+//!
+//! Runs the deposit round from the terminal, one file per message:
+//!
+//! 1. `deposit create`: each bettor builds the contribution funding their bet
+//!    (the first contribution freezes the bet set).
+//! 2. `deposit sign`: each bettor rebuilds the deposit PSBT from every contribution
+//!    and signs their own inputs.
+//! 3. `deposit combine`: the signed PSBTs are merged into the deposit transaction,
+//!    and its first output is recorded as the market pool UTXO.
+
+use crate::{
+    config::Config,
+    market::{read_text, SavedMarket},
+    output::{CliError, Report},
+};
+use anyhow::{bail, Context, Result};
+use bitcoin::{
+    consensus::encode::{deserialize_hex, serialize_hex},
+    Address, Amount, OutPoint, PrivateKey, Psbt, ScriptBuf, Transaction, TxOut,
+};
+use clap::{Args, Subcommand};
+use colored::*;
+use markstr_core::{
+    deposit::{
+        build_deposit_psbt, combine_deposit_transaction, create_partial_pool_tx, sign_deposit_psbt,
+        DepositInput, PartialDepositTx,
+    },
+    network::network_name,
+    MarketState, PredictionMarket,
+};
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr};
+
+/// Environment variable holding the bettor private key
+pub const KEY_ENV: &str = "MARKSTR_BETTOR_KEY";
+
+/// Where the bettor private key is read from
+#[derive(Args)]
+pub struct KeyArgs {
+    /// File holding the private key of the deposit inputs (WIF or hex), defaults to $MARKSTR_BETTOR_KEY
+    #[arg(long)]
+    key_file: Option<PathBuf>,
+}
+
+impl KeyArgs {
+    /// Loads the private key from the file, or from the environment
+    fn load(&self, market: &PredictionMarket) -> Result<PrivateKey> {
+        let key = match &self.key_file {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+            None => std::env::var(KEY_ENV).map_err(|_| {
+                CliError::MissingInput(format!("No private key: pass --key-file or set {KEY_ENV}"))
+            })?,
+        };
+        let key = key.trim();
+        if let Ok(key) = PrivateKey::from_wif(key) {
+            return Ok(key);
+        }
+        let bytes = hex::decode(key).map_err(|_| {
+            CliError::InvalidArgument("Private key is neither WIF nor hex".to_string())
+        })?;
+        Ok(PrivateKey::from_slice(&bytes, market.network)?)
+    }
+}
+
+#[derive(Subcommand)]
+pub enum DepositCommand {
+    /// Build the contribution funding a bet, freezing the bet set if needed
+    Create {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Index of the bet being funded, as listed by `markstr info`
+        #[arg(short, long)]
+        bet: usize,
+        /// UTXO spent by the contribution, as TXID:VOUT:SATS:ADDRESS, with a
+        /// trailing :REDEEM_SCRIPT (hex) for P2SH-P2WPKH (repeat for each input)
+        #[arg(short, long = "input", required = true)]
+        inputs: Vec<String>,
+        /// Address receiving the change
+        #[arg(short, long)]
+        change: String,
+        /// Write the contribution to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Sign your inputs of the deposit PSBT built from every contribution
+    Sign {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Contribution file (repeat for each bet)
+        #[arg(long = "contribution", required = true)]
+        contributions: Vec<PathBuf>,
+        /// Index of your bet
+        #[arg(short, long)]
+        bet: usize,
+        #[command(flatten)]
+        key: KeyArgs,
+        /// Write the signed PSBT to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Merge the signed PSBTs into the deposit transaction and record the pool UTXO
+    Combine {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Contribution file (repeat for each bet)
+        #[arg(long = "contribution", required = true)]
+        contributions: Vec<PathBuf>,
+        /// Signed PSBT, base64 or file (repeat for each bet)
+        #[arg(long = "psbt", required = true)]
+        psbts: Vec<String>,
+        /// Raw transaction (hex or file) creating deposit inputs, checked against the
+        /// contributions (repeat for each funding transaction)
+        #[arg(long = "prevout-tx")]
+        prevout_txs: Vec<String>,
+        /// Write the raw transaction to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+/// Runs a deposit command, markets are looked up in the configured store
+pub fn run(command: DepositCommand, config: &Config, report: &mut Report) -> Result<()> {
+    match command {
+        DepositCommand::Create {
+            market,
+            bet,
+            inputs,
+            change,
+            out,
+        } => {
+            let mut saved = SavedMarket::load(&market, config)?;
+            config.require_market_covenants(&saved.market)?;
+
+            // Build the contribution first, so that a bad input or change address
+            // does not leave the bet set frozen behind it
+            let market = &saved.market;
+            let funded_bet = market.all_bets().nth(bet).ok_or_else(|| {
+                CliError::InvalidArgument(format!(
+                    "Bet {bet} out of range, market has {} bets",
+                    market.all_bets().count()
+                ))
+            })?;
+            let inputs = inputs
+                .iter()
+                .map(|input| parse_input(input, market))
+                .collect::<Result<Vec<_>>>()?;
+            let change = parse_address(&change, market)?;
+            let contribution = create_partial_pool_tx(market, funded_bet, bet, inputs, &change)?;
+            let bet_amount = funded_bet.amount;
+
+            let frozen = saved.market.state == MarketState::Open;
+            if frozen {
+                saved.market.freeze_bets()?;
+                saved.save()?;
+                report.title("Bet set frozen");
+            }
+            report.set("frozen", frozen);
+            let market = &saved.market;
+            let pool_address = market.get_market_address()?;
+            report.line(format!(
+                "{}: {}",
+                "Pool Address".cyan().bold(),
+                pool_address
+            ));
+            report.set("market_id", &market.market_id);
+            report.set("pool_address", pool_address);
+            report.set("bet_index", bet);
+            report.labeled("Bet Amount", format!("{bet_amount} sats"));
+            report.set("bet_amount", bet_amount);
+            report.artifact(
+                "Contribution",
+                "contribution",
+                &serde_json::to_string_pretty(&contribution)?,
+                serde_json::to_value(&contribution)?,
+                out.as_deref(),
+            )?;
+        }
+
+        DepositCommand::Sign {
+            market,
+            contributions,
+            bet,
+            key,
+            out,
+        } => {
+            let market = SavedMarket::load(&market, config)?.market;
+            config.require_market_covenants(&market)?;
+            let contributions = read_contributions(&contributions)?;
+            let own = contributions
+                .iter()
+                .find(|contribution| contribution.input_index == bet)
+                .ok_or_else(|| {
+                    CliError::MissingInput(format!("No contribution funds bet {bet}"))
+                })?;
+
+            let mut psbt = build_deposit_psbt(&market, contributions.clone())?;
+            let signed = sign_deposit_psbt(&mut psbt, &market, own, &key.load(&market)?)?;
+            report.line(format!(
+                "{}: {} input(s) of bet {}",
+                "Signed".green().bold(),
+                signed,
+                bet
+            ));
+            report.set("bet_index", bet);
+            report.set("signed_inputs", signed);
+            report.artifact(
+                "Signed PSBT",
+                "psbt",
+                &psbt.to_string(),
+                psbt.to_string().into(),
+                out.as_deref(),
+            )?;
+        }
+
+        DepositCommand::Combine {
+            market,
+            contributions,
+            psbts,
+            prevout_txs,
+            out,
+        } => {
+            let mut saved = SavedMarket::load(&market, config)?;
+            config.require_market_covenants(&saved.market)?;
+            let contributions = read_contributions(&contributions)?;
+            let psbts = psbts
+                .iter()
+                .map(|psbt| {
+                    Psbt::from_str(&read_text(psbt)?)
+                        .with_context(|| CliError::InvalidArgument(format!("Invalid PSBT {psbt}")))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            // The terminal has no view of the chain: without the funding transactions,
+            // prevouts are taken from the contributions themselves and only checked
+            // by the network when the deposit is broadcast
+            let verified = !prevout_txs.is_empty();
+            let prevouts = if verified {
+                funding_prevouts(&prevout_txs)?
+            } else {
+                contribution_prevouts(&contributions)
+            };
+            let transaction =
+                combine_deposit_transaction(&saved.market, &contributions, psbts, |outpoint| {
+                    prevouts.get(outpoint).cloned()
+                })?;
+
+            let txid = transaction.compute_txid();
+            let pool_utxo = OutPoint::new(txid, 0);
+            saved.market.mark_funded(pool_utxo)?;
+            saved.save()?;
+            let raw = serialize_hex(&transaction);
+            report.title("Deposit transaction complete");
+            report.set("market_id", &saved.market.market_id);
+            report.field("Txid", "txid", txid);
+            report.field("Pool UTXO", "pool_utxo", pool_utxo);
+            report.set("prevouts_verified", verified);
+            if !verified {
+                report.line(
+                    "Prevouts were not verified: the input amounts and scripts are the ones \
+                     claimed by the contributions, pass --prevout-tx to check them."
+                        .yellow(),
+                );
+            }
+            report.artifact(
+                "Deposit Transaction",
+                "transaction",
+                &raw,
+                raw.clone().into(),
+                out.as_deref(),
+            )?;
+            report.line(
+                "Broadcast the deposit transaction, the market is recorded as funded."
+                    .bright_blue(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a `TXID:VOUT:SATS:ADDRESS[:REDEEM_SCRIPT]` deposit input
+fn parse_input(input: &str, market: &PredictionMarket) -> Result<DepositInput> {
+    let parts: Vec<&str> = input.split(':').collect();
+    let (txid, vout, amount, address, redeem_script) = match parts.as_slice() {
+        [txid, vout, amount, address] => (txid, vout, amount, address, None),
+        [txid, vout, amount, address, redeem_script] => {
+            (txid, vout, amount, address, Some(redeem_script))
+        }
+        _ => bail!(CliError::InvalidArgument(format!(
+            "Invalid input {input}, expected TXID:VOUT:SATS:ADDRESS[:REDEEM_SCRIPT]"
+        ))),
+    };
+    let outpoint = OutPoint::from_str(&format!("{txid}:{vout}"))
+        .with_context(|| CliError::InvalidArgument(format!("Invalid outpoint in input {input}")))?;
+    let prevout = TxOut {
+        value: Amount::from_sat(amount.parse().with_context(|| {
+            CliError::InvalidArgument(format!("Invalid amount in input {input}"))
+        })?),
+        script_pubkey: parse_address(address, market)?.script_pubkey(),
+    };
+    Ok(match redeem_script {
+        Some(redeem_script) => DepositInput::with_redeem_script(
+            outpoint,
+            prevout,
+            ScriptBuf::from_hex(redeem_script).with_context(|| {
+                CliError::InvalidArgument(format!("Invalid redeem script in input {input}"))
+            })?,
+        ),
+        None => DepositInput::new(outpoint, prevout),
+    })
+}
+
+/// Parses an address of the market network
+fn parse_address(address: &str, market: &PredictionMarket) -> Result<Address> {
+    Address::from_str(address)
+        .with_context(|| CliError::InvalidArgument(format!("Invalid address {address}")))?
+        .require_network(market.network)
+        .with_context(|| {
+            CliError::InvalidArgument(format!(
+                "Address {address} is not valid for {}",
+                network_name(market.network)
+            ))
+        })
+}
+
+/// Reads the contribution files
+fn read_contributions(paths: &[PathBuf]) -> Result<Vec<PartialDepositTx>> {
+    paths
+        .iter()
+        .map(|path| {
+            let json = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&json).with_context(|| {
+                CliError::InvalidArgument(format!("Invalid contribution in {}", path.display()))
+            })
+        })
+        .collect()
+}
+
+/// The outputs of the funding transactions, each a raw transaction in hex or a file
+fn funding_prevouts(transactions: &[String]) -> Result<HashMap<OutPoint, TxOut>> {
+    let mut prevouts = HashMap::new();
+    for transaction in transactions {
+        let transaction: Transaction =
+            deserialize_hex(&read_text(transaction)?).with_context(|| {
+                CliError::InvalidArgument(format!("Invalid funding transaction {transaction}"))
+            })?;
+        let txid = transaction.compute_txid();
+        prevouts.extend(
+            transaction
+                .output
+                .into_iter()
+                .enumerate()
+                .map(|(vout, output)| (OutPoint::new(txid, vout as u32), output)),
+        );
+    }
+    Ok(prevouts)
+}
+
+/// The prevouts claimed by the contributions
+fn contribution_prevouts(contributions: &[PartialDepositTx]) -> HashMap<OutPoint, TxOut> {
+    contributions
+        .iter()
+        .flat_map(|contribution| {
+            contribution
+                .psbt
+                .unsigned_tx
+                .input
+                .iter()
+                .zip(&contribution.psbt.inputs)
+                .filter_map(|(tx_input, input)| {
+                    Some((tx_input.previous_output, input.witness_utxo.clone()?))
+                })
+        })
+        .collect()
+}
@@ -0,0 +1,91 @@
+//! # Graph Command
+//!
+//! Exports every transaction a market can produce: the deposit, the payout of
+//! each outcome and the escape, with their txids, CTV hashes and amounts, and
+//! the spend graph in the Graphviz DOT language.
+
+use crate::{
+    config::Config,
+    market::{read_text, SavedMarket},
+    output::{CliError, Report},
+};
+use anyhow::{Context, Result};
+use bitcoin::{consensus::encode::deserialize_hex, Transaction};
+use colored::*;
+use markstr_core::{
+    graph::{build_transaction_graph, DepositSource, WithdrawNode},
+    inspect::SpentLeaf,
+};
+use std::path::Path;
+
+/// Runs the graph command, markets are looked up in the configured store
+pub fn run(
+    market: &str,
+    deposit: Option<&str>,
+    out: Option<&Path>,
+    config: &Config,
+    report: &mut Report,
+) -> Result<()> {
+    let market = SavedMarket::load(market, config)?.market;
+    let deposit: Option<Transaction> = deposit
+        .map(|deposit| {
+            deserialize_hex(&read_text(deposit)?).with_context(|| {
+                CliError::InvalidArgument("Invalid raw deposit transaction".to_string())
+            })
+        })
+        .transpose()?;
+    let graph = build_transaction_graph(&market, deposit.as_ref())?;
+
+    report.title("Transaction Graph");
+    report.rule();
+    report.labeled("Pool Address", &graph.pool_address);
+    report.labeled("Pool UTXO", graph.pool_utxo);
+    report.labeled("Pool Amount", format!("{} sats", graph.pool_amount));
+    let source = match graph.deposit.source {
+        DepositSource::Transaction => "given transaction".normal(),
+        DepositSource::Recorded => "recorded pool UTXO".normal(),
+        DepositSource::Template => {
+            "template, txids are placeholders until the deposit round".yellow()
+        }
+    };
+    report.labeled(
+        "Deposit",
+        format!(
+            "{} ({}, {} input(s))",
+            graph.deposit.txid,
+            source,
+            graph.deposit.inputs.len()
+        ),
+    );
+    for node in graph.payouts.iter().chain([&graph.escape]) {
+        let name = match node.leaf {
+            SpentLeaf::Payout { outcome_index } => {
+                format!("Payout {}", graph.outcome_labels[outcome_index])
+            }
+            SpentLeaf::Escape => "Escape".to_string(),
+        };
+        describe(report, &name, node);
+    }
+    report.rule();
+
+    report.set("market_id", &market.market_id);
+    report.set("graph", &graph);
+    let dot = graph.to_dot();
+    report.artifact("Graphviz DOT", "dot", &dot, dot.clone().into(), out)
+}
+
+/// Prints a payout or escape transaction
+fn describe(report: &Report, name: &str, node: &WithdrawNode) {
+    report.labeled(name, node.txid);
+    report.line(format!("  CTV hash: {}", node.ctv_hash));
+    if node.leaf == SpentLeaf::Escape {
+        report.line(format!(
+            "  Lock time {}, sequence {}",
+            node.lock_time, node.sequence
+        ));
+    }
+    for output in &node.outputs {
+        report.line(format!("  {} sats to {}", output.amount, output.address));
+    }
+    report.line(format!("  Fee: {} sats", node.fee));
+}
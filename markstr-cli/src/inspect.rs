@@ -0,0 +1,152 @@
+//! # Inspect Command
+//!
+//! Decodes a raw transaction or PSBT spending a market pool and explains it:
+//! the leaf spent, the CTV template check, the payout of every bet, the fee and
+//! the witness.
+
+use crate::{
+    config::Config,
+    market::{read_text, SavedMarket},
+    output::{CliError, Report},
+};
+use anyhow::{Context, Result};
+use bitcoin::{consensus::encode::deserialize_hex, Address, Psbt, Transaction};
+use colored::*;
+use markstr_core::{
+    inspect::{inspect_pool_psbt, inspect_pool_spend, SpentLeaf, WitnessRole},
+    PredictionMarket,
+};
+use std::str::FromStr;
+
+/// Runs the inspect command, markets are looked up in the configured store
+pub fn run(market: &str, tx: &str, config: &Config, report: &mut Report) -> Result<()> {
+    let market = SavedMarket::load(market, config)?.market;
+    let text = read_text(tx)?;
+    let (tx, inspection, format) = match deserialize_hex::<Transaction>(&text) {
+        Ok(tx) => {
+            let inspection = inspect_pool_spend(&market, &tx, None)?;
+            (tx, inspection, "transaction")
+        }
+        Err(_) => {
+            let psbt = Psbt::from_str(&text).with_context(|| {
+                CliError::InvalidArgument(
+                    "Expected a raw transaction (hex) or a PSBT (base64)".to_string(),
+                )
+            })?;
+            let inspection = inspect_pool_psbt(&market, &psbt)?;
+            (psbt.unsigned_tx, inspection, "psbt")
+        }
+    };
+
+    report.title("Pool Transaction");
+    report.rule();
+    report.labeled("Txid", inspection.txid);
+    report.labeled(
+        "Pool Input",
+        match inspection.spends_pool_utxo {
+            Some(true) => format!("{} (pool UTXO)", inspection.pool_input),
+            Some(false) => format!(
+                "{} {}",
+                inspection.pool_input,
+                "(not the recorded pool UTXO)".red()
+            ),
+            None => inspection.pool_input.to_string(),
+        },
+    );
+    report.labeled("Leaf", describe_leaf(&market, inspection.leaf));
+    report.labeled(
+        "CTV Template",
+        format!(
+            "{} ({})",
+            check(inspection.template_matches, "matches", "does not match"),
+            inspection.ctv_hash
+        ),
+    );
+    if let Some(valid) = inspection.control_block_valid {
+        report.labeled("Control Block", check(valid, "valid", "invalid"));
+    }
+    if let Some(valid) = inspection.oracle_signature_valid {
+        report.labeled("Oracle Signature", check(valid, "valid", "invalid"));
+    }
+
+    for payout in &inspection.payouts {
+        report.labeled(
+            &format!("Bet {}", payout.bet_index),
+            format!(
+                "{} sats on {} to {}: {} sats",
+                payout.amount,
+                market.outcomes[payout.outcome_index].label,
+                payout.payout_address,
+                payout.payout
+            ),
+        );
+    }
+    if inspection.administrator_fee > 0 {
+        report.labeled(
+            "Administrator Fee",
+            format!("{} sats", inspection.administrator_fee),
+        );
+    }
+    for &index in &inspection.unmatched_outputs {
+        let output = &tx.output[index];
+        let address = Address::from_script(&output.script_pubkey, market.network)
+            .map_or_else(|_| output.script_pubkey.to_hex_string(), |a| a.to_string());
+        report.labeled(
+            &format!("Unexpected Output {index}"),
+            format!("{} sats to {}", output.value.to_sat(), address).red(),
+        );
+    }
+    report.labeled(
+        "Amounts",
+        format!(
+            "{} sats in, {} sats out",
+            inspection.input_amount, inspection.output_amount
+        ),
+    );
+    report.labeled(
+        "Fee",
+        match inspection.fee {
+            Some(fee) => format!("{fee} sats"),
+            None => "outputs exceed the pool".red().to_string(),
+        },
+    );
+
+    if inspection.witness.is_empty() {
+        report.labeled("Witness", "none (unsigned)".bright_black());
+    }
+    for (index, element) in inspection.witness.iter().enumerate() {
+        let role = match element.role {
+            WitnessRole::OracleSignature => "oracle signature",
+            WitnessRole::LeafScript => "leaf script",
+            WitnessRole::ControlBlock => "control block",
+            WitnessRole::StackItem => "stack item",
+        };
+        report.labeled(&format!("Witness {index} ({role})"), &element.hex);
+    }
+    report.rule();
+
+    report.set("market_id", &market.market_id);
+    report.set("format", format);
+    report.set("inspection", &inspection);
+    Ok(())
+}
+
+/// Describes the spent leaf, `payout A (...)` or `escape`
+fn describe_leaf(market: &PredictionMarket, leaf: Option<SpentLeaf>) -> ColoredString {
+    match leaf {
+        Some(SpentLeaf::Payout { outcome_index }) => {
+            let outcome = &market.outcomes[outcome_index];
+            format!("payout {} ({})", outcome.label, outcome.outcome).normal()
+        }
+        Some(SpentLeaf::Escape) => "escape".normal(),
+        None => "no leaf of the pool".red(),
+    }
+}
+
+fn check(ok: bool, yes: &str, no: &str) -> ColoredString {
+    if ok {
+        yes.green()
+    } else {
+        no.red()
+    }
+}
@@ -2,16 +2,34 @@
 //!
 //! Command-line interface for creating and managing Nostr-based Bitcoin prediction markets.
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+mod config;
+mod deposit;
+mod graph;
+mod inspect;
+mod market;
+mod oracle;
+mod output;
+mod store;
+mod withdraw;
+
+use anyhow::{bail, Context, Result};
+use clap::{error::ErrorKind, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use colored::*;
-use markstr_core::{utils::*, PredictionMarket};
+use market::{find_outcome, market_json, read_text, SavedMarket};
+use markstr_core::{
+    network::network_name, utils::*, AddressScheme, MarketState, NostrEvent, PredictionMarket,
+};
+use output::{CliError, OutputFormat, Report};
+use serde_json::json;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "markstr")]
 #[command(about = "Nostr-based Bitcoin prediction markets using CSFS and Taproot")]
 #[command(version)]
 struct Cli {
+    #[command(flatten)]
+    config: config::Config,
     #[command(subcommand)]
     command: Commands,
 }
@@ -23,33 +41,32 @@ enum Commands {
         /// Market question
         #[arg(short, long)]
         question: String,
-        /// Outcome A description
-        #[arg(short = 'a', long)]
-        outcome_a: String,
-        /// Outcome B description
-        #[arg(short = 'b', long)]
-        outcome_b: String,
-        /// Oracle's Nostr public key (hex)
+        /// Outcome description (repeat for each outcome, 2 to 32)
+        #[arg(long = "outcome", required = true)]
+        outcomes: Vec<String>,
+        /// Oracle's Nostr public key (npub or hex)
         #[arg(short, long)]
         oracle: String,
         /// Settlement timestamp (Unix timestamp)
         #[arg(short, long)]
         settlement: u64,
+        /// Also print the legacy OP_CHECKSIGFROMSTACK address of earlier releases
+        #[arg(long)]
+        legacy_address: bool,
     },
-    /// Show market information
+    /// Show a saved market
     Info {
-        /// Market ID
+        /// Market ID (hex, note, nevent or naddr)
         market_id: String,
     },
+    /// List the saved markets
+    List,
     /// Generate a new market ID
     GenerateId,
-    /// Validate a Bitcoin address
+    /// Validate a Bitcoin address for the selected network
     ValidateAddress {
         /// Bitcoin address to validate
         address: String,
-        /// Network (0=Bitcoin, 1=Testnet, 2=Signet, 3=Regtest)
-        #[arg(short, long, default_value = "2")]
-        network: u8,
     },
     /// Convert between Bitcoin and satoshis
     Convert {
@@ -63,122 +80,486 @@ enum Commands {
         /// Message to hash
         message: String,
     },
+    /// Register a bet, opening the market for bets if needed
+    Bet {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Label (A, B, ...) or index of the outcome
+        #[arg(short, long)]
+        outcome: String,
+        /// Amount bet in satoshis
+        #[arg(short, long)]
+        amount: u64,
+        /// Address receiving the payout or refund
+        #[arg(short, long)]
+        payout_address: String,
+        /// UTXO funding the bet (TXID:VOUT), for the record
+        #[arg(long)]
+        utxo: Option<String>,
+    },
+    /// Deposit round: build, sign and combine the pool deposit
+    Deposit {
+        #[command(subcommand)]
+        command: deposit::DepositCommand,
+    },
+    /// Settle a funded market with the oracle attestation
+    Settle {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Signed outcome event (JSON or file), defaults to the attestation saved with the market
+        #[arg(short, long)]
+        event: Option<String>,
+    },
+    /// Pay out the winning bets of a settled market
+    Payout {
+        #[command(subcommand)]
+        command: withdraw::PayoutCommand,
+    },
+    /// Refund every bet through the escape path
+    Escape {
+        #[command(subcommand)]
+        command: withdraw::EscapeCommand,
+    },
+    /// Explain a transaction or PSBT spending the pool of a market
+    Inspect {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Raw transaction (hex) or PSBT (base64), or a file holding it
+        #[arg(short, long)]
+        tx: String,
+    },
+    /// Export every transaction of a market and its spend graph (Graphviz DOT)
+    Graph {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Deposit transaction (hex or file), defaults to the recorded pool UTXO,
+        /// or a template before the deposit round
+        #[arg(short, long)]
+        deposit: Option<String>,
+        /// Write the DOT rendering to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Oracle tools: list outcome ids and attest the winning outcome
+    Oracle {
+        #[command(subcommand)]
+        command: oracle::OracleCommand,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = match Cli::command().try_get_matches() {
+        Ok(matches) => matches,
+        Err(error) => exit_on_parse_error(error),
+    };
+    let command = command_name(&matches);
+    let cli = Cli::from_arg_matches(&matches)?;
+    let format = cli.config.output();
+    let mut report = Report::new(format);
+
+    match run(cli, &mut report) {
+        Ok(()) => {
+            report.finish(&command);
+            Ok(())
+        }
+        Err(error) if format == OutputFormat::Json => {
+            output::print_json_error(&command, &error);
+            std::process::exit(1);
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Name of the command run, subcommands included (`deposit sign`)
+fn command_name(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, subcommand)) = matches.subcommand() {
+        names.push(name);
+        matches = subcommand;
+    }
+    names.join(" ")
+}
+
+/// Exits on invalid arguments, with a JSON document when JSON output was asked for
+fn exit_on_parse_error(error: clap::Error) -> ! {
+    let help = matches!(
+        error.kind(),
+        ErrorKind::DisplayHelp | ErrorKind::DisplayVersion
+    );
+    if help || !output::json_requested(std::env::args()) {
+        error.exit();
+    }
+    // First paragraph of the message, without the usage and help hints
+    let message = error.to_string();
+    let message = message
+        .split("\n\n")
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    output::print_json_error(
+        "markstr",
+        &CliError::InvalidArgument(message.trim_start_matches("error: ").to_string()).into(),
+    );
+    std::process::exit(2);
+}
+
+fn run(cli: Cli, report: &mut Report) -> Result<()> {
+    let config = &cli.config;
+    let store_path = config.store_path();
 
     match cli.command {
         Commands::Create {
             question,
-            outcome_a,
-            outcome_b,
+            outcomes,
             oracle,
             settlement,
+            legacy_address,
         } => {
-            println!("{}", "Creating new prediction market...".green().bold());
-
-            let market = PredictionMarket::new(
-                question.clone(),
-                outcome_a.clone(),
-                outcome_b.clone(),
-                oracle.clone(),
-                settlement,
-            )?;
-
-            let market_address = market.get_market_address()?;
-
-            println!();
-            println!("{}", "Market Created Successfully!".green().bold());
-            println!("{}", "═".repeat(50).bright_black());
-            println!("{}: {}", "Market ID".yellow().bold(), market.market_id);
-            println!("{}: {}", "Question".yellow().bold(), question);
-            println!("{}: {}", "Outcome A".yellow().bold(), outcome_a);
-            println!("{}: {}", "Outcome B".yellow().bold(), outcome_b);
-            println!("{}: {}", "Oracle PubKey".yellow().bold(), oracle);
-            println!(
-                "{}: {}",
-                "Settlement Time".yellow().bold(),
-                format_timestamp(settlement)
+            let (network, signet_challenge) = config.network()?;
+            config.require_covenants(network, signet_challenge.as_ref())?;
+            report.title("Creating new prediction market...");
+
+            let mut market = PredictionMarket::new(question.clone(), outcomes, oracle, settlement)?;
+            market.set_network(network, signet_challenge)?;
+            store::open_store(&store_path)?.save_market(&market)?;
+
+            report.line("");
+            report.title("Market Created Successfully!");
+            report.rule();
+            report.labeled("Market ID", &market.market_id);
+            report.labeled("Market nevent", market.nevent()?);
+            report.labeled("Market naddr", market.naddr()?);
+            report.labeled("Saved To", store_path.display());
+            report.set("saved_to", &store_path);
+            report.labeled("Question", &question);
+            for outcome in &market.outcomes {
+                report.labeled(&format!("Outcome {}", outcome.label), &outcome.outcome);
+            }
+            report.labeled("Oracle PubKey", market.oracle_npub()?);
+            report.labeled("Settlement Time", format_timestamp(settlement));
+            report.labeled(
+                "Network",
+                config::describe(market.network, market.signet_challenge.as_ref()),
             );
-            println!("{}: {}", "Network".yellow().bold(), "Signet");
-            println!("{}: {}", "Market Address".cyan().bold(), market_address);
-            println!("{}: {}", "Status".yellow().bold(), market.get_status());
-            println!("{}", "═".repeat(50).bright_black());
-            println!();
-            println!("{}", "Send bets to the market address above.".bright_blue());
-            println!(
-                "{}",
-                "Winners will be paid out proportionally after settlement.".bright_blue()
+            report.line(format!(
+                "{}: {}",
+                "Pool Address".cyan().bold(),
+                "committed once bets are registered".bright_black()
+            ));
+            if legacy_address {
+                let legacy = market.pool_descriptor(AddressScheme::Legacy)?;
+                report.labeled("Legacy Address", &legacy.address);
+                report.set("legacy_pool", legacy);
+            }
+            report.labeled("Status", market.get_status());
+            report.set("market", market_json(&market)?);
+            report.rule();
+            report.line("");
+            if legacy_address {
+                report.line(
+                    "Do not send funds to the legacy address: it cannot be spent by the withdraw module and anyone can spend it."
+                        .red()
+                        .bold(),
+                );
+            }
+            report.line(
+                "Bettors register their bets, then deposit into the pool address.".bright_blue(),
             );
+            report.line("Winners will be paid out proportionally after settlement.".bright_blue());
         }
 
         Commands::Info { market_id } => {
-            println!("{}", format!("Market Info: {}", market_id).green().bold());
-            println!("{}", "This would show stored market information.".yellow());
-            println!(
-                "{}",
-                "Note: Full market persistence not implemented in this demo.".bright_black()
+            let store = store::open_store(&store_path)?;
+            let market = store::find_market(store.as_ref(), &market_id)?;
+            report.title("Market Info");
+            report.rule();
+            report.labeled("Market ID", &market.market_id);
+            report.labeled("Market nevent", market.nevent()?);
+            report.labeled("Question", &market.question);
+            for (index, outcome) in market.outcomes.iter().enumerate() {
+                report.labeled(
+                    &format!("Outcome {}", outcome.label),
+                    format!(
+                        "{} ({} bets, {} sats)",
+                        outcome.outcome,
+                        market.bets[index].len(),
+                        market.get_total(index)
+                    ),
+                );
+            }
+            report.labeled("Oracle PubKey", market.oracle_npub()?);
+            report.labeled(
+                "Settlement Time",
+                format_timestamp(market.settlement_timestamp),
             );
+            report.labeled(
+                "Network",
+                config::describe(market.network, market.signet_challenge.as_ref()),
+            );
+            report.labeled("Total Amount", market.total_amount);
+            let bets = market
+                .bets
+                .iter()
+                .enumerate()
+                .flat_map(|(outcome, bets)| bets.iter().map(move |bet| (outcome, bet)));
+            for (index, (outcome, bet)) in bets.enumerate() {
+                report.labeled(
+                    &format!("Bet {index}"),
+                    format!(
+                        "{} sats on {} to {}",
+                        bet.amount, market.outcomes[outcome].label, bet.payout_address
+                    ),
+                );
+            }
+            if let Some(utxo) = market.market_utxo {
+                report.labeled("Pool UTXO", utxo);
+            }
+            report.labeled("Status", market.get_status());
+            report.set("market", market_json(&market)?);
+
+            let session = store.load_session(&market.market_id)?;
+            if let Some(session) = &session {
+                report.labeled(
+                    "Deposit Round",
+                    format!("{:?} (round {})", session.phase(), session.round()),
+                );
+            }
+            report.set(
+                "deposit_round",
+                session
+                    .map(|session| json!({ "phase": session.phase(), "round": session.round() })),
+            );
+            let attestation = store.load_attestation(&market.market_id)?;
+            if let Some(attestation) = &attestation {
+                report.labeled(
+                    "Attested Outcome",
+                    &market.outcomes[attestation.outcome_index].outcome,
+                );
+            }
+            report.set(
+                "attestation",
+                attestation.map(|attestation| {
+                    json!({
+                        "outcome_index": attestation.outcome_index,
+                        "outcome_id": attestation.outcome_id,
+                        "csfs_signature": hex::encode(&attestation.csfs_signature),
+                        "event": attestation.event,
+                    })
+                }),
+            );
+            report.rule();
+        }
+
+        Commands::List => {
+            let markets = store::open_store(&store_path)?.list_markets()?;
+            if markets.is_empty() {
+                report.line("No saved markets.".yellow());
+            }
+            for market in &markets {
+                report.line(format!(
+                    "{} {} {}",
+                    market.market_id.cyan(),
+                    format!("[{}]", market.state).bright_black(),
+                    market.question
+                ));
+            }
+            let markets: Vec<_> = markets
+                .iter()
+                .map(|market| {
+                    json!({
+                        "market_id": market.market_id,
+                        "question": market.question,
+                        "state": market.state.name(),
+                        "network": network_name(market.network),
+                    })
+                })
+                .collect();
+            report.set("markets", markets);
         }
 
         Commands::GenerateId => {
             let id = generate_market_id();
-            println!("{}: {}", "Generated Market ID".green().bold(), id.cyan());
+            report.line(format!(
+                "{}: {}",
+                "Generated Market ID".green().bold(),
+                id.cyan()
+            ));
+            report.set("market_id", id);
         }
 
-        Commands::ValidateAddress { address, network } => {
-            let network = u8_to_network(network)?;
+        Commands::ValidateAddress { address } => {
+            let (network, _) = config.network()?;
+            let network_name = network_name(network);
             let is_valid = validate_address(&address, network);
 
             if is_valid {
-                println!(
+                report.line(format!(
                     "{}: {} is {} for {}",
                     "Address Validation".green().bold(),
                     address.cyan(),
                     "valid".green(),
-                    format!("{:?}", network).yellow()
-                );
+                    network_name.yellow()
+                ));
             } else {
-                println!(
+                report.line(format!(
                     "{}: {} is {} for {}",
                     "Address Validation".red().bold(),
                     address.cyan(),
                     "invalid".red(),
-                    format!("{:?}", network).yellow()
-                );
+                    network_name.yellow()
+                ));
             }
+            report.set("address", address);
+            report.set("network", network_name);
+            report.set("valid", is_valid);
         }
 
-        Commands::Convert { amount, unit } => match unit.to_lowercase().as_str() {
-            "btc" => {
-                let satoshis = btc_to_satoshi(amount);
-                println!(
+        Commands::Convert { amount, unit } => {
+            let (satoshis, btc) = match unit.to_lowercase().as_str() {
+                "btc" => (btc_to_satoshi(amount), amount),
+                "sat" | "sats" => (amount as u64, satoshi_to_btc(amount as u64)),
+                _ => bail!(CliError::InvalidArgument(
+                    "Unit must be 'btc' or 'sat'".to_string()
+                )),
+            };
+            if unit.eq_ignore_ascii_case("btc") {
+                report.line(format!(
                     "{}: {} BTC = {} satoshis",
                     "Conversion".green().bold(),
-                    amount.to_string().cyan(),
+                    btc.to_string().cyan(),
                     satoshis.to_string().yellow()
-                );
-            }
-            "sat" | "sats" => {
-                let btc = satoshi_to_btc(amount as u64);
-                println!(
+                ));
+            } else {
+                report.line(format!(
                     "{}: {} satoshis = {} BTC",
                     "Conversion".green().bold(),
-                    (amount as u64).to_string().cyan(),
+                    satoshis.to_string().cyan(),
                     btc.to_string().yellow()
-                );
-            }
-            _ => {
-                println!("{}: Unit must be 'btc' or 'sat'", "Error".red().bold());
+                ));
             }
-        },
+            report.set("btc", btc);
+            report.set("sats", satoshis);
+        }
 
         Commands::Hash { message } => {
             let hash = sha256_hash(&message);
-            println!("{}: {}", "SHA256 Hash".green().bold(), hash.cyan());
+            report.line(format!("{}: {}", "SHA256 Hash".green().bold(), hash.cyan()));
+            report.set("message", message);
+            report.set("sha256", hash);
         }
+
+        Commands::Bet {
+            market,
+            outcome,
+            amount,
+            payout_address,
+            utxo,
+        } => {
+            let mut saved = SavedMarket::load(&market, config)?;
+            let outcome = find_outcome(&saved.market, &outcome)?;
+            let network = saved.market.network;
+            if !validate_address(&payout_address, network) {
+                bail!(CliError::InvalidArgument(format!(
+                    "Payout address {payout_address} is not valid for {}",
+                    network_name(network)
+                )));
+            }
+            let (txid, vout) = match &utxo {
+                Some(utxo) => {
+                    let invalid = || {
+                        CliError::InvalidArgument(format!(
+                            "Invalid UTXO {utxo}, expected TXID:VOUT"
+                        ))
+                    };
+                    let (txid, vout) = utxo.split_once(':').ok_or_else(invalid)?;
+                    (txid.to_string(), vout.parse().with_context(invalid)?)
+                }
+                None => (String::new(), 0),
+            };
+
+            if saved.market.state == MarketState::Draft {
+                saved.market.open_registration()?;
+            }
+            saved
+                .market
+                .place_bet(outcome, amount, payout_address, txid, vout)?;
+            saved.save()?;
+
+            let market = &saved.market;
+            report.title("Bet registered");
+            report.field("Bet Index", "bet_index", market.all_bets().count() - 1);
+            report.labeled("Outcome", &market.outcomes[outcome].outcome);
+            report.set("outcome_index", outcome);
+            report.labeled("Amount", format!("{amount} sats"));
+            report.set("amount", amount);
+            report.labeled("Total Amount", format!("{} sats", market.total_amount));
+            report.set("market", market_json(market)?);
+        }
+
+        Commands::Deposit { command } => deposit::run(command, config, report)?,
+
+        Commands::Settle { market, event } => {
+            let mut saved = SavedMarket::load(&market, config)?;
+            let event: NostrEvent = match event {
+                Some(event) => serde_json::from_str(&read_text(&event)?).with_context(|| {
+                    CliError::InvalidArgument("Invalid outcome event".to_string())
+                })?,
+                None => {
+                    saved
+                        .store()?
+                        .map(|store| store.load_attestation(&saved.market.market_id))
+                        .transpose()?
+                        .flatten()
+                        .ok_or_else(|| {
+                            CliError::MissingInput(
+                                "No saved attestation: pass the outcome event with --event"
+                                    .to_string(),
+                            )
+                        })?
+                        .event
+                }
+            };
+
+            saved.market.settle_with_attestation(&event)?;
+            saved.save()?;
+            let winning_outcome = saved
+                .market
+                .winning_outcome()
+                .ok_or_else(|| CliError::InvalidState("Market is not settled".to_string()))?;
+            report.title("Market settled");
+            report.labeled(
+                "Winning Outcome",
+                &saved.market.outcomes[winning_outcome].outcome,
+            );
+            report.labeled(
+                "Winning Bets",
+                format!("{} bets", saved.market.bets[winning_outcome].len()),
+            );
+            report.set("winning_outcome", winning_outcome);
+            report.set("market", market_json(&saved.market)?);
+        }
+
+        Commands::Payout { command } => withdraw::run_payout(command, config, report)?,
+
+        Commands::Escape { command } => withdraw::run_escape(command, config, report)?,
+
+        Commands::Inspect { market, tx } => inspect::run(&market, &tx, config, report)?,
+
+        Commands::Graph {
+            market,
+            deposit,
+            out,
+        } => graph::run(&market, deposit.as_deref(), out.as_deref(), config, report)?,
+
+        Commands::Oracle { command } => oracle::run(command, config, report)?,
     }
 
     Ok(())
@@ -0,0 +1,175 @@
+//! # Market Files
+//!
+//! The lifecycle commands read a market from its JSON file or from the store,
+//! and write it back where it came from. Transactions, PSBTs and deposit
+//! contributions are exchanged as files or printed to the terminal.
+
+use crate::{
+    config::Config,
+    output::CliError,
+    store::{find_market, open_store},
+};
+use anyhow::{Context, Result};
+use markstr_core::{network::network_name, AddressScheme, MarketStore, PredictionMarket};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Where a market was read from
+enum Location {
+    /// A market JSON file
+    File(PathBuf),
+    /// The market store at this path
+    Store(PathBuf),
+}
+
+/// A market read from a file or from the store
+pub struct SavedMarket {
+    pub market: PredictionMarket,
+    location: Location,
+}
+
+impl SavedMarket {
+    /// Reads a market from its JSON file, or from the store, and checks that it
+    /// is on the selected network
+    pub fn load(market: &str, config: &Config) -> Result<Self> {
+        let saved = Self::read(market, &config.store_path())?;
+        config.check_market(&saved.market)?;
+        Ok(saved)
+    }
+
+    fn read(market: &str, store_path: &Path) -> Result<Self> {
+        let path = Path::new(market);
+        if path.is_file() {
+            let json = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let market = serde_json::from_str(&json)
+                .with_context(|| format!("Invalid market in {}", path.display()))?;
+            return Ok(Self {
+                market,
+                location: Location::File(path.to_path_buf()),
+            });
+        }
+        let store = open_store(store_path)?;
+        Ok(Self {
+            market: find_market(store.as_ref(), market)?,
+            location: Location::Store(store_path.to_path_buf()),
+        })
+    }
+
+    /// The store holding the market, `None` for a market file
+    pub fn store(&self) -> Result<Option<Box<dyn MarketStore>>> {
+        match &self.location {
+            Location::File(_) => Ok(None),
+            Location::Store(path) => open_store(path).map(Some),
+        }
+    }
+
+    /// Writes the market back to its file or to the store
+    pub fn save(&self) -> Result<()> {
+        match &self.location {
+            Location::File(path) => fs::write(path, serde_json::to_string_pretty(&self.market)?)
+                .with_context(|| format!("Failed to write {}", path.display())),
+            Location::Store(path) => Ok(open_store(path)?.save_market(&self.market)?),
+        }
+    }
+}
+
+/// Finds an outcome by label (case-insensitive) or index
+pub fn find_outcome(market: &PredictionMarket, outcome: &str) -> Result<usize> {
+    market
+        .outcomes
+        .iter()
+        .position(|o| o.label.eq_ignore_ascii_case(outcome))
+        .or_else(|| {
+            outcome
+                .parse::<usize>()
+                .ok()
+                .filter(|&i| i < market.outcomes.len())
+        })
+        .ok_or_else(|| {
+            let labels: Vec<&str> = market.outcomes.iter().map(|o| o.label.as_str()).collect();
+            CliError::InvalidArgument(format!(
+                "Unknown outcome {outcome}, expected one of {}",
+                labels.join(", ")
+            ))
+            .into()
+        })
+}
+
+/// Reads `value` from the file it names, or takes it literally
+pub fn read_text(value: &str) -> Result<String> {
+    let path = Path::new(value);
+    if path.is_file() {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        return Ok(text.trim().to_string());
+    }
+    Ok(value.trim().to_string())
+}
+
+/// JSON description of a market, shared by the commands showing one.
+///
+/// The pool, with its address and tapleaf scripts, is only described once
+/// bets are registered.
+pub fn market_json(market: &PredictionMarket) -> Result<Value> {
+    let outcomes = market
+        .outcomes
+        .iter()
+        .enumerate()
+        .map(|(index, outcome)| {
+            Ok(json!({
+                "index": index,
+                "label": outcome.label,
+                "outcome": outcome.outcome,
+                "id": outcome.nostr_id(),
+                "note": outcome.note()?,
+                "bets": market.bets[index].len(),
+                "total": market.get_total(index),
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let bets: Vec<Value> = market
+        .bets
+        .iter()
+        .enumerate()
+        .flat_map(|(outcome, bets)| bets.iter().map(move |bet| (outcome, bet)))
+        .enumerate()
+        .map(|(index, (outcome, bet))| {
+            json!({
+                "index": index,
+                "outcome_index": outcome,
+                "amount": bet.amount,
+                "payout_address": bet.payout_address,
+                "txid": bet.txid,
+                "vout": bet.vout,
+            })
+        })
+        .collect();
+    let pool = if bets.is_empty() {
+        None
+    } else {
+        Some(market.pool_descriptor(AddressScheme::Pool)?)
+    };
+
+    Ok(json!({
+        "market_id": market.market_id,
+        "nevent": market.nevent()?,
+        "naddr": market.naddr()?,
+        "question": market.question,
+        "outcomes": outcomes,
+        "oracle_pubkey": market.oracle_pubkey,
+        "oracle_npub": market.oracle_npub()?,
+        "settlement_timestamp": market.settlement_timestamp,
+        "network": network_name(market.network),
+        "signet_challenge": market.signet_challenge.as_ref().map(|c| c.to_hex_string()),
+        "state": market.state.name(),
+        "winning_outcome": market.winning_outcome(),
+        "total_amount": market.total_amount,
+        "bets": bets,
+        "pool_utxo": market.market_utxo.map(|utxo| utxo.to_string()),
+        "pool": pool,
+    }))
+}
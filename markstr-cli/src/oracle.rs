@@ -0,0 +1,165 @@
+//! # Oracle Commands
+//!
+//! Attestation toolkit for market oracles: list the outcome ids of a market and
+//! sign the winning one, as a raw CSFS signature for the payout leaf and as a
+//! Nostr event to publish.
+
+use crate::{
+    config::Config,
+    market::{find_outcome, SavedMarket},
+    output::{CliError, Report},
+};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use colored::*;
+use markstr_core::{
+    attestation::attest_outcome,
+    nip19,
+    utils::{format_timestamp, public_key_from_secret},
+};
+use serde_json::json;
+use std::{fs, path::PathBuf};
+
+/// Environment variable holding the oracle secret key
+pub const SECRET_ENV: &str = "MARKSTR_ORACLE_SECRET";
+
+/// Where the oracle secret key is read from
+#[derive(Args)]
+pub struct SecretArgs {
+    /// File holding the oracle secret key (nsec or hex), defaults to $MARKSTR_ORACLE_SECRET
+    #[arg(long)]
+    secret_file: Option<PathBuf>,
+}
+
+impl SecretArgs {
+    /// Loads the 32-byte secret key from the file, or from the environment
+    fn load(&self) -> Result<[u8; 32]> {
+        let secret = match &self.secret_file {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+            None => std::env::var(SECRET_ENV).map_err(|_| {
+                CliError::MissingInput(format!(
+                    "No oracle secret key: pass --secret-file or set {SECRET_ENV}"
+                ))
+            })?,
+        };
+        Ok(nip19::parse_secret_key(&secret)?)
+    }
+}
+
+#[derive(Subcommand)]
+pub enum OracleCommand {
+    /// Show the public key of the oracle secret key
+    Pubkey {
+        #[command(flatten)]
+        secret: SecretArgs,
+    },
+    /// List the outcomes of a market and their event ids
+    Outcomes {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+    },
+    /// Attest the winning outcome of a market
+    Sign {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Label (A, B, ...) or index of the winning outcome
+        #[arg(short, long)]
+        outcome: String,
+        /// Sign before the settlement time of the market
+        #[arg(long)]
+        allow_early: bool,
+        #[command(flatten)]
+        secret: SecretArgs,
+    },
+}
+
+/// Runs an oracle command, markets are looked up in the configured store
+pub fn run(command: OracleCommand, config: &Config, report: &mut Report) -> Result<()> {
+    match command {
+        OracleCommand::Pubkey { secret } => {
+            let pubkey = public_key_from_secret(&secret.load()?)?;
+            let npub = nip19::encode_public_key(&pubkey)?;
+            report.line(format!(
+                "{}: {}",
+                "Oracle PubKey".green().bold(),
+                npub.cyan()
+            ));
+            report.line(format!("{}: {}", "Hex".green().bold(), pubkey));
+            report.set("npub", npub);
+            report.set("pubkey", pubkey);
+        }
+
+        OracleCommand::Outcomes { market } => {
+            let market = SavedMarket::load(&market, config)?.market;
+            report.labeled("Market", market.nevent()?);
+            report.labeled("Question", &market.question);
+            report.rule();
+            let mut outcomes = Vec::new();
+            for (index, outcome) in market.outcomes.iter().enumerate() {
+                report.line(format!(
+                    "{} {}: {} ({})",
+                    format!("Outcome {}", outcome.label).yellow().bold(),
+                    outcome.outcome,
+                    outcome.nostr_id().cyan(),
+                    outcome.note()?
+                ));
+                outcomes.push(json!({
+                    "index": index,
+                    "label": outcome.label,
+                    "outcome": outcome.outcome,
+                    "id": outcome.nostr_id(),
+                    "note": outcome.note()?,
+                }));
+            }
+            report.set("market_id", &market.market_id);
+            report.set("outcomes", outcomes);
+        }
+
+        OracleCommand::Sign {
+            market,
+            outcome,
+            allow_early,
+            secret,
+        } => {
+            let saved = SavedMarket::load(&market, config)?;
+            let market = &saved.market;
+            let outcome_index = find_outcome(market, &outcome)?;
+            let now = chrono::Utc::now().timestamp().max(0) as u64;
+            if now < market.settlement_timestamp && !allow_early {
+                bail!(CliError::InvalidState(format!(
+                    "Market settles at {}, pass --allow-early to sign before",
+                    format_timestamp(market.settlement_timestamp)
+                )));
+            }
+
+            let attestation = attest_outcome(market, outcome_index, &secret.load()?)?;
+            let outcome = &market.outcomes[outcome_index];
+            let csfs_signature = hex::encode(&attestation.csfs_signature);
+            report.title("Outcome attested");
+            report.rule();
+            report.labeled(
+                "Outcome",
+                format!("{} ({})", outcome.outcome, outcome.label),
+            );
+            report.set("market_id", &market.market_id);
+            report.set("outcome_index", outcome_index);
+            report.set("outcome", &outcome.outcome);
+            report.field("Outcome ID", "outcome_id", &attestation.outcome_id);
+            report.field("Outcome note", "note", outcome.note()?);
+            report.labeled("CSFS Signature", csfs_signature.cyan());
+            report.set("csfs_signature", csfs_signature);
+            report.rule();
+            report.line("Nostr event to publish:".bright_blue());
+            report.line(serde_json::to_string_pretty(&attestation.event)?);
+            report.set("event", &attestation.event);
+            if let Some(mut store) = saved.store()? {
+                store.save_attestation(&market.market_id, &attestation)?;
+            }
+        }
+    }
+
+    Ok(())
+}
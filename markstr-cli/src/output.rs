@@ -0,0 +1,266 @@
+//! # Output
+//!
+//! Commands describe their result on a [`Report`]: colored lines for people,
+//! or with `--output json` a single JSON document on stdout:
+//!
+//! ```text
+//! {"version": 1, "command": "deposit sign", "ok": true, "data": {...}}
+//! {"version": 1, "command": "settle", "ok": false,
+//!  "error": {"code": "invalid_state", "message": "...", "causes": [...]}}
+//! ```
+//!
+//! Keys are snake_case and only ever added within a version. Amounts are in
+//! satoshis, scripts and transactions are hex, PSBTs are base64.
+
+use anyhow::Context;
+use clap::ValueEnum;
+use colored::*;
+use markstr_core::{
+    attestation::AttestationError, deposit::DepositError, nip19::Nip19Error, MarketError,
+    StoreError,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::{fmt::Display, fs, path::Path};
+use thiserror::Error;
+
+/// Version of the JSON document layout
+pub const OUTPUT_VERSION: u32 = 1;
+
+/// How command results are printed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored text
+    #[default]
+    Text,
+    /// One JSON document
+    Json,
+}
+
+/// Errors raised by the CLI itself, each with its error code
+#[derive(Debug, Error)]
+pub enum CliError {
+    /// An argument or input file is malformed
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// A key, signature or event the command needs was not provided
+    #[error("{0}")]
+    MissingInput(String),
+    /// The market is not in a state allowing the command
+    #[error("{0}")]
+    InvalidState(String),
+    /// No saved market matches
+    #[error("{0}")]
+    NotFound(String),
+}
+
+impl CliError {
+    /// Machine-readable code of the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::MissingInput(_) => "missing_input",
+            Self::InvalidState(_) => "invalid_state",
+            Self::NotFound(_) => "market_not_found",
+        }
+    }
+}
+
+/// Error code of a command failure.
+///
+/// Errors raised by the CLI come first, then the core errors, found either as
+/// the error itself or as the source of another error.
+pub fn error_code(error: &anyhow::Error) -> &'static str {
+    // Context values are only reachable through anyhow's downcast
+    if let Some(error) = error.downcast_ref::<CliError>() {
+        return error.code();
+    }
+    error
+        .chain()
+        .find_map(|cause| {
+            if let Some(error) = cause.downcast_ref::<MarketError>() {
+                Some(error.code())
+            } else if let Some(error) = cause.downcast_ref::<StoreError>() {
+                Some(error.code())
+            } else if let Some(error) = cause.downcast_ref::<DepositError>() {
+                Some(error.code())
+            } else if cause.is::<Nip19Error>() {
+                Some("nip19")
+            } else if cause.is::<AttestationError>() {
+                Some("invalid_attestation")
+            } else if cause.is::<std::io::Error>() {
+                Some("io")
+            } else if cause.is::<serde_json::Error>() {
+                Some("json")
+            } else {
+                None
+            }
+        })
+        .unwrap_or("error")
+}
+
+/// The result of a command
+pub struct Report {
+    format: OutputFormat,
+    data: Map<String, Value>,
+}
+
+impl Report {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            data: Map::new(),
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+
+    /// Prints a heading
+    pub fn title(&self, title: &str) {
+        if self.is_text() {
+            println!("{}", title.green().bold());
+        }
+    }
+
+    /// Prints a separator
+    pub fn rule(&self) {
+        if self.is_text() {
+            println!("{}", "═".repeat(50).bright_black());
+        }
+    }
+
+    /// Prints a line of text, left out of the JSON document
+    pub fn line(&self, line: impl Display) {
+        if self.is_text() {
+            println!("{line}");
+        }
+    }
+
+    /// A labeled line of text, left out of the JSON document
+    pub fn labeled(&self, label: &str, text: impl Display) {
+        if self.is_text() {
+            println!("{}: {}", label.yellow().bold(), text);
+        }
+    }
+
+    /// A labeled value: `Label: value` as text, `"key": value` in JSON
+    pub fn field(&mut self, label: &str, key: &str, value: impl Serialize + Display) {
+        if self.is_text() {
+            println!("{}: {}", label.yellow().bold(), value);
+        }
+        self.set(key, value);
+    }
+
+    /// A value of the JSON document only
+    pub fn set(&mut self, key: &str, value: impl Serialize) {
+        if !self.is_text() {
+            let value = serde_json::to_value(value).unwrap_or(Value::Null);
+            self.data.insert(key.to_string(), value);
+        }
+    }
+
+    /// A transaction, PSBT or contribution: written to `out` if given, and
+    /// otherwise printed. `value` is its JSON form, in the document either way.
+    pub fn artifact(
+        &mut self,
+        label: &str,
+        key: &str,
+        contents: &str,
+        value: Value,
+        out: Option<&Path>,
+    ) -> anyhow::Result<()> {
+        if let Some(path) = out {
+            fs::write(path, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            self.labeled(label, path.display());
+            self.set("out", path);
+        } else if self.is_text() {
+            println!("{}:", label.yellow().bold());
+            println!("{contents}");
+        }
+        self.set(key, value);
+        Ok(())
+    }
+
+    /// Prints the JSON document of a successful command
+    pub fn finish(self, command: &str) {
+        if !self.is_text() {
+            print_json(&json!({
+                "version": OUTPUT_VERSION,
+                "command": command,
+                "ok": true,
+                "data": self.data,
+            }));
+        }
+    }
+}
+
+/// Whether the raw command line asks for JSON output, for errors raised
+/// before the arguments are parsed
+pub fn json_requested(args: impl IntoIterator<Item = String>) -> bool {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--output=json" || (arg == "--output" && args.next().as_deref() == Some("json")) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Prints the JSON document of a failed command
+pub fn print_json_error(command: &str, error: &anyhow::Error) {
+    let causes: Vec<String> = error.chain().skip(1).map(ToString::to_string).collect();
+    print_json(&json!({
+        "version": OUTPUT_VERSION,
+        "command": command,
+        "ok": false,
+        "error": {
+            "code": error_code(error),
+            "message": error.to_string(),
+            "causes": causes,
+        },
+    }));
+}
+
+fn print_json(document: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(document).unwrap_or_default()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code() {
+        let error = anyhow::Error::new(MarketError::InvalidState("settled".to_string()))
+            .context("Failed to build the payout");
+        assert_eq!(error_code(&error), "invalid_state");
+
+        // Errors raised by the CLI take precedence
+        let error = anyhow::Error::new(StoreError::NotFound("abc".to_string()))
+            .context(CliError::InvalidArgument("Bad market".to_string()));
+        assert_eq!(error_code(&error), "invalid_argument");
+
+        let error = anyhow::Error::new(std::io::Error::other("disk full")).context("Saving");
+        assert_eq!(error_code(&error), "io");
+        assert_eq!(error_code(&anyhow::anyhow!("Unexpected")), "error");
+    }
+
+    #[test]
+    fn test_json_requested() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(json_requested(args(&[
+            "markstr", "info", "--output", "json"
+        ])));
+        assert!(json_requested(args(&["markstr", "--output=json", "list"])));
+        assert!(!json_requested(args(&[
+            "markstr", "--output", "text", "json"
+        ])));
+        assert!(!json_requested(args(&["markstr", "hash", "json"])));
+    }
+}
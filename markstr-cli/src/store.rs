@@ -0,0 +1,58 @@
+//! # Saved Markets
+//!
+//! Opens the market store shared by the commands and finds markets in it.
+
+use crate::output::CliError;
+use anyhow::Result;
+use markstr_core::{nip19, JsonStore, MarketStore, PredictionMarket, SqliteStore};
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the default store location
+pub const STORE_ENV: &str = "MARKSTR_STORE";
+
+/// Default store: `$MARKSTR_STORE`, else `~/.markstr`
+pub fn default_store_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(STORE_ENV) {
+        return path.into();
+    }
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".markstr")
+}
+
+/// Opens the store at `path`: a SQLite database for `.db`, `.sqlite` and
+/// `.sqlite3` files, a JSON directory otherwise
+pub fn open_store(path: &Path) -> Result<Box<dyn MarketStore>> {
+    let sqlite = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext, "db" | "sqlite" | "sqlite3"));
+    Ok(if sqlite {
+        Box::new(SqliteStore::open(path)?)
+    } else {
+        Box::new(JsonStore::open(path)?)
+    })
+}
+
+/// Finds a saved market by id, given as hex, `note`, `nevent` or `naddr`
+pub fn find_market(store: &dyn MarketStore, market_id: &str) -> Result<PredictionMarket> {
+    let market_id = market_id.trim();
+    if market_id.starts_with(nip19::NADDR) {
+        let address = nip19::AddressPointer::decode(market_id)?;
+        return store
+            .list_markets()?
+            .into_iter()
+            .find(|market| {
+                market.oracle_pubkey == address.author
+                    && market.announcement_event().identifier() == Some(&address.identifier)
+            })
+            .ok_or_else(|| CliError::NotFound(format!("No saved market at {market_id}")).into());
+    }
+    let id = if market_id.starts_with(nip19::NOTE) || market_id.starts_with(nip19::NEVENT) {
+        nip19::parse_event_id(market_id)?
+    } else {
+        market_id.to_string()
+    };
+    Ok(store.load_market(&id)?)
+}
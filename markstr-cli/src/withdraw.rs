@@ -0,0 +1,269 @@
+//! # Withdraw Commands
+//!
+//! Builds and signs the transactions spending the market pool: the payout to
+//! the winning bets once the market is settled, and the escape refunding every
+//! bet once the escape timelock has passed.
+//!
+//! `build` prints the unsigned transaction committed in the pool, `sign` checks
+//! a transaction against that template and attaches the witness.
+
+use crate::{
+    config::Config,
+    market::{read_text, SavedMarket},
+    output::{CliError, Report},
+};
+use anyhow::{Context, Result};
+use bitcoin::{
+    consensus::encode::{deserialize_hex, serialize_hex},
+    Address, Transaction,
+};
+use clap::Subcommand;
+use colored::*;
+use markstr_core::{
+    pool::calculate_ctv_hash_from_transaction,
+    withdraw::{
+        build_withdraw_transaction, sign_withdraw_transaction, WithdrawParams, WithdrawType,
+    },
+};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum PayoutCommand {
+    /// Build the unsigned payout transaction of a settled market
+    Build {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Write the raw transaction to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Attach the oracle signature to the payout transaction
+    Sign {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Unsigned payout transaction, hex or file
+        #[arg(short, long)]
+        tx: String,
+        /// Oracle CSFS signature (hex), defaults to the attestation saved with the market
+        #[arg(short, long)]
+        signature: Option<String>,
+        /// Write the signed transaction to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum EscapeCommand {
+    /// Build the unsigned escape transaction refunding every bet
+    Build {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Write the raw transaction to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Attach the escape witness to the escape transaction
+    Sign {
+        /// Market JSON file, or id of a saved market
+        #[arg(short, long)]
+        market: String,
+        /// Unsigned escape transaction, hex or file
+        #[arg(short, long)]
+        tx: String,
+        /// Write the signed transaction to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+/// Runs a payout command, markets are looked up in the configured store
+pub fn run_payout(command: PayoutCommand, config: &Config, report: &mut Report) -> Result<()> {
+    match command {
+        PayoutCommand::Build { market, out } => build(
+            &market,
+            WithdrawType::Payout,
+            out.as_deref(),
+            config,
+            report,
+        ),
+        PayoutCommand::Sign {
+            market,
+            tx,
+            signature,
+            out,
+        } => {
+            let saved = SavedMarket::load(&market, config)?;
+            let signature = match signature {
+                Some(signature) => hex::decode(signature.trim()).map_err(|_| {
+                    CliError::InvalidArgument("Oracle signature must be hex".to_string())
+                })?,
+                None => {
+                    saved
+                        .store()?
+                        .map(|store| store.load_attestation(&saved.market.market_id))
+                        .transpose()?
+                        .flatten()
+                        .ok_or_else(|| {
+                            CliError::MissingInput(
+                                "No saved attestation: pass the oracle signature with --signature"
+                                    .to_string(),
+                            )
+                        })?
+                        .csfs_signature
+                }
+            };
+            sign(
+                &saved,
+                config,
+                WithdrawType::Payout,
+                &tx,
+                Some(&signature),
+                out.as_deref(),
+                report,
+            )
+        }
+    }
+}
+
+/// Runs an escape command, markets are looked up in the configured store
+pub fn run_escape(command: EscapeCommand, config: &Config, report: &mut Report) -> Result<()> {
+    match command {
+        EscapeCommand::Build { market, out } => build(
+            &market,
+            WithdrawType::Escape,
+            out.as_deref(),
+            config,
+            report,
+        ),
+        EscapeCommand::Sign { market, tx, out } => {
+            let saved = SavedMarket::load(&market, config)?;
+            sign(
+                &saved,
+                config,
+                WithdrawType::Escape,
+                &tx,
+                None,
+                out.as_deref(),
+                report,
+            )
+        }
+    }
+}
+
+/// Withdraw parameters spending the recorded pool UTXO
+fn withdraw_params(
+    saved: &SavedMarket,
+    withdraw_type: WithdrawType,
+    config: &Config,
+) -> Result<WithdrawParams> {
+    let market = &saved.market;
+    config.require_market_covenants(market)?;
+    let pool_utxo = market.market_utxo.ok_or_else(|| {
+        CliError::InvalidState(
+            "Market has no pool UTXO yet, combine the deposit transaction first".to_string(),
+        )
+    })?;
+    Ok(WithdrawParams {
+        market: market.clone(),
+        withdraw_type,
+        pool_utxo,
+        fee_rate: None,
+    })
+}
+
+fn build(
+    market: &str,
+    withdraw_type: WithdrawType,
+    out: Option<&Path>,
+    config: &Config,
+    report: &mut Report,
+) -> Result<()> {
+    let saved = SavedMarket::load(market, config)?;
+    let params = withdraw_params(&saved, withdraw_type, config)?;
+    let tx = build_withdraw_transaction(params.clone())?;
+    let pool = saved.market.pool_spend_info()?;
+    let (leaf_script, _) = match params.withdraw_type {
+        WithdrawType::Payout => {
+            let winning_outcome = saved
+                .market
+                .winning_outcome()
+                .ok_or_else(|| CliError::InvalidState("Market is not settled".to_string()))?;
+            pool.payout_leaf(winning_outcome)?
+        }
+        WithdrawType::Escape => pool.escape_leaf()?,
+    };
+
+    report.set("market_id", &saved.market.market_id);
+    report.set("withdraw_type", &params.withdraw_type);
+    report.labeled("Pool UTXO", params.pool_utxo.to_string().cyan());
+    report.set("pool_utxo", params.pool_utxo);
+    let mut outputs = Vec::new();
+    for output in &tx.output {
+        let address = Address::from_script(&output.script_pubkey, saved.market.network)
+            .map_or_else(|_| output.script_pubkey.to_hex_string(), |a| a.to_string());
+        report.labeled(
+            "Output",
+            format!("{} sats to {}", output.value.to_sat(), address),
+        );
+        outputs.push(json!({ "amount": output.value.to_sat(), "address": address }));
+    }
+    report.set("outputs", outputs);
+    if params.withdraw_type == WithdrawType::Escape {
+        report.labeled(
+            "Escape Timelock",
+            format!(
+                "lock time {}, sequence {}",
+                tx.lock_time, tx.input[0].sequence
+            ),
+        );
+    }
+    report.set("lock_time", tx.lock_time.to_consensus_u32());
+    report.set("sequence", tx.input[0].sequence.to_consensus_u32());
+    report.set("txid", tx.compute_txid());
+    report.set(
+        "ctv_hash",
+        hex::encode(calculate_ctv_hash_from_transaction(&tx)),
+    );
+    report.set("leaf_script", leaf_script.to_hex_string());
+    let raw = serialize_hex(&tx);
+    report.artifact(
+        "Unsigned Transaction",
+        "transaction",
+        &raw,
+        raw.clone().into(),
+        out,
+    )
+}
+
+fn sign(
+    saved: &SavedMarket,
+    config: &Config,
+    withdraw_type: WithdrawType,
+    tx: &str,
+    oracle_signature: Option<&[u8]>,
+    out: Option<&Path>,
+    report: &mut Report,
+) -> Result<()> {
+    let params = withdraw_params(saved, withdraw_type, config)?;
+    let tx: Transaction = deserialize_hex(&read_text(tx)?)
+        .with_context(|| CliError::InvalidArgument("Invalid raw transaction".to_string()))?;
+    let signed = sign_withdraw_transaction(tx, &params, oracle_signature)?;
+
+    report.title("Withdraw transaction signed");
+    report.set("market_id", &saved.market.market_id);
+    report.set("withdraw_type", &params.withdraw_type);
+    report.field("Txid", "txid", signed.compute_txid());
+    let raw = serialize_hex(&signed);
+    report.artifact(
+        "Signed Transaction",
+        "transaction",
+        &raw,
+        raw.clone().into(),
+        out,
+    )
+}
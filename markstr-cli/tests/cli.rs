@@ -0,0 +1,512 @@
+//! Runs the `markstr` binary through a whole regtest market, against a
+//! temporary store, and checks the `--output json` documents.
+
+use bitcoin::{
+    absolute::LockTime, consensus::encode::serialize_hex, hashes::Hash, secp256k1::Secp256k1,
+    transaction::Version, Address, Amount, CompressedPublicKey, Network, OutPoint, PrivateKey,
+    Transaction, TxIn, TxOut,
+};
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Oracle secret key (hex)
+const ORACLE_SECRET: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+/// Settlement time of the test markets, in the past so the oracle can sign
+const SETTLEMENT: &str = "1735689600";
+
+/// A temporary directory holding the store and the exchanged files
+struct Workspace {
+    dir: PathBuf,
+}
+
+impl Workspace {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("markstr-cli-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Writes a file of the workspace and returns its path
+    fn write(&self, name: &str, contents: &str) -> String {
+        let path = self.path(name);
+        fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    /// Runs `markstr` on regtest with JSON output, returning the exit code and document
+    fn run(&self, args: &[&str]) -> (i32, Value) {
+        self.run_with(args, &["--network", "regtest", "--allow-inactive-opcodes"])
+    }
+
+    fn run_with(&self, args: &[&str], options: &[&str]) -> (i32, Value) {
+        let output = Command::new(env!("CARGO_BIN_EXE_markstr"))
+            .args(args)
+            .args(options)
+            .arg("--store")
+            .arg(self.path("store"))
+            .args(["--output", "json"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let document: Value = serde_json::from_str(&stdout)
+            .unwrap_or_else(|e| panic!("{args:?} printed invalid JSON ({e}): {stdout}"));
+        assert_eq!(document["version"], 1);
+        (output.status.code().unwrap(), document)
+    }
+
+    /// Runs a command expected to succeed and returns its data
+    fn ok(&self, args: &[&str]) -> Value {
+        let (code, document) = self.run(args);
+        assert_eq!(document["ok"], true, "{args:?} failed: {document}");
+        assert_eq!(code, 0);
+        document["data"].clone()
+    }
+
+    /// Runs a command expected to fail and returns its error code
+    fn error(&self, args: &[&str]) -> String {
+        let (code, document) = self.run(args);
+        assert_eq!(document["ok"], false, "{args:?} succeeded: {document}");
+        assert_ne!(code, 0);
+        document["error"]["code"].as_str().unwrap().to_string()
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A bettor: key, P2WPKH address and the transaction funding its deposit input
+struct Bettor {
+    key: PrivateKey,
+    address: Address,
+    funding: Transaction,
+}
+
+impl Bettor {
+    fn new(index: u8, amount: u64) -> Self {
+        let secp = Secp256k1::new();
+        let key = PrivateKey::from_slice(&[index; 32], Network::Regtest).unwrap();
+        let public_key = CompressedPublicKey::from_private_key(&secp, &key).unwrap();
+        let address = Address::p2wpkh(&public_key, Network::Regtest);
+        let funding = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(bitcoin::Txid::from_byte_array([index; 32]), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(amount),
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        Self {
+            key,
+            address,
+            funding,
+        }
+    }
+
+    /// The deposit input, as TXID:VOUT:SATS:ADDRESS
+    fn input(&self) -> String {
+        format!(
+            "{}:0:{}:{}",
+            self.funding.compute_txid(),
+            self.funding.output[0].value.to_sat(),
+            self.address
+        )
+    }
+}
+
+/// A market with a bet on each outcome, contributed and signed, ready to be combined
+struct SignedMarket {
+    workspace: Workspace,
+    market_id: String,
+    bettors: Vec<Bettor>,
+}
+
+impl SignedMarket {
+    fn new(name: &str) -> Self {
+        let workspace = Workspace::new(name);
+        let market_id = create_market(&workspace);
+
+        let bettors = vec![Bettor::new(1, 200_000), Bettor::new(2, 100_000)];
+        for (outcome, (bettor, amount)) in ["A", "B"]
+            .iter()
+            .zip(bettors.iter().zip(["100000", "50000"]))
+        {
+            let address = bettor.address.to_string();
+            let bet = workspace.ok(&[
+                "bet",
+                "--market",
+                &market_id,
+                "--outcome",
+                outcome,
+                "--amount",
+                amount,
+                "--payout-address",
+                &address,
+            ]);
+            assert_eq!(bet["market"]["state"], "open");
+        }
+
+        for (index, bettor) in bettors.iter().enumerate() {
+            let out = workspace.path(&format!("bet{index}.json"));
+            let contribution = workspace.ok(&[
+                "deposit",
+                "create",
+                "--market",
+                &market_id,
+                "--bet",
+                &index.to_string(),
+                "--input",
+                &bettor.input(),
+                "--change",
+                &bettor.address.to_string(),
+                "--out",
+                out.to_str().unwrap(),
+            ]);
+            assert_eq!(contribution["frozen"], index == 0);
+            assert_eq!(contribution["bet_index"], index);
+            assert!(out.is_file());
+        }
+
+        let contributions = contribution_args(&workspace, bettors.len());
+        for (index, bettor) in bettors.iter().enumerate() {
+            let key = workspace.write(&format!("bet{index}.key"), &bettor.key.to_wif());
+            let out = workspace.path(&format!("bet{index}.psbt"));
+            let mut args = vec![
+                "deposit".to_string(),
+                "sign".to_string(),
+                "--market".to_string(),
+                market_id.clone(),
+                "--bet".to_string(),
+                index.to_string(),
+                "--key-file".to_string(),
+                key,
+                "--out".to_string(),
+                out.display().to_string(),
+            ];
+            args.extend(contributions.clone());
+            let signed = workspace.ok(&strs(&args));
+            assert_eq!(signed["signed_inputs"], 1);
+        }
+
+        Self {
+            workspace,
+            market_id,
+            bettors,
+        }
+    }
+
+    /// Arguments of `deposit combine`, with the funding transactions if `verified`
+    fn combine_args(&self, verified: bool) -> Vec<String> {
+        let mut args = vec![
+            "deposit".to_string(),
+            "combine".to_string(),
+            "--market".to_string(),
+            self.market_id.clone(),
+        ];
+        args.extend(contribution_args(&self.workspace, self.bettors.len()));
+        for index in 0..self.bettors.len() {
+            args.push("--psbt".to_string());
+            args.push(
+                self.workspace
+                    .path(&format!("bet{index}.psbt"))
+                    .display()
+                    .to_string(),
+            );
+        }
+        if verified {
+            for (index, bettor) in self.bettors.iter().enumerate() {
+                let funding = self.workspace.write(
+                    &format!("funding{index}.hex"),
+                    &serialize_hex(&bettor.funding),
+                );
+                args.push("--prevout-tx".to_string());
+                args.push(funding);
+            }
+        }
+        args
+    }
+
+    /// Combines the deposit, checking the funding transactions
+    fn combine(&self) -> Value {
+        let combined = self.workspace.ok(&strs(&self.combine_args(true)));
+        assert_eq!(combined["prevouts_verified"], true);
+        combined
+    }
+
+    fn market(&self) -> Value {
+        self.workspace.ok(&["info", &self.market_id])["market"].clone()
+    }
+}
+
+/// Creates a regtest market on two outcomes and returns its id
+fn create_market(workspace: &Workspace) -> String {
+    let oracle_secret = workspace.write("oracle.key", ORACLE_SECRET);
+    let oracle = workspace.ok(&["oracle", "pubkey", "--secret-file", &oracle_secret]);
+    let oracle = oracle["pubkey"].as_str().unwrap().to_string();
+
+    let created = workspace.ok(&[
+        "create",
+        "--question",
+        "Will it rain?",
+        "--outcome",
+        "Yes",
+        "--outcome",
+        "No",
+        "--oracle",
+        &oracle,
+        "--settlement",
+        SETTLEMENT,
+    ]);
+    assert_eq!(created["market"]["state"], "draft");
+    created["market"]["market_id"].as_str().unwrap().to_string()
+}
+
+fn contribution_args(workspace: &Workspace, count: usize) -> Vec<String> {
+    (0..count)
+        .flat_map(|index| {
+            [
+                "--contribution".to_string(),
+                workspace
+                    .path(&format!("bet{index}.json"))
+                    .display()
+                    .to_string(),
+            ]
+        })
+        .collect()
+}
+
+fn strs(args: &[String]) -> Vec<&str> {
+    args.iter().map(String::as_str).collect()
+}
+
+fn file(path: &Path) -> String {
+    path.display().to_string()
+}
+
+#[test]
+fn test_payout_flow() {
+    let signed = SignedMarket::new("payout");
+    let workspace = &signed.workspace;
+    let market_id = signed.market_id.as_str();
+
+    let combined = signed.combine();
+    let pool_utxo = combined["pool_utxo"].as_str().unwrap().to_string();
+    let market = signed.market();
+    assert_eq!(market["state"], "funded");
+    assert_eq!(market["pool_utxo"], pool_utxo.as_str());
+
+    let outcomes = workspace.ok(&["oracle", "outcomes", "--market", market_id]);
+    assert_eq!(outcomes["outcomes"].as_array().unwrap().len(), 2);
+    let secret = file(&workspace.path("oracle.key"));
+    let attestation = workspace.ok(&[
+        "oracle",
+        "sign",
+        "--market",
+        market_id,
+        "--outcome",
+        "A",
+        "--secret-file",
+        &secret,
+    ]);
+    assert_eq!(attestation["outcome_index"], 0);
+    assert_eq!(attestation["outcome_id"], outcomes["outcomes"][0]["id"]);
+
+    // The attestation saved by `oracle sign` settles the market
+    let settled = workspace.ok(&["settle", "--market", market_id]);
+    assert_eq!(settled["winning_outcome"], 0);
+    assert_eq!(settled["market"]["state"], "settled");
+
+    let unsigned = workspace.path("payout.hex");
+    let payout = workspace.ok(&[
+        "payout",
+        "build",
+        "--market",
+        market_id,
+        "--out",
+        &file(&unsigned),
+    ]);
+    assert_eq!(payout["pool_utxo"], pool_utxo.as_str());
+    assert_eq!(payout["withdraw_type"], "payout");
+    let outputs = payout["outputs"].as_array().unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0]["address"], signed.bettors[0].address.to_string());
+
+    let signed_payout = workspace.ok(&[
+        "payout",
+        "sign",
+        "--market",
+        market_id,
+        "--tx",
+        &file(&unsigned),
+    ]);
+    assert_eq!(signed_payout["txid"], payout["txid"]);
+    let transaction = signed_payout["transaction"].as_str().unwrap();
+
+    let inspected = workspace.ok(&["inspect", "--market", market_id, "--tx", transaction]);
+    let inspection = &inspected["inspection"];
+    assert_eq!(inspection["leaf"]["type"], "payout");
+    assert_eq!(inspection["leaf"]["outcome_index"], 0);
+    assert_eq!(inspection["template_matches"], true);
+    assert_eq!(inspection["control_block_valid"], true);
+    assert_eq!(inspection["oracle_signature_valid"], true);
+    assert_eq!(inspection["spends_pool_utxo"], true);
+
+    let graph = workspace.ok(&["graph", "--market", market_id]);
+    assert_eq!(graph["graph"]["pool_utxo"], pool_utxo.as_str());
+    assert_eq!(graph["graph"]["deposit"]["source"], "recorded");
+    assert_eq!(graph["graph"]["payouts"][0]["txid"], payout["txid"]);
+    assert!(graph["dot"].as_str().unwrap().starts_with("digraph"));
+}
+
+#[test]
+fn test_escape_flow() {
+    let signed = SignedMarket::new("escape");
+    let workspace = &signed.workspace;
+    let market_id = signed.market_id.as_str();
+    signed.combine();
+
+    let unsigned = workspace.path("escape.hex");
+    let escape = workspace.ok(&[
+        "escape",
+        "build",
+        "--market",
+        market_id,
+        "--out",
+        &file(&unsigned),
+    ]);
+    assert_eq!(escape["withdraw_type"], "escape");
+    assert_eq!(escape["outputs"].as_array().unwrap().len(), 2);
+
+    let signed_escape = workspace.ok(&[
+        "escape",
+        "sign",
+        "--market",
+        market_id,
+        "--tx",
+        &file(&unsigned),
+    ]);
+    assert_eq!(signed_escape["txid"], escape["txid"]);
+
+    let transaction = signed_escape["transaction"].as_str().unwrap();
+    let inspected = workspace.ok(&["inspect", "--market", market_id, "--tx", transaction]);
+    assert_eq!(inspected["inspection"]["leaf"]["type"], "escape");
+    assert_eq!(inspected["inspection"]["template_matches"], true);
+
+    // The payout needs a settled market
+    assert_eq!(
+        workspace.error(&["payout", "build", "--market", market_id]),
+        "invalid_state"
+    );
+}
+
+#[test]
+fn test_combine_prevouts() {
+    let signed = SignedMarket::new("combine");
+    let workspace = &signed.workspace;
+
+    // A funding transaction that does not create the claimed input is rejected
+    let mut args = signed.combine_args(false);
+    let other = Bettor::new(3, 200_000);
+    let funding = workspace.write("other.hex", &serialize_hex(&other.funding));
+    args.extend(["--prevout-tx".to_string(), funding]);
+    assert_eq!(workspace.error(&strs(&args)), "unknown_prevout");
+    assert_eq!(signed.market()["state"], "deposit_signing");
+
+    // Without funding transactions, the prevouts are the contributions' claims
+    let combined = workspace.ok(&strs(&signed.combine_args(false)));
+    assert_eq!(combined["prevouts_verified"], false);
+    assert_eq!(signed.market()["state"], "funded");
+}
+
+#[test]
+fn test_deposit_create_does_not_freeze_on_error() {
+    let workspace = Workspace::new("create");
+    let market_id = create_market(&workspace);
+    let bettor = Bettor::new(1, 200_000);
+    let address = bettor.address.to_string();
+    workspace.ok(&[
+        "bet",
+        "--market",
+        &market_id,
+        "--outcome",
+        "A",
+        "--amount",
+        "100000",
+        "--payout-address",
+        &address,
+    ]);
+
+    let code = workspace.error(&[
+        "deposit",
+        "create",
+        "--market",
+        &market_id,
+        "--bet",
+        "0",
+        "--input",
+        "not-an-input",
+        "--change",
+        &address,
+    ]);
+    assert_eq!(code, "invalid_argument");
+    let market = workspace.ok(&["info", &market_id])["market"].clone();
+    assert_eq!(market["state"], "open");
+}
+
+#[test]
+fn test_error_codes() {
+    let workspace = Workspace::new("errors");
+
+    // Regtest is refused unless the inactive opcodes are allowed
+    let (code, document) = workspace.run_with(
+        &[
+            "create",
+            "--question",
+            "Q?",
+            "--outcome",
+            "Yes",
+            "--outcome",
+            "No",
+            "--oracle",
+            "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e",
+            "--settlement",
+            SETTLEMENT,
+        ],
+        &["--network", "regtest"],
+    );
+    assert_eq!(code, 1);
+    assert_eq!(document["ok"], false);
+    assert_eq!(document["command"], "create");
+    assert_eq!(document["error"]["code"], "network");
+
+    assert_eq!(
+        workspace.error(&["info", &"ab".repeat(32)]),
+        "market_not_found"
+    );
+    assert_eq!(
+        workspace.error(&["convert", "1", "eur"]),
+        "invalid_argument"
+    );
+
+    // Argument errors are reported as JSON too
+    let (code, document) = workspace.run(&["bet", "--market", "m"]);
+    assert_eq!(code, 2);
+    assert_eq!(document["ok"], false);
+    assert_eq!(document["error"]["code"], "invalid_argument");
+}
@@ -0,0 +1,323 @@
+//! # Oracle Attestations
+//!
+//! BIP-340 verification of the oracle's signed outcome events.
+//!
+//! The oracle attests an outcome by signing its template event (see
+//! [`crate::event`]): the `sig` of the event is a BIP-340 Schnorr signature of the
+//! 32-byte NIP-01 event id, under the oracle's x-only public key. Verification
+//! recomputes the id from the event fields before checking the signature, so the
+//! content and tags are covered as well.
+//!
+//! [`attest_outcome`] is the oracle side: it produces both the signed event and
+//! the CSFS signature the payout leaves check (see
+//! [`PredictionMarket::create_csfs_signature`]).
+
+use crate::{
+    error::{MarketError, Result},
+    event::NostrEvent,
+    market::{PredictionMarket, PredictionOutcome},
+};
+use bitcoin::secp256k1::{schnorr, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Why an attestation was rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AttestationError {
+    /// The signed message is not 32 bytes
+    #[error("Message must be 32 bytes, got {0}")]
+    InvalidMessage(usize),
+
+    /// The public key is not a valid x-only key
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+
+    /// The signature is not a valid 64-byte BIP-340 signature encoding
+    #[error("Invalid signature encoding: {0}")]
+    InvalidSignatureEncoding(String),
+
+    /// The event id is not 32 hex-encoded bytes
+    #[error("Invalid event id: {0}")]
+    InvalidId(String),
+
+    /// The event id does not match the event fields
+    #[error("Event id {id} does not match its content (expected {computed})")]
+    IdMismatch { id: String, computed: String },
+
+    /// The event carries no signature
+    #[error("Event is not signed")]
+    Unsigned,
+
+    /// The signature does not verify under the public key
+    #[error("Signature does not verify")]
+    BadSignature,
+
+    /// The event is signed by another key than the oracle
+    #[error("Signed by {found}, expected the oracle {expected}")]
+    WrongOracle { expected: String, found: String },
+
+    /// The event attests another outcome
+    #[error("Event {found} does not attest outcome {expected}")]
+    WrongOutcome { expected: String, found: String },
+}
+
+/// The oracle's attestation of one outcome of a market
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    /// Index of the attested outcome
+    pub outcome_index: usize,
+    /// Nostr event id of the attested outcome
+    pub outcome_id: String,
+    /// Signature checked by `OP_CHECKSIGFROMSTACK` in the payout leaf
+    pub csfs_signature: Vec<u8>,
+    /// The outcome event, signed by the oracle
+    pub event: NostrEvent,
+}
+
+/// Attests an outcome of `market` with the oracle's secret key.
+///
+/// # Arguments
+/// * `market` - The market to settle
+/// * `outcome_index` - Index of the winning outcome
+/// * `oracle_secret_key` - 32-byte secret key of the market oracle
+pub fn attest_outcome(
+    market: &PredictionMarket,
+    outcome_index: usize,
+    oracle_secret_key: &[u8],
+) -> Result<Attestation> {
+    let outcome = market.outcome(outcome_index)?;
+    let keypair = Keypair::from_seckey_slice(&Secp256k1::new(), oracle_secret_key)
+        .map_err(|e| MarketError::InvalidSignature(format!("Invalid secret key: {e}")))?;
+    let pubkey = keypair.x_only_public_key().0.to_string();
+    if pubkey != market.oracle_pubkey {
+        return Err(MarketError::Oracle(format!(
+            "Secret key belongs to {pubkey}, not to the market oracle {}",
+            market.oracle_pubkey
+        )));
+    }
+
+    let outcome_id = outcome.nostr_id();
+    let csfs_signature = market.create_csfs_signature(oracle_secret_key, &outcome_id)?;
+    let mut event = outcome.nostr_event();
+    event.sign(&keypair)?;
+
+    Ok(Attestation {
+        outcome_index,
+        outcome_id,
+        csfs_signature,
+        event,
+    })
+}
+
+/// Verifies a BIP-340 signature of a 32-byte message.
+///
+/// # Arguments
+/// * `message` - The signed message, e.g. a Nostr event id
+/// * `signature` - 64-byte Schnorr signature
+/// * `pubkey` - 32-byte x-only public key
+pub fn verify_schnorr(
+    message: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+) -> std::result::Result<(), AttestationError> {
+    let message: [u8; 32] = message
+        .try_into()
+        .map_err(|_| AttestationError::InvalidMessage(message.len()))?;
+    let pubkey = XOnlyPublicKey::from_slice(pubkey)
+        .map_err(|e| AttestationError::InvalidPublicKey(e.to_string()))?;
+    let signature = schnorr::Signature::from_slice(signature)
+        .map_err(|e| AttestationError::InvalidSignatureEncoding(e.to_string()))?;
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &Message::from_digest(message), &pubkey)
+        .map_err(|_| AttestationError::BadSignature)
+}
+
+/// Verifies the NIP-01 id and the signature of a signed Nostr event.
+pub fn verify_event(event: &NostrEvent) -> std::result::Result<(), AttestationError> {
+    let computed = event.compute_id();
+    if event.id != computed {
+        return Err(AttestationError::IdMismatch {
+            id: event.id.clone(),
+            computed,
+        });
+    }
+    let signature = event.sig.as_ref().ok_or(AttestationError::Unsigned)?;
+
+    let id = hex::decode(&event.id).map_err(|e| AttestationError::InvalidId(e.to_string()))?;
+    let signature = hex::decode(signature)
+        .map_err(|e| AttestationError::InvalidSignatureEncoding(e.to_string()))?;
+    let pubkey = hex::decode(&event.pubkey)
+        .map_err(|e| AttestationError::InvalidPublicKey(e.to_string()))?;
+    verify_schnorr(&id, &signature, &pubkey)
+}
+
+/// Verifies that `event` is the oracle's signed attestation of `outcome`.
+pub fn verify_attestation(
+    event: &NostrEvent,
+    outcome: &PredictionOutcome,
+) -> std::result::Result<(), AttestationError> {
+    verify_event(event)?;
+    if event.pubkey != outcome.oracle {
+        return Err(AttestationError::WrongOracle {
+            expected: outcome.oracle.clone(),
+            found: event.pubkey.clone(),
+        });
+    }
+    let expected = outcome.nostr_id();
+    if event.id != expected {
+        return Err(AttestationError::WrongOutcome {
+            expected,
+            found: event.id.clone(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::{sha256, Hash};
+
+    fn keypair(byte: u8) -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[byte; 32]).unwrap()
+    }
+
+    fn oracle_market() -> PredictionMarket {
+        PredictionMarket::new(
+            "Who wins?".to_string(),
+            vec!["Alice".to_string(), "Bob".to_string()],
+            keypair(0x11).x_only_public_key().0.to_string(),
+            1735689600,
+        )
+        .unwrap()
+    }
+
+    fn attestation(outcome: &PredictionOutcome) -> NostrEvent {
+        let mut event = outcome.nostr_event();
+        event.sign(&keypair(0x11)).unwrap();
+        event
+    }
+
+    #[test]
+    fn test_verify_attestation() {
+        let market = oracle_market();
+        let event = attestation(&market.outcomes[1]);
+        assert_eq!(verify_event(&event), Ok(()));
+        assert_eq!(verify_attestation(&event, &market.outcomes[1]), Ok(()));
+        assert!(matches!(
+            verify_attestation(&event, &market.outcomes[0]),
+            Err(AttestationError::WrongOutcome { .. })
+        ));
+
+        // Round trip through the NIP-01 JSON
+        let json = serde_json::to_string(&event).unwrap();
+        let event: NostrEvent = serde_json::from_str(&json).unwrap();
+        assert_eq!(event.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_tampered_events() {
+        let market = oracle_market();
+        let event = attestation(&market.outcomes[0]);
+
+        let mut tampered = event.clone();
+        tampered.content = "Bob".to_string();
+        assert!(matches!(
+            verify_event(&tampered),
+            Err(AttestationError::IdMismatch { .. })
+        ));
+
+        // Signature of another event
+        let mut swapped = event.clone();
+        swapped.sig = attestation(&market.outcomes[1]).sig;
+        assert_eq!(verify_event(&swapped), Err(AttestationError::BadSignature));
+
+        let mut unsigned = event.clone();
+        unsigned.sig = None;
+        assert_eq!(verify_event(&unsigned), Err(AttestationError::Unsigned));
+
+        let mut garbled = event.clone();
+        garbled.sig = Some("zz".to_string());
+        assert!(matches!(
+            verify_event(&garbled),
+            Err(AttestationError::InvalidSignatureEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_other_signers() {
+        let market = oracle_market();
+        let outcome = &market.outcomes[0];
+
+        // The same outcome announced and signed by somebody else
+        let impostor = keypair(0x22);
+        let mut forged = outcome.clone();
+        forged.oracle = impostor.x_only_public_key().0.to_string();
+        let mut event = forged.nostr_event();
+        event.sign(&impostor).unwrap();
+        assert_eq!(verify_event(&event), Ok(()));
+        assert!(matches!(
+            verify_attestation(&event, outcome),
+            Err(AttestationError::WrongOracle { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_schnorr_bytes() {
+        let market = oracle_market();
+        let event = attestation(&market.outcomes[0]);
+        let id = hex::decode(&event.id).unwrap();
+        let signature = hex::decode(event.sig.unwrap()).unwrap();
+        let pubkey = hex::decode(&event.pubkey).unwrap();
+
+        assert_eq!(verify_schnorr(&id, &signature, &pubkey), Ok(()));
+        assert_eq!(
+            verify_schnorr(&id[..31], &signature, &pubkey),
+            Err(AttestationError::InvalidMessage(31))
+        );
+        assert!(matches!(
+            verify_schnorr(&id, &signature[..63], &pubkey),
+            Err(AttestationError::InvalidSignatureEncoding(_))
+        ));
+        assert!(matches!(
+            verify_schnorr(&id, &signature, &[0; 32]),
+            Err(AttestationError::InvalidPublicKey(_))
+        ));
+        let mut other = id.clone();
+        other[0] ^= 1;
+        assert_eq!(
+            verify_schnorr(&other, &signature, &pubkey),
+            Err(AttestationError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn test_attest_outcome() {
+        let market = oracle_market();
+        let attestation = attest_outcome(&market, 1, &[0x11; 32]).unwrap();
+        assert_eq!(attestation.outcome_id, market.outcomes[1].nostr_id());
+        assert_eq!(
+            verify_attestation(&attestation.event, &market.outcomes[1]),
+            Ok(())
+        );
+
+        // The CSFS signature commits to the hex outcome id, as in the payout leaf
+        let message = sha256::Hash::hash(attestation.outcome_id.as_bytes());
+        verify_schnorr(
+            message.as_byte_array(),
+            &attestation.csfs_signature,
+            &hex::decode(&market.oracle_pubkey).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            attest_outcome(&market, 2, &[0x11; 32]),
+            Err(MarketError::InvalidOutcome(_))
+        ));
+        assert!(matches!(
+            attest_outcome(&market, 0, &[0x22; 32]),
+            Err(MarketError::Oracle(_))
+        ));
+        assert!(attest_outcome(&market, 0, &[0; 32]).is_err());
+    }
+}
@@ -0,0 +1,795 @@
+//! # Deposit Coordinator
+//!
+//! Transport-agnostic state machine driving the deposit round with
+//! [`ProtocolMessage`]s:
+//!
+//! ```text
+//! Registration --freeze--> Contributions --all received--> Signatures --all signed--> Complete
+//! ```
+//!
+//! The coordinator never talks to the network itself: every call returns the
+//! messages to broadcast to the participants, and prevouts are looked up through a
+//! caller-supplied function. Participants are identified by the index of their bet in
+//! [`PredictionMarket::all_bets`] order.
+//!
+//! A participant who never answers would otherwise stall the pool forever, since the
+//! pool address commits to every bet. The caller owns the clock and calls
+//! [`DepositCoordinator::timeout`] when a phase exceeds its deadline: the participants
+//! still missing are blamed and dropped, and the round restarts with the remaining bets.
+
+use crate::{
+    deposit::{
+        build_deposit_psbt, combine_deposit_transaction, verify_contribution,
+        verify_participant_signatures, DepositError, PartialDepositTx, ProtocolMessage,
+    },
+    Bet, MarketError, PredictionMarket,
+};
+use bitcoin::{OutPoint, Psbt, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+
+/// Phase of the deposit round
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinatorPhase {
+    /// Bets are being registered
+    Registration,
+    /// Bet set is frozen, waiting for every contribution
+    Contributions,
+    /// Deposit template is distributed, waiting for every signature
+    Signatures,
+    /// Deposit transaction is final
+    Complete,
+}
+
+impl fmt::Display for CoordinatorPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Registration => write!(f, "registration"),
+            Self::Contributions => write!(f, "contributions"),
+            Self::Signatures => write!(f, "signatures"),
+            Self::Complete => write!(f, "complete"),
+        }
+    }
+}
+
+/// A participant dropped from the deposit round
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Blame {
+    /// Index of the participant in the round it was dropped from
+    pub participant: usize,
+    /// Outcome of the dropped bet
+    pub outcome: usize,
+    /// The dropped bet
+    pub bet: Bet,
+    /// Phase the participant did not complete
+    pub phase: CoordinatorPhase,
+    /// Why the participant's last message was rejected, if it sent an invalid one
+    pub rejection: Option<String>,
+}
+
+impl Blame {
+    /// Whether the participant sent invalid messages rather than none at all
+    pub fn misbehaved(&self) -> bool {
+        self.rejection.is_some()
+    }
+}
+
+/// Errors of the deposit coordinator
+#[derive(Error, Debug)]
+pub enum CoordinatorError {
+    /// The message is not expected in the current phase
+    #[error("Unexpected {message} message during {phase}")]
+    UnexpectedMessage {
+        phase: CoordinatorPhase,
+        message: &'static str,
+    },
+
+    /// A participant's contribution or signature was rejected
+    #[error("Rejected: {0}")]
+    Rejected(#[from] DepositError),
+
+    /// A participant signed another transaction than the distributed template
+    #[error("Participant {participant}: signed deposit does not match the template")]
+    WrongTemplate { participant: usize },
+
+    /// The market refused the operation
+    #[error(transparent)]
+    Market(#[from] MarketError),
+
+    /// The deposit transaction cannot be built
+    #[error("Deposit transaction error: {0}")]
+    Transaction(String),
+}
+
+impl CoordinatorError {
+    /// Index of the participant at fault, if any
+    pub fn participant(&self) -> Option<usize> {
+        match self {
+            Self::Rejected(error) => error.participant(),
+            Self::WrongTemplate { participant } => Some(*participant),
+            _ => None,
+        }
+    }
+}
+
+/// Coordinator of a market's deposit round
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DepositCoordinator {
+    market: PredictionMarket,
+    phase: CoordinatorPhase,
+    contributions: BTreeMap<usize, PartialDepositTx>,
+    template: Option<Psbt>,
+    signed: BTreeMap<usize, Psbt>,
+    transaction: Option<Transaction>,
+    rejections: BTreeMap<usize, String>,
+    round: u32,
+}
+
+impl DepositCoordinator {
+    /// Starts a round for a market open for bet registration
+    pub fn new(market: PredictionMarket) -> Result<Self, CoordinatorError> {
+        if !market.state.accepts_bets() {
+            return Err(MarketError::InvalidState(format!(
+                "deposit round needs a market open for bets ({})",
+                market.state
+            ))
+            .into());
+        }
+        Ok(Self {
+            market,
+            phase: CoordinatorPhase::Registration,
+            contributions: BTreeMap::new(),
+            template: None,
+            signed: BTreeMap::new(),
+            transaction: None,
+            rejections: BTreeMap::new(),
+            round: 0,
+        })
+    }
+
+    /// The market, with the bets registered so far
+    pub fn market(&self) -> &PredictionMarket {
+        &self.market
+    }
+
+    /// Current phase of the round
+    pub fn phase(&self) -> CoordinatorPhase {
+        self.phase
+    }
+
+    /// Number of restarts so far
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    /// Number of participants (registered bets)
+    pub fn participants(&self) -> usize {
+        self.market.all_bets().count()
+    }
+
+    /// Participants the current phase is still waiting for
+    pub fn missing_participants(&self) -> Vec<usize> {
+        let received: Vec<usize> = match self.phase {
+            CoordinatorPhase::Contributions => self.contributions.keys().copied().collect(),
+            CoordinatorPhase::Signatures => self.signed.keys().copied().collect(),
+            _ => return Vec::new(),
+        };
+        (0..self.participants())
+            .filter(|participant| !received.contains(participant))
+            .collect()
+    }
+
+    /// The unsigned deposit PSBT, once every contribution is received
+    pub fn deposit_template(&self) -> Option<&Psbt> {
+        self.template.as_ref()
+    }
+
+    /// The final deposit transaction, once every participant signed
+    pub fn deposit_transaction(&self) -> Option<&Transaction> {
+        self.transaction.as_ref()
+    }
+
+    /// Freezes the bet set and asks the participants for their contributions
+    pub fn freeze(&mut self) -> Result<Vec<ProtocolMessage>, CoordinatorError> {
+        if self.phase != CoordinatorPhase::Registration {
+            return Err(MarketError::InvalidState(format!(
+                "bets are already frozen ({})",
+                self.phase
+            ))
+            .into());
+        }
+        self.market.freeze_bets()?;
+        self.phase = CoordinatorPhase::Contributions;
+        Ok(vec![ProtocolMessage::BetsFrozen(Box::new(
+            self.market.clone(),
+        ))])
+    }
+
+    /// Handles a participant's message and returns the messages to broadcast.
+    ///
+    /// # Arguments
+    /// * `message` - The participant's message
+    /// * `prevout_lookup` - Returns the unspent output at an outpoint, if any
+    pub fn handle<F>(
+        &mut self,
+        message: ProtocolMessage,
+        prevout_lookup: F,
+    ) -> Result<Vec<ProtocolMessage>, CoordinatorError>
+    where
+        F: Fn(&OutPoint) -> Option<TxOut>,
+    {
+        let result = match (self.phase, message) {
+            (CoordinatorPhase::Registration, ProtocolMessage::Bet { outcome, bet }) => {
+                self.market.place_bet(
+                    outcome,
+                    bet.amount,
+                    bet.payout_address,
+                    bet.txid,
+                    bet.vout,
+                )?;
+                Ok(Vec::new())
+            }
+            (CoordinatorPhase::Contributions, ProtocolMessage::PartialDepositTx(contribution)) => {
+                self.add_contribution(contribution, &prevout_lookup)
+            }
+            (
+                CoordinatorPhase::Signatures,
+                ProtocolMessage::SignedDeposit { input_index, psbt },
+            ) => self.add_signature(input_index, psbt, &prevout_lookup),
+            (phase, message) => Err(CoordinatorError::UnexpectedMessage {
+                phase,
+                message: message.name(),
+            }),
+        };
+        if let Err(error) = &result {
+            if let Some(participant) = error.participant() {
+                if participant < self.participants() {
+                    self.rejections.insert(participant, error.to_string());
+                }
+            }
+        }
+        result
+    }
+
+    /// Ends the current phase once its deadline has passed.
+    ///
+    /// The participants the phase is still waiting for are blamed and their bets are
+    /// dropped from the market. The pool address and CTV payout templates are then
+    /// recomputed for the remaining bets and the round restarts: `RoundRestarted`
+    /// carries the new market, followed by the new `DepositTemplate` when every
+    /// remaining contribution was already received. Remaining participants keep their
+    /// contributions, renumbered to the index of their bet in the new market, and must
+    /// sign again. Returns nothing when no participant is missing.
+    pub fn timeout(&mut self) -> Result<Vec<ProtocolMessage>, CoordinatorError> {
+        if !matches!(
+            self.phase,
+            CoordinatorPhase::Contributions | CoordinatorPhase::Signatures
+        ) {
+            return Err(
+                MarketError::InvalidState(format!("no deadline during {}", self.phase)).into(),
+            );
+        }
+        let dropped = self.missing_participants();
+        if dropped.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.market.reopen_registration()?;
+        let removed = self.market.remove_bets(&dropped)?;
+        let blamed: Vec<Blame> = dropped
+            .iter()
+            .zip(removed)
+            .map(|(&participant, (outcome, bet))| Blame {
+                participant,
+                outcome,
+                bet,
+                phase: self.phase,
+                rejection: self.rejections.get(&participant).cloned(),
+            })
+            .collect();
+
+        let contributions = std::mem::take(&mut self.contributions);
+        self.contributions = contributions
+            .into_iter()
+            .filter(|(participant, _)| !dropped.contains(participant))
+            .map(|(participant, mut contribution)| {
+                let shift = dropped.iter().filter(|&&d| d < participant).count();
+                contribution.input_index = participant - shift;
+                (contribution.input_index, contribution)
+            })
+            .collect();
+        self.template = None;
+        self.signed.clear();
+        self.rejections.clear();
+        self.round += 1;
+
+        if self.participants() == 0 {
+            self.phase = CoordinatorPhase::Registration;
+            return Ok(vec![ProtocolMessage::RoundRestarted {
+                blamed,
+                market: Box::new(self.market.clone()),
+            }]);
+        }
+        self.market.freeze_bets()?;
+        self.phase = CoordinatorPhase::Contributions;
+        let mut messages = vec![ProtocolMessage::RoundRestarted {
+            blamed,
+            market: Box::new(self.market.clone()),
+        }];
+        if self.contributions.len() == self.participants() {
+            messages.push(self.distribute_template()?);
+        }
+        Ok(messages)
+    }
+
+    fn add_contribution<F>(
+        &mut self,
+        contribution: PartialDepositTx,
+        prevout_lookup: &F,
+    ) -> Result<Vec<ProtocolMessage>, CoordinatorError>
+    where
+        F: Fn(&OutPoint) -> Option<TxOut>,
+    {
+        let participant = contribution.input_index;
+        if self.contributions.contains_key(&participant) {
+            return Err(DepositError::DuplicateContribution { participant }.into());
+        }
+        let mut spent: HashSet<OutPoint> = self
+            .contributions
+            .values()
+            .flat_map(|c| c.psbt.unsigned_tx.input.iter().map(|i| i.previous_output))
+            .collect();
+        verify_contribution(&self.market, &contribution, &mut spent, prevout_lookup)?;
+        self.contributions.insert(participant, contribution);
+        self.rejections.remove(&participant);
+
+        if self.contributions.len() < self.participants() {
+            return Ok(Vec::new());
+        }
+        Ok(vec![self.distribute_template()?])
+    }
+
+    fn distribute_template(&mut self) -> Result<ProtocolMessage, CoordinatorError> {
+        let template =
+            build_deposit_psbt(&self.market, self.contributions.values().cloned().collect())
+                .map_err(|e| CoordinatorError::Transaction(e.to_string()))?;
+        self.template = Some(template.clone());
+        self.phase = CoordinatorPhase::Signatures;
+        Ok(ProtocolMessage::DepositTemplate(template))
+    }
+
+    fn add_signature<F>(
+        &mut self,
+        participant: usize,
+        psbt: Psbt,
+        prevout_lookup: &F,
+    ) -> Result<Vec<ProtocolMessage>, CoordinatorError>
+    where
+        F: Fn(&OutPoint) -> Option<TxOut>,
+    {
+        let contribution = self
+            .contributions
+            .get(&participant)
+            .ok_or(DepositError::UnknownBet { participant })?;
+        let template = self
+            .template
+            .as_ref()
+            .ok_or_else(|| CoordinatorError::Transaction("no deposit template".to_string()))?;
+        if psbt.unsigned_tx != template.unsigned_tx {
+            return Err(CoordinatorError::WrongTemplate { participant });
+        }
+
+        // Contributions are laid out in input_index order
+        let first_input = self
+            .contributions
+            .range(..participant)
+            .map(|(_, c)| c.psbt.inputs.len())
+            .sum();
+        verify_participant_signatures(&psbt, contribution, first_input, prevout_lookup)?;
+        self.signed.insert(participant, psbt);
+        self.rejections.remove(&participant);
+
+        if self.signed.len() < self.participants() {
+            return Ok(Vec::new());
+        }
+        let contributions: Vec<PartialDepositTx> = self.contributions.values().cloned().collect();
+        let transaction = combine_deposit_transaction(
+            &self.market,
+            &contributions,
+            self.signed.values().cloned().collect(),
+            prevout_lookup,
+        )
+        .map_err(|e| match e.downcast::<DepositError>() {
+            Ok(error) => CoordinatorError::Rejected(error),
+            Err(e) => CoordinatorError::Transaction(e.to_string()),
+        })?;
+        self.transaction = Some(transaction.clone());
+        self.phase = CoordinatorPhase::Complete;
+        Ok(vec![ProtocolMessage::DepositTransaction(transaction)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deposit::sign_deposit_psbt;
+    use crate::test_utils::*;
+    use crate::{Bet, MarketState};
+    use bitcoin::Amount;
+    use std::collections::HashMap;
+
+    /// Registers the bets of `create_test_market` through the coordinator
+    fn registered_coordinator() -> DepositCoordinator {
+        let mut coordinator = DepositCoordinator::new(create_empty_test_market()).unwrap();
+        for (outcome, bets) in create_test_market().bets.into_iter().enumerate() {
+            for bet in bets {
+                let messages = coordinator
+                    .handle(ProtocolMessage::Bet { outcome, bet }, |_| None)
+                    .unwrap();
+                assert!(messages.is_empty());
+            }
+        }
+        coordinator
+    }
+
+    /// Freezes the bets and sends every contribution, in the given order
+    fn contributed_coordinator(
+        order: &[usize],
+    ) -> (
+        DepositCoordinator,
+        Vec<(PartialDepositTx, bitcoin::PrivateKey)>,
+        HashMap<OutPoint, TxOut>,
+    ) {
+        let mut coordinator = registered_coordinator();
+        coordinator.freeze().unwrap();
+        let contributions = deposit_contributions(coordinator.market());
+        let partials: Vec<_> = contributions.iter().map(|(p, _)| p.clone()).collect();
+        let utxos = utxo_set(&partials);
+        for &participant in order {
+            coordinator
+                .handle(
+                    ProtocolMessage::PartialDepositTx(contributions[participant].0.clone()),
+                    |outpoint| utxos.get(outpoint).cloned(),
+                )
+                .unwrap();
+        }
+        (coordinator, contributions, utxos)
+    }
+
+    fn sign(
+        coordinator: &DepositCoordinator,
+        contribution: &(PartialDepositTx, bitcoin::PrivateKey),
+    ) -> ProtocolMessage {
+        let mut psbt = coordinator.deposit_template().unwrap().clone();
+        sign_deposit_psbt(
+            &mut psbt,
+            coordinator.market(),
+            &contribution.0,
+            &contribution.1,
+        )
+        .unwrap();
+        ProtocolMessage::SignedDeposit {
+            input_index: contribution.0.input_index,
+            psbt,
+        }
+    }
+
+    #[test]
+    fn test_registration_phase() {
+        let mut coordinator = registered_coordinator();
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Registration);
+        assert_eq!(coordinator.participants(), 3);
+        assert_eq!(coordinator.market().total_amount, 300000);
+        assert!(coordinator.missing_participants().is_empty());
+
+        // Contributions are only accepted once the bets are frozen
+        let partial = deposit_contributions(&create_test_market()).remove(0).0;
+        let result = coordinator.handle(ProtocolMessage::PartialDepositTx(partial), |_| None);
+        assert!(matches!(
+            result,
+            Err(CoordinatorError::UnexpectedMessage {
+                phase: CoordinatorPhase::Registration,
+                message: "partial_deposit_tx"
+            })
+        ));
+
+        // Bets on unknown outcomes are refused by the market
+        let bet = create_test_market().bets[0][0].clone();
+        let result = coordinator.handle(ProtocolMessage::Bet { outcome: 5, bet }, |_| None);
+        assert!(matches!(result, Err(CoordinatorError::Market(_))));
+
+        // A market that is not open cannot start a round
+        let mut market = create_test_market();
+        market.state = MarketState::Funded;
+        assert!(DepositCoordinator::new(market).is_err());
+    }
+
+    #[test]
+    fn test_freeze_phase() {
+        let mut empty = DepositCoordinator::new(create_empty_test_market()).unwrap();
+        assert!(empty.freeze().is_err());
+        assert_eq!(empty.phase(), CoordinatorPhase::Registration);
+
+        let mut coordinator = registered_coordinator();
+        let messages = coordinator.freeze().unwrap();
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Contributions);
+        assert_eq!(coordinator.market().state, MarketState::DepositSigning);
+        assert_eq!(coordinator.missing_participants(), vec![0, 1, 2]);
+        match &messages[..] {
+            [ProtocolMessage::BetsFrozen(market)] => {
+                assert_eq!(
+                    market.get_market_address().unwrap(),
+                    coordinator.market().get_market_address().unwrap()
+                );
+            }
+            other => panic!("unexpected messages {other:?}"),
+        }
+
+        // No more bets
+        let bet = Bet {
+            payout_address: create_valid_regtest_address(9),
+            amount: 10000,
+            txid: constants::TEST_TXID.to_string(),
+            vout: 9,
+        };
+        let result = coordinator.handle(ProtocolMessage::Bet { outcome: 0, bet }, |_| None);
+        assert!(matches!(
+            result,
+            Err(CoordinatorError::UnexpectedMessage { .. })
+        ));
+        assert!(coordinator.freeze().is_err());
+    }
+
+    #[test]
+    fn test_contribution_phase() {
+        let (mut coordinator, contributions, utxos) = contributed_coordinator(&[2]);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+        assert_eq!(coordinator.missing_participants(), vec![0, 1]);
+        assert!(coordinator.deposit_template().is_none());
+
+        // The same bet twice
+        let result = coordinator.handle(
+            ProtocolMessage::PartialDepositTx(contributions[2].0.clone()),
+            lookup,
+        );
+        assert_eq!(result.unwrap_err().participant(), Some(2));
+
+        // Funded from a UTXO the chain does not know
+        let result = coordinator.handle(
+            ProtocolMessage::PartialDepositTx(contributions[1].0.clone()),
+            |_| None,
+        );
+        assert!(matches!(
+            result,
+            Err(CoordinatorError::Rejected(DepositError::UnknownPrevout {
+                participant: 1,
+                ..
+            }))
+        ));
+        assert_eq!(coordinator.missing_participants(), vec![0, 1]);
+
+        // The rejected participant can send a valid contribution again
+        for participant in [1, 0] {
+            let messages = coordinator
+                .handle(
+                    ProtocolMessage::PartialDepositTx(contributions[participant].0.clone()),
+                    lookup,
+                )
+                .unwrap();
+            if participant == 1 {
+                assert!(messages.is_empty());
+            } else {
+                assert!(matches!(
+                    &messages[..],
+                    [ProtocolMessage::DepositTemplate(_)]
+                ));
+            }
+        }
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Signatures);
+        assert_eq!(coordinator.missing_participants(), vec![0, 1, 2]);
+        let template = coordinator.deposit_template().unwrap();
+        assert_eq!(template.unsigned_tx.input.len(), 4);
+        assert_eq!(template.unsigned_tx.output[0].value.to_sat(), 300000);
+    }
+
+    #[test]
+    fn test_signature_phase() {
+        let (mut coordinator, contributions, utxos) = contributed_coordinator(&[0, 1, 2]);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+
+        // Signed without the participant's key
+        let message = match sign(&coordinator, &contributions[1]) {
+            ProtocolMessage::SignedDeposit { psbt, .. } => ProtocolMessage::SignedDeposit {
+                input_index: 0,
+                psbt,
+            },
+            _ => unreachable!(),
+        };
+        let result = coordinator.handle(message, lookup);
+        assert!(matches!(
+            result,
+            Err(CoordinatorError::Rejected(DepositError::Unsigned {
+                participant: 0,
+                ..
+            }))
+        ));
+
+        // Signed another transaction
+        let mut message = sign(&coordinator, &contributions[1]);
+        if let ProtocolMessage::SignedDeposit { psbt, .. } = &mut message {
+            psbt.unsigned_tx.output[0].value = Amount::from_sat(1000);
+        }
+        let result = coordinator.handle(message, lookup);
+        assert_eq!(result.unwrap_err().participant(), Some(1));
+
+        for participant in [2, 0] {
+            let message = sign(&coordinator, &contributions[participant]);
+            assert!(coordinator.handle(message, lookup).unwrap().is_empty());
+        }
+        assert_eq!(coordinator.missing_participants(), vec![1]);
+        assert!(coordinator.deposit_transaction().is_none());
+
+        let message = sign(&coordinator, &contributions[1]);
+        let messages = coordinator.handle(message, lookup).unwrap();
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Complete);
+        assert!(coordinator.missing_participants().is_empty());
+        match &messages[..] {
+            [ProtocolMessage::DepositTransaction(tx)] => {
+                assert_eq!(Some(tx), coordinator.deposit_transaction());
+                assert_eq!(tx.input.len(), 4);
+                assert!(tx.input.iter().all(|input| !input.witness.is_empty()));
+                assert_eq!(
+                    tx.output[0].script_pubkey,
+                    coordinator
+                        .market()
+                        .pool_spend_info()
+                        .unwrap()
+                        .address()
+                        .script_pubkey()
+                );
+            }
+            other => panic!("unexpected messages {other:?}"),
+        }
+
+        // The round is over
+        let message = sign(&coordinator, &contributions[1]);
+        assert!(matches!(
+            coordinator.handle(message, lookup),
+            Err(CoordinatorError::UnexpectedMessage {
+                phase: CoordinatorPhase::Complete,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_timeout_drops_unresponsive_signer() {
+        let (mut coordinator, contributions, utxos) = contributed_coordinator(&[0, 1, 2]);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+        let old_address = coordinator.market().get_market_address().unwrap();
+        for participant in [0, 2] {
+            let message = sign(&coordinator, &contributions[participant]);
+            coordinator.handle(message, lookup).unwrap();
+        }
+
+        let messages = coordinator.timeout().unwrap();
+        assert_eq!(coordinator.round(), 1);
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Signatures);
+        assert_eq!(coordinator.participants(), 2);
+        assert_eq!(coordinator.missing_participants(), vec![0, 1]);
+        let new_address = coordinator.market().get_market_address().unwrap();
+        assert_ne!(new_address, old_address);
+        match &messages[..] {
+            [ProtocolMessage::RoundRestarted { blamed, market }, ProtocolMessage::DepositTemplate(template)] =>
+            {
+                assert_eq!(blamed.len(), 1);
+                assert_eq!(blamed[0].participant, 1);
+                assert_eq!(blamed[0].phase, CoordinatorPhase::Signatures);
+                assert!(!blamed[0].misbehaved());
+                assert_eq!(market.get_market_address().unwrap(), new_address);
+                assert_eq!(market.total_amount, 300000 - blamed[0].bet.amount);
+                assert_eq!(template.unsigned_tx.input.len(), 3);
+                assert_eq!(
+                    template.unsigned_tx.output[0].value.to_sat(),
+                    market.total_amount
+                );
+            }
+            other => panic!("unexpected messages {other:?}"),
+        }
+
+        // Signatures of the previous round commit to the old pool
+        let stale = sign(&contributed_coordinator(&[0, 1, 2]).0, &contributions[0]);
+        assert!(matches!(
+            coordinator.handle(stale, lookup),
+            Err(CoordinatorError::WrongTemplate { participant: 0 })
+        ));
+
+        // The remaining participants sign again under their new index
+        for (new_index, old_index) in [(0, 0), (1, 2)] {
+            let mut contribution = contributions[old_index].clone();
+            contribution.0.input_index = new_index;
+            let message = sign(&coordinator, &contribution);
+            coordinator.handle(message, lookup).unwrap();
+        }
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Complete);
+        let tx = coordinator.deposit_transaction().unwrap();
+        assert_eq!(tx.input.len(), 3);
+        assert_eq!(
+            tx.output[0].value.to_sat(),
+            coordinator.market().total_amount
+        );
+    }
+
+    #[test]
+    fn test_timeout_blames_misbehaving_contributor() {
+        let (mut coordinator, contributions, utxos) = contributed_coordinator(&[2]);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+        let result = coordinator.handle(
+            ProtocolMessage::PartialDepositTx(contributions[0].0.clone()),
+            |_| None,
+        );
+        assert!(result.is_err());
+
+        let messages = coordinator.timeout().unwrap();
+        let blamed = match &messages[..] {
+            [ProtocolMessage::RoundRestarted { blamed, .. }, ProtocolMessage::DepositTemplate(_)] => {
+                blamed
+            }
+            other => panic!("unexpected messages {other:?}"),
+        };
+        let participants: Vec<_> = blamed.iter().map(|b| b.participant).collect();
+        assert_eq!(participants, vec![0, 1]);
+        assert!(blamed[0].misbehaved());
+        assert!(!blamed[1].misbehaved());
+        assert!(blamed
+            .iter()
+            .all(|b| b.phase == CoordinatorPhase::Contributions));
+        assert_eq!(coordinator.participants(), 1);
+
+        let mut contribution = contributions[2].clone();
+        contribution.0.input_index = 0;
+        let message = sign(&coordinator, &contribution);
+        let messages = coordinator.handle(message, lookup).unwrap();
+        assert!(matches!(
+            &messages[..],
+            [ProtocolMessage::DepositTransaction(_)]
+        ));
+    }
+
+    #[test]
+    fn test_timeout_without_participants() {
+        let mut coordinator = registered_coordinator();
+        assert!(coordinator.timeout().is_err());
+
+        coordinator.freeze().unwrap();
+        let messages = coordinator.timeout().unwrap();
+        assert!(matches!(
+            &messages[..],
+            [ProtocolMessage::RoundRestarted { blamed, .. }] if blamed.len() == 3
+        ));
+        assert_eq!(coordinator.phase(), CoordinatorPhase::Registration);
+        assert_eq!(coordinator.market().state, MarketState::Open);
+        assert_eq!(coordinator.market().total_amount, 0);
+        assert!(coordinator.timeout().is_err());
+    }
+
+    #[test]
+    fn test_round_is_deterministic() {
+        // Schnorr signatures use fresh auxiliary randomness, the txid does not depend on them
+        let run = |contribution_order: &[usize], signature_order: &[usize]| {
+            let (mut coordinator, contributions, utxos) =
+                contributed_coordinator(contribution_order);
+            for &participant in signature_order {
+                let message = sign(&coordinator, &contributions[participant]);
+                coordinator
+                    .handle(message, |outpoint| utxos.get(outpoint).cloned())
+                    .unwrap();
+            }
+            coordinator.deposit_transaction().unwrap().compute_txid()
+        };
+        assert_eq!(run(&[0, 1, 2], &[0, 1, 2]), run(&[2, 0, 1], &[1, 2, 0]));
+    }
+}
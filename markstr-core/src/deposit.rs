@@ -1,136 +1,597 @@
 //! Depositing funds into the pool.
 //!
 //! The pool is a single UTXO that contains all the bets.
-//! Each participant creates and signs a partial transaction with one input (from the bet) and one output (to the pool address).
-//! The partial transactions are combined into a single transaction, and submitted to the network.
+//! The deposit round has two steps:
+//!
+//! 1. Each participant builds a contribution PSBT with their inputs (P2WPKH,
+//!    P2SH-P2WPKH or P2TR UTXOs of any size) and an optional change output.
+//! 2. The contributions are combined into the unsigned deposit PSBT, which pays the whole
+//!    pool to a single output followed by the change outputs. Each participant checks that
+//!    their contribution is included and signs their own inputs with
+//!    `SIGHASH_ALL | SIGHASH_ANYONECANPAY`, independently of the others.
+//!
+//! The signed PSBTs are merged, finalized, and the deposit transaction
+//! is extracted and submitted to the network.
+//! Any PSBT signer (bdk, hardware wallets, Bitcoin Core) can sign.
+//!
+//! PSBTs follow BIP-174 (version 0), the version supported by rust-bitcoin and every wallet.
 
 use bitcoin::{
     absolute::LockTime,
+    ecdsa,
     hashes::Hash,
-    key::{Keypair, PrivateKey, Secp256k1},
+    key::{Keypair, PrivateKey, Secp256k1, TapTweak},
+    psbt::{self, Psbt, PsbtSighashType},
+    script::PushBytesBuf,
     secp256k1::Message,
     sighash::{Prevouts, SighashCache},
-    taproot::Signature,
+    taproot,
     transaction::Version,
-    Amount, OutPoint, ScriptBuf, Sequence, TapSighashType, Transaction, TxIn, TxOut, Witness,
+    Address, Amount, EcdsaSighashType, OutPoint, Script, ScriptBuf, Sequence, TapSighashType,
+    Transaction, TxIn, TxOut, Witness, XOnlyPublicKey,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+use crate::{
+    coordinator::Blame, get_tx_version, pool::generate_pool_address, Bet, PredictionMarket,
+    DUST_THRESHOLD,
 };
 
-use crate::{get_tx_version, pool::generate_pool_address, Bet, PredictionMarket};
+/// Sighash type of every Taproot deposit signature
+pub const DEPOSIT_SIGHASH_TYPE: TapSighashType = TapSighashType::AllPlusAnyoneCanPay;
 
-#[derive(Clone, Debug)]
+/// Sighash type of every P2WPKH and P2SH-P2WPKH deposit signature
+pub const DEPOSIT_ECDSA_SIGHASH_TYPE: EcdsaSighashType = EcdsaSighashType::AllPlusAnyoneCanPay;
+
+/// Messages of the deposit round, driven by [`crate::coordinator::DepositCoordinator`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ProtocolMessage {
-    Bet(Bet),
+    /// Participant registers a bet on an outcome
+    Bet { outcome: usize, bet: Bet },
+    /// Coordinator froze the bet set, the market carries the final bets and pool
+    BetsFrozen(Box<PredictionMarket>),
+    /// Participant's unsigned contribution
     PartialDepositTx(PartialDepositTx),
+    /// Coordinator distributes the unsigned deposit PSBT
+    DepositTemplate(Psbt),
+    /// Participant's signed copy of the deposit PSBT
+    SignedDeposit { input_index: usize, psbt: Psbt },
+    /// Coordinator publishes the final deposit transaction
+    DepositTransaction(Transaction),
+    /// Coordinator dropped the participants that stalled the round, the market
+    /// carries the remaining bets and the recomputed pool
+    RoundRestarted {
+        blamed: Vec<Blame>,
+        market: Box<PredictionMarket>,
+    },
+}
+
+impl ProtocolMessage {
+    /// Stable machine-readable name of the message
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bet { .. } => "bet",
+            Self::BetsFrozen(_) => "bets_frozen",
+            Self::PartialDepositTx(_) => "partial_deposit_tx",
+            Self::DepositTemplate(_) => "deposit_template",
+            Self::SignedDeposit { .. } => "signed_deposit",
+            Self::DepositTransaction(_) => "deposit_transaction",
+            Self::RoundRestarted { .. } => "round_restarted",
+        }
+    }
+
+    /// Whether the coordinator sends this message, as opposed to a participant
+    pub fn is_from_coordinator(&self) -> bool {
+        matches!(
+            self,
+            Self::BetsFrozen(_)
+                | Self::DepositTemplate(_)
+                | Self::DepositTransaction(_)
+                | Self::RoundRestarted { .. }
+        )
+    }
 }
 
-#[derive(Clone, Debug)]
+/// A participant's contribution to the deposit transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PartialDepositTx {
-    pub transaction: Transaction,
+    /// PSBT with the participant's inputs and optional change output
+    pub psbt: Psbt,
+    /// Index of the bet in `PredictionMarket::all_bets` order, which is also the
+    /// position of the contribution in the combined deposit transaction
     pub input_index: usize,
 }
 
-/// Creates a partial transaction with one input (from the bet) and one output (to the pool address).
-/// This transaction will later be combined with other participants' inputs.
+/// Why a deposit contribution or signature was rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DepositError {
+    /// The contribution's input_index does not match any bet
+    #[error("Participant {participant}: no bet at this index")]
+    UnknownBet { participant: usize },
+
+    /// No contribution for this bet
+    #[error("Participant {participant}: contribution is missing")]
+    MissingContribution { participant: usize },
+
+    /// Several contributions claim the same bet
+    #[error("Participant {participant}: several contributions for the same bet")]
+    DuplicateContribution { participant: usize },
+
+    /// The contribution spends nothing
+    #[error("Participant {participant}: contribution has no inputs")]
+    NoInputs { participant: usize },
+
+    /// The contribution has more than a change output
+    #[error("Participant {participant}: contribution has {outputs} outputs, at most one change output is allowed")]
+    TooManyOutputs { participant: usize, outputs: usize },
+
+    /// The UTXO is already spent by this or another contribution
+    #[error("Participant {participant}: input {outpoint} is spent twice")]
+    DuplicateInput {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The UTXO does not exist or is already spent
+    #[error("Participant {participant}: input {outpoint} is not an unspent output")]
+    UnknownPrevout {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The PSBT claims another prevout than the chain
+    #[error("Participant {participant}: prevout of input {outpoint} does not match the chain")]
+    PrevoutMismatch {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The prevout is not P2WPKH, P2SH-P2WPKH or P2TR
+    #[error("Participant {participant}: input {outpoint} has an unsupported script")]
+    UnsupportedScript {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The inputs do not cover the bet, the deposit fees and the change
+    #[error("Participant {participant}: inputs hold {available} sats, {required} sats required")]
+    InsufficientFunds {
+        participant: usize,
+        available: u64,
+        required: u64,
+    },
+
+    /// The input carries no signature
+    #[error("Participant {participant}: input {outpoint} is not signed")]
+    Unsigned {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The signature does not verify against the prevout
+    #[error("Participant {participant}: input {outpoint} has an invalid signature")]
+    InvalidSignature {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The signature does not use SIGHASH_ALL | SIGHASH_ANYONECANPAY
+    #[error("Participant {participant}: input {outpoint} is signed with the wrong sighash type")]
+    WrongSighashType {
+        participant: usize,
+        outpoint: OutPoint,
+    },
+
+    /// The first output does not pay the whole pool to the market's pool address
+    #[error("Deposit transaction does not pay the pool to the market address")]
+    PoolOutput,
+
+    /// The deposit transaction is not the one built from the contributions
+    #[error("Deposit transaction does not match the contributions")]
+    TransactionMismatch,
+
+    /// The pool address cannot be computed
+    #[error("Pool error: {0}")]
+    Pool(String),
+}
+
+impl DepositError {
+    /// Index of the participant at fault, if any
+    pub fn participant(&self) -> Option<usize> {
+        match self {
+            Self::UnknownBet { participant }
+            | Self::MissingContribution { participant }
+            | Self::DuplicateContribution { participant }
+            | Self::NoInputs { participant }
+            | Self::TooManyOutputs { participant, .. }
+            | Self::DuplicateInput { participant, .. }
+            | Self::UnknownPrevout { participant, .. }
+            | Self::PrevoutMismatch { participant, .. }
+            | Self::UnsupportedScript { participant, .. }
+            | Self::InsufficientFunds { participant, .. }
+            | Self::Unsigned { participant, .. }
+            | Self::InvalidSignature { participant, .. }
+            | Self::WrongSighashType { participant, .. } => Some(*participant),
+            Self::PoolOutput | Self::TransactionMismatch | Self::Pool(_) => None,
+        }
+    }
+
+    /// Stable machine-readable code of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownBet { .. } => "unknown_bet",
+            Self::MissingContribution { .. } => "missing_contribution",
+            Self::DuplicateContribution { .. } => "duplicate_contribution",
+            Self::NoInputs { .. } => "no_inputs",
+            Self::TooManyOutputs { .. } => "too_many_outputs",
+            Self::DuplicateInput { .. } => "duplicate_input",
+            Self::UnknownPrevout { .. } => "unknown_prevout",
+            Self::PrevoutMismatch { .. } => "prevout_mismatch",
+            Self::UnsupportedScript { .. } => "unsupported_script",
+            Self::InsufficientFunds { .. } => "insufficient_funds",
+            Self::Unsigned { .. } => "unsigned_input",
+            Self::InvalidSignature { .. } => "invalid_input_signature",
+            Self::WrongSighashType { .. } => "wrong_sighash_type",
+            Self::PoolOutput => "pool_output",
+            Self::TransactionMismatch => "transaction_mismatch",
+            Self::Pool(_) => "pool",
+        }
+    }
+}
+
+/// Script types accepted as deposit inputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepositInputType {
+    /// Native SegWit v0 key hash
+    P2wpkh,
+    /// SegWit v0 key hash nested in P2SH
+    P2shP2wpkh,
+    /// Taproot key path spend (BIP-86)
+    P2tr,
+}
+
+impl DepositInputType {
+    /// Detect the type of a prevout script, using the redeem script for P2SH outputs
+    pub fn detect(script_pubkey: &Script, redeem_script: Option<&Script>) -> Option<Self> {
+        if script_pubkey.is_p2wpkh() {
+            Some(Self::P2wpkh)
+        } else if script_pubkey.is_p2tr() {
+            Some(Self::P2tr)
+        } else if script_pubkey.is_p2sh() {
+            redeem_script
+                .filter(|redeem| {
+                    redeem.is_p2wpkh()
+                        && ScriptBuf::new_p2sh(&redeem.script_hash()).as_script() == script_pubkey
+                })
+                .map(|_| Self::P2shP2wpkh)
+        } else {
+            None
+        }
+    }
+}
+
+/// A UTXO spent by a participant into the deposit transaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositInput {
+    /// Outpoint of the UTXO
+    pub outpoint: OutPoint,
+    /// Output being spent
+    pub prevout: TxOut,
+    /// P2WPKH redeem script, required for P2SH-P2WPKH inputs
+    pub redeem_script: Option<ScriptBuf>,
+}
+
+impl DepositInput {
+    /// A P2WPKH or P2TR input
+    pub fn new(outpoint: OutPoint, prevout: TxOut) -> Self {
+        Self {
+            outpoint,
+            prevout,
+            redeem_script: None,
+        }
+    }
+
+    /// A P2SH-P2WPKH input with its redeem script
+    pub fn with_redeem_script(
+        outpoint: OutPoint,
+        prevout: TxOut,
+        redeem_script: ScriptBuf,
+    ) -> Self {
+        Self {
+            outpoint,
+            prevout,
+            redeem_script: Some(redeem_script),
+        }
+    }
+
+    /// Script type of the input
+    pub fn input_type(&self) -> anyhow::Result<DepositInputType> {
+        DepositInputType::detect(&self.prevout.script_pubkey, self.redeem_script.as_deref())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Input {} has an unsupported script {} (expected P2WPKH, P2SH-P2WPKH or P2TR)",
+                    self.outpoint,
+                    self.prevout.script_pubkey
+                )
+            })
+    }
+}
+
+/// Creates a participant's contribution to the deposit transaction.
+///
+/// The inputs must cover the bet amount plus `fees.fee_per_deposit_input` per input.
+/// The remainder is returned to `change_address`, unless it is dust.
 ///
 /// # Arguments
 /// * `market` - The prediction market
-/// * `bet` - The bet containing the input UTXO information
-/// * `input_index` - The index of the input in the combined pooltransaction
+/// * `bet` - The bet being funded
+/// * `input_index` - Index of the bet in `PredictionMarket::all_bets` order
+/// * `inputs` - The UTXOs funding the bet
+/// * `change_address` - Address receiving the change
 ///
 /// # Returns
-/// A partial transaction ready to be signed with SIGHASH_SINGLE | SIGHASH_ANYONECANPAY
+/// An unsigned contribution PSBT, to be combined with `build_deposit_psbt`
 pub fn create_partial_pool_tx(
     market: &PredictionMarket,
     bet: &Bet,
     input_index: usize,
+    inputs: Vec<DepositInput>,
+    change_address: &Address,
 ) -> anyhow::Result<PartialDepositTx> {
-    let pool_address = generate_pool_address(market)?;
-
-    // Create the input from the bet's UTXO
-    let input = TxIn {
-        previous_output: OutPoint {
-            txid: bet.txid.parse()?,
-            vout: bet.vout,
-        },
-        script_sig: ScriptBuf::new(),
-        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-        witness: Witness::new(),
-    };
+    if inputs.is_empty() {
+        return Err(anyhow::anyhow!("A deposit needs at least one input"));
+    }
+    for input in &inputs {
+        input.input_type()?;
+    }
 
-    // Create the output to the pool address
-    let output_amount = bet.amount.saturating_sub(market.fees.fee_per_deposit_output);
-    let output = TxOut {
-        value: Amount::from_sat(output_amount),
-        script_pubkey: pool_address.script_pubkey(),
-    };
+    let input_total: u64 = inputs
+        .iter()
+        .map(|input| input.prevout.value.to_sat())
+        .sum();
+    let required = bet.amount + market.fees.total_deposit_fees(inputs.len());
+    if input_total < required {
+        return Err(anyhow::anyhow!(
+            "Inputs hold {} sats, less than the {} sats bet plus deposit fees",
+            input_total,
+            required
+        ));
+    }
+
+    let mut outputs = Vec::new();
+    let change = input_total - required;
+    if change > DUST_THRESHOLD {
+        outputs.push(TxOut {
+            value: Amount::from_sat(change),
+            script_pubkey: change_address.script_pubkey(),
+        });
+    }
 
-    // Create the partial transaction
     let transaction = Transaction {
         version: Version(get_tx_version(market.network)),
-        lock_time: LockTime::from_time(market.settlement_timestamp as u32)?,
-        input: vec![input],
-        output: vec![output],
+        lock_time: LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|input| TxIn {
+                previous_output: input.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs,
     };
 
-    Ok(PartialDepositTx {
-        transaction,
-        input_index,
-    })
+    let mut psbt = Psbt::from_unsigned_tx(transaction)?;
+    for (psbt_input, input) in psbt.inputs.iter_mut().zip(inputs) {
+        psbt_input.witness_utxo = Some(input.prevout);
+        psbt_input.redeem_script = input.redeem_script;
+        // ALL|ANYONECANPAY is 0x81 for both ECDSA and Schnorr signatures
+        psbt_input.sighash_type = Some(PsbtSighashType::from(DEPOSIT_ECDSA_SIGHASH_TYPE));
+    }
+
+    Ok(PartialDepositTx { psbt, input_index })
 }
 
-/// Signs a transaction using the provided keypair with SIGHASH_SINGLE | SIGHASH_ANYONECANPAY.
-/// This allows the transaction to be combined with other inputs and outputs later.
+/// Builds the unsigned deposit PSBT from the participants' contributions.
+///
+/// The deposit transaction pays `market.total_amount` to the pool address in its first output,
+/// followed by the change outputs. The contributions can be provided in arbitrary order - they
+/// are sorted by their `input_index` field to ensure proper ordering in the final transaction.
 ///
 /// # Arguments
-/// * `partial_tx` - The partial transaction to sign
-/// * `keypair` - The keypair to sign with (can be created from a PrivateKey)
-/// * `prevout_value` - The value of the UTXO being spent
-/// * `prevout_script` - The script pubkey of the UTXO being spent
+/// * `market` - The prediction market, with its bet set frozen
+/// * `partial_transactions` - Vector of contributions from participants (can be in any order)
 ///
-/// # Returns
-/// The signature that can be used in the transaction witness
-pub fn sign_partial_transaction(
-    partial_tx: &PartialDepositTx,
-    keypair: &Keypair,
-    prevout_value: u64,
-    prevout_script: &ScriptBuf,
-) -> anyhow::Result<Signature> {
-    let secp = Secp256k1::new();
+/// # Errors
+/// Returns an error if the contributions vector is empty or if any contribution is invalid
+pub fn build_deposit_psbt(
+    market: &PredictionMarket,
+    mut partial_transactions: Vec<PartialDepositTx>,
+) -> anyhow::Result<Psbt> {
+    if partial_transactions.is_empty() {
+        return Err(anyhow::anyhow!("Cannot combine empty partial transactions"));
+    }
 
-    // Create the previous outputs for sighash calculation
-    let prevouts = vec![TxOut {
-        value: Amount::from_sat(prevout_value),
-        script_pubkey: prevout_script.clone(),
-    }];
+    // Sort partial transactions by input_index to ensure correct ordering
+    partial_transactions.sort_by_key(|partial_tx| partial_tx.input_index);
 
-    let prevouts = Prevouts::All(&prevouts);
+    let mut inputs = Vec::new();
+    let mut outputs = vec![pool_output(market)?];
+    let mut psbt_inputs = Vec::new();
+    let mut psbt_outputs = vec![psbt::Output::default()];
 
-    // Create sighash cache
-    let mut sighash_cache = SighashCache::new(&partial_tx.transaction);
+    // Collect all inputs and change outputs in sorted order
+    for partial_tx in partial_transactions {
+        let tx = &partial_tx.psbt.unsigned_tx;
+        if tx.input.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Partial transaction {} has no inputs",
+                partial_tx.input_index
+            ));
+        }
+        if tx.output.len() > 1 {
+            return Err(anyhow::anyhow!(
+                "Partial transaction {} must have at most one change output, found {}",
+                partial_tx.input_index,
+                tx.output.len()
+            ));
+        }
 
-    // Use SIGHASH_SINGLE | SIGHASH_ANYONECANPAY to sign only this input and corresponding output
-    let sighash_type = TapSighashType::SinglePlusAnyoneCanPay;
+        inputs.extend(tx.input.iter().cloned());
+        outputs.extend(tx.output.iter().cloned());
+        psbt_inputs.extend(partial_tx.psbt.inputs);
+        psbt_outputs.extend(partial_tx.psbt.outputs);
+    }
 
-    // Calculate the sighash
-    let sighash = sighash_cache.taproot_key_spend_signature_hash(
-        partial_tx.input_index,
-        &prevouts,
-        sighash_type,
-    )?;
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: Version(get_tx_version(market.network)),
+        lock_time: LockTime::ZERO,
+        input: inputs,
+        output: outputs,
+    })?;
+    psbt.inputs = psbt_inputs;
+    psbt.outputs = psbt_outputs;
 
-    // Convert to secp256k1 message
-    let message = Message::from_digest_slice(sighash.as_byte_array())?;
+    Ok(psbt)
+}
 
-    // Sign the message
-    let signature = secp.sign_schnorr(&message, keypair);
+/// Signs a participant's inputs of the deposit PSBT with SIGHASH_ALL | SIGHASH_ANYONECANPAY.
+///
+/// Before signing, checks that the deposit pays the whole pool to the market's pool address
+/// and that the participant's contribution (inputs and change output) is included unchanged.
+/// P2WPKH and P2SH-P2WPKH inputs are signed with ECDSA (BIP-143), P2TR inputs with a
+/// BIP-86 key path spend. Inputs not controlled by `private_key` are left untouched.
+///
+/// # Arguments
+/// * `psbt` - The deposit PSBT built by `build_deposit_psbt`
+/// * `market` - The prediction market
+/// * `contribution` - The participant's own contribution
+/// * `private_key` - Key controlling the contribution's inputs
+///
+/// # Returns
+/// The number of inputs signed
+pub fn sign_deposit_psbt(
+    psbt: &mut Psbt,
+    market: &PredictionMarket,
+    contribution: &PartialDepositTx,
+    private_key: &PrivateKey,
+) -> anyhow::Result<usize> {
+    let secp = Secp256k1::new();
 
-    // Create taproot signature
-    let taproot_signature = Signature {
-        signature,
-        sighash_type,
-    };
+    if psbt.unsigned_tx.output.first() != Some(&pool_output(market)?) {
+        return Err(anyhow::anyhow!(
+            "Deposit transaction does not pay the pool to the market address"
+        ));
+    }
+    for change in &contribution.psbt.unsigned_tx.output {
+        if !psbt.unsigned_tx.output[1..].contains(change) {
+            return Err(anyhow::anyhow!(
+                "Deposit transaction is missing the change output of contribution {}",
+                contribution.input_index
+            ));
+        }
+    }
+
+    let keypair = keypair_from_private_key(private_key)?;
+    let public_key = private_key.public_key(&secp);
+    let p2wpkh_script = public_key
+        .wpubkey_hash()
+        .map(|hash| ScriptBuf::new_p2wpkh(&hash));
+    let p2tr_script = ScriptBuf::new_p2tr(&secp, keypair.x_only_public_key().0, None);
+
+    let mut signed = 0;
+    for (tx_input, own_input) in contribution
+        .psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .zip(&contribution.psbt.inputs)
+    {
+        let index = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .position(|input| input.previous_output == tx_input.previous_output)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Deposit transaction is missing input {}",
+                    tx_input.previous_output
+                )
+            })?;
+        let input = &psbt.inputs[index];
+        if input.witness_utxo != own_input.witness_utxo
+            || input.redeem_script != own_input.redeem_script
+        {
+            return Err(anyhow::anyhow!(
+                "Deposit transaction changed the prevout of input {}",
+                tx_input.previous_output
+            ));
+        }
+        let prevout = input
+            .witness_utxo
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Input {} has no witness UTXO", index))?;
 
-    Ok(taproot_signature)
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+        match DepositInputType::detect(&prevout.script_pubkey, input.redeem_script.as_deref()) {
+            Some(DepositInputType::P2tr) if prevout.script_pubkey == p2tr_script => {
+                let sighash = cache.taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::One(index, &prevout),
+                    DEPOSIT_SIGHASH_TYPE,
+                )?;
+                let message = Message::from_digest(sighash.to_byte_array());
+                let tweaked = keypair.tap_tweak(&secp, None);
+                let signature = secp.sign_schnorr(&message, &tweaked.to_keypair());
+
+                let input = &mut psbt.inputs[index];
+                input.tap_internal_key = Some(keypair.x_only_public_key().0);
+                input.tap_key_sig = Some(taproot::Signature {
+                    signature,
+                    sighash_type: DEPOSIT_SIGHASH_TYPE,
+                });
+            }
+            Some(input_type @ (DepositInputType::P2wpkh | DepositInputType::P2shP2wpkh)) => {
+                let script_code = match (input_type, &input.redeem_script) {
+                    (DepositInputType::P2shP2wpkh, Some(redeem_script)) => redeem_script,
+                    _ => &prevout.script_pubkey,
+                };
+                if p2wpkh_script.as_ref().ok() != Some(script_code) {
+                    continue;
+                }
+                let sighash = cache.p2wpkh_signature_hash(
+                    index,
+                    script_code,
+                    prevout.value,
+                    DEPOSIT_ECDSA_SIGHASH_TYPE,
+                )?;
+                let message = Message::from_digest(sighash.to_byte_array());
+                let signature = secp.sign_ecdsa(&message, &private_key.inner);
+
+                psbt.inputs[index].partial_sigs.insert(
+                    public_key,
+                    ecdsa::Signature {
+                        signature,
+                        sighash_type: DEPOSIT_ECDSA_SIGHASH_TYPE,
+                    },
+                );
+            }
+            _ => continue,
+        }
+        signed += 1;
+    }
+
+    if signed == 0 {
+        return Err(anyhow::anyhow!(
+            "Key does not control any input of contribution {}",
+            contribution.input_index
+        ));
+    }
+    Ok(signed)
 }
 
 /// Helper function to create a keypair from a private key
@@ -146,174 +607,687 @@ pub fn keypair_from_private_key(private_key: &PrivateKey) -> anyhow::Result<Keyp
     Ok(keypair)
 }
 
-/// Adds a signature to the partial transaction's witness data.
+/// Finalizes every input of a deposit PSBT.
+///
+/// Signatures are moved into the final witness (and script sig for P2SH-P2WPKH),
+/// inputs already finalized by a wallet are left untouched.
+pub fn finalize_deposit_psbt(psbt: &mut Psbt) -> anyhow::Result<()> {
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        if input.final_script_witness.is_some() {
+            continue;
+        }
+        let prevout = input
+            .witness_utxo
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Input {} has no witness UTXO", index))?;
+        let input_type =
+            DepositInputType::detect(&prevout.script_pubkey, input.redeem_script.as_deref())
+                .ok_or_else(|| anyhow::anyhow!("Input {} has an unsupported script", index))?;
+
+        let mut witness = Witness::new();
+        let mut script_sig = None;
+        match input_type {
+            DepositInputType::P2tr => {
+                let signature = input
+                    .tap_key_sig
+                    .ok_or_else(|| anyhow::anyhow!("Input {} is not signed", index))?;
+                if signature.sighash_type != DEPOSIT_SIGHASH_TYPE {
+                    return Err(anyhow::anyhow!(
+                        "Input {} is signed with {} instead of {}",
+                        index,
+                        signature.sighash_type,
+                        DEPOSIT_SIGHASH_TYPE
+                    ));
+                }
+                witness.push(signature.to_vec());
+            }
+            DepositInputType::P2wpkh | DepositInputType::P2shP2wpkh => {
+                let (public_key, signature) = input
+                    .partial_sigs
+                    .iter()
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Input {} is not signed", index))?;
+                if signature.sighash_type != DEPOSIT_ECDSA_SIGHASH_TYPE {
+                    return Err(anyhow::anyhow!(
+                        "Input {} is signed with {} instead of {}",
+                        index,
+                        signature.sighash_type,
+                        DEPOSIT_ECDSA_SIGHASH_TYPE
+                    ));
+                }
+                witness.push(signature.to_vec());
+                witness.push(public_key.to_bytes());
+
+                if let Some(redeem_script) = &input.redeem_script {
+                    let push = PushBytesBuf::try_from(redeem_script.to_bytes())?;
+                    script_sig = Some(ScriptBuf::builder().push_slice(push).into_script());
+                }
+            }
+        }
+
+        *input = psbt::Input {
+            witness_utxo: input.witness_utxo.take(),
+            final_script_sig: script_sig,
+            final_script_witness: Some(witness),
+            ..Default::default()
+        };
+    }
+    Ok(())
+}
+
+/// Extracts the deposit transaction from a finalized PSBT.
+///
+/// Checks that every input is finalized and that the fee is neither negative nor absurd.
+pub fn extract_deposit_transaction(psbt: Psbt) -> anyhow::Result<Transaction> {
+    if let Some(index) = psbt
+        .inputs
+        .iter()
+        .position(|input| input.final_script_witness.is_none())
+    {
+        return Err(anyhow::anyhow!("Input {} is not finalized", index));
+    }
+    psbt.extract_tx()
+        .map_err(|e| anyhow::anyhow!("Failed to extract deposit transaction: {}", e))
+}
+
+/// Checks the participants' contributions against the frozen bet set.
+///
+/// Every bet must have exactly one contribution (`input_index` values unique and
+/// contiguous), no UTXO may be spent twice, every prevout must match the one returned
+/// by `prevout_lookup` (the caller's view of the chain, not the PSBT's claim), and the
+/// inputs must cover the bet, the deposit fees and the change output.
 ///
 /// # Arguments
-/// * `partial_tx` - The partial transaction to update
-/// * `signature` - The signature to add to the witness
-pub fn add_signature_to_partial_tx(
-    partial_tx: &mut PartialDepositTx,
-    signature: Signature,
-) -> anyhow::Result<()> {
-    if partial_tx.transaction.input.is_empty() {
-        return Err(anyhow::anyhow!("Transaction has no inputs"));
+/// * `market` - The prediction market, with its bet set frozen
+/// * `contributions` - The participants' contributions (can be in any order)
+/// * `prevout_lookup` - Returns the unspent output at an outpoint, if any
+pub fn verify_contributions<F>(
+    market: &PredictionMarket,
+    contributions: &[PartialDepositTx],
+    prevout_lookup: F,
+) -> Result<(), DepositError>
+where
+    F: Fn(&OutPoint) -> Option<TxOut>,
+{
+    // input_index values must be unique and contiguous
+    let mut counts = vec![0usize; market.all_bets().count()];
+    for contribution in contributions {
+        match counts.get_mut(contribution.input_index) {
+            Some(count) => *count += 1,
+            None => {
+                return Err(DepositError::UnknownBet {
+                    participant: contribution.input_index,
+                })
+            }
+        }
+    }
+    for (participant, count) in counts.into_iter().enumerate() {
+        match count {
+            0 => return Err(DepositError::MissingContribution { participant }),
+            1 => {}
+            _ => return Err(DepositError::DuplicateContribution { participant }),
+        }
     }
 
-    // Create witness with the signature
-    let mut witness = Witness::new();
-    witness.push(signature.to_vec());
+    let mut spent = HashSet::new();
+    for contribution in contributions {
+        verify_contribution(market, contribution, &mut spent, &prevout_lookup)?;
+    }
 
-    // Update the input's witness
-    partial_tx.transaction.input[partial_tx.input_index].witness = witness;
+    Ok(())
+}
+
+/// Checks a single contribution: inputs, prevouts and funds.
+///
+/// `spent` collects the outpoints of the contributions checked so far.
+pub(crate) fn verify_contribution<F>(
+    market: &PredictionMarket,
+    contribution: &PartialDepositTx,
+    spent: &mut HashSet<OutPoint>,
+    prevout_lookup: &F,
+) -> Result<(), DepositError>
+where
+    F: Fn(&OutPoint) -> Option<TxOut>,
+{
+    let participant = contribution.input_index;
+    let bet = market
+        .all_bets()
+        .nth(participant)
+        .ok_or(DepositError::UnknownBet { participant })?;
+    let tx = &contribution.psbt.unsigned_tx;
+    if tx.input.is_empty() || contribution.psbt.inputs.len() != tx.input.len() {
+        return Err(DepositError::NoInputs { participant });
+    }
+    if tx.output.len() > 1 {
+        return Err(DepositError::TooManyOutputs {
+            participant,
+            outputs: tx.output.len(),
+        });
+    }
+
+    let mut available = 0u64;
+    for (tx_input, input) in tx.input.iter().zip(&contribution.psbt.inputs) {
+        let outpoint = tx_input.previous_output;
+        if !spent.insert(outpoint) {
+            return Err(DepositError::DuplicateInput {
+                participant,
+                outpoint,
+            });
+        }
+        let prevout = prevout_lookup(&outpoint).ok_or(DepositError::UnknownPrevout {
+            participant,
+            outpoint,
+        })?;
+        if input.witness_utxo.as_ref() != Some(&prevout) {
+            return Err(DepositError::PrevoutMismatch {
+                participant,
+                outpoint,
+            });
+        }
+        if DepositInputType::detect(&prevout.script_pubkey, input.redeem_script.as_deref())
+            .is_none()
+        {
+            return Err(DepositError::UnsupportedScript {
+                participant,
+                outpoint,
+            });
+        }
+        available += prevout.value.to_sat();
+    }
+
+    let change: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+    let required = bet.amount + market.fees.total_deposit_fees(tx.input.len()) + change;
+    if available < required {
+        return Err(DepositError::InsufficientFunds {
+            participant,
+            available,
+            required,
+        });
+    }
 
     Ok(())
 }
 
-/// Combines multiple signed partial transactions into a single pool deposit transaction.
+/// Checks a signed deposit PSBT before it is finalized.
 ///
-/// This function takes a vector of partial transactions that have been signed by participants
-/// and combines them into a single transaction that deposits all funds into the market pool.
-/// Each partial transaction should contain one input (the bet UTXO) and one output (to the pool address).
+/// On top of [`verify_contributions`], checks that the unsigned transaction is exactly the
+/// one built from the contributions (so every output pays the pool or a participant's
+/// change) and that every input carries a valid `SIGHASH_ALL | SIGHASH_ANYONECANPAY`
+/// signature for its prevout. Inputs already finalized by a wallet are checked too.
 ///
-/// The partial transactions can be provided in arbitrary order - they will be sorted by their
-/// `input_index` field to ensure proper ordering in the final transaction.
+/// # Arguments
+/// * `market` - The prediction market, with its bet set frozen
+/// * `psbt` - The merged deposit PSBT
+/// * `contributions` - The participants' contributions (can be in any order)
+/// * `prevout_lookup` - Returns the unspent output at an outpoint, if any
+pub fn verify_deposit_psbt<F>(
+    market: &PredictionMarket,
+    psbt: &Psbt,
+    contributions: &[PartialDepositTx],
+    prevout_lookup: F,
+) -> Result<(), DepositError>
+where
+    F: Fn(&OutPoint) -> Option<TxOut>,
+{
+    verify_contributions(market, contributions, &prevout_lookup)?;
+
+    let pool = pool_output(market).map_err(|e| DepositError::Pool(e.to_string()))?;
+    if psbt.unsigned_tx.output.first() != Some(&pool) {
+        return Err(DepositError::PoolOutput);
+    }
+    let expected = build_deposit_psbt(market, contributions.to_vec())
+        .map_err(|e| DepositError::Pool(e.to_string()))?;
+    if psbt.unsigned_tx != expected.unsigned_tx || psbt.inputs.len() != expected.inputs.len() {
+        return Err(DepositError::TransactionMismatch);
+    }
+
+    // The expected transaction lists each participant's inputs in input_index order
+    let mut sorted: Vec<&PartialDepositTx> = contributions.iter().collect();
+    sorted.sort_by_key(|contribution| contribution.input_index);
+    let mut first_input = 0;
+    for contribution in sorted {
+        verify_participant_signatures(psbt, contribution, first_input, &prevout_lookup)?;
+        first_input += contribution.psbt.inputs.len();
+    }
+
+    Ok(())
+}
+
+/// Checks the signatures of one participant's inputs, found in the deposit PSBT
+/// from `first_input` on.
+pub(crate) fn verify_participant_signatures<F>(
+    psbt: &Psbt,
+    contribution: &PartialDepositTx,
+    first_input: usize,
+    prevout_lookup: &F,
+) -> Result<(), DepositError>
+where
+    F: Fn(&OutPoint) -> Option<TxOut>,
+{
+    let participant = contribution.input_index;
+    for (offset, (tx_input, own_input)) in contribution
+        .psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .zip(&contribution.psbt.inputs)
+        .enumerate()
+    {
+        let index = first_input + offset;
+        let outpoint = tx_input.previous_output;
+        if psbt
+            .unsigned_tx
+            .input
+            .get(index)
+            .map(|input| input.previous_output)
+            != Some(outpoint)
+            || index >= psbt.inputs.len()
+        {
+            return Err(DepositError::TransactionMismatch);
+        }
+        let prevout = prevout_lookup(&outpoint).ok_or(DepositError::UnknownPrevout {
+            participant,
+            outpoint,
+        })?;
+        verify_input_signature(
+            &psbt.unsigned_tx,
+            index,
+            &psbt.inputs[index],
+            &prevout,
+            own_input.redeem_script.as_deref(),
+        )
+        .map_err(|fault| fault.into_error(participant, outpoint))?;
+    }
+
+    Ok(())
+}
+
+/// Merges the deposit PSBTs signed by each participant, verifies, finalizes and extracts
+/// the deposit transaction.
 ///
 /// # Arguments
-/// * `partial_transactions` - Vector of signed partial transactions from participants (can be in any order)
+/// * `market` - The prediction market, with its bet set frozen
+/// * `contributions` - The participants' contributions (can be in any order)
+/// * `signed_psbts` - The deposit PSBT signed by each participant (can be in any order)
+/// * `prevout_lookup` - Returns the unspent output at an outpoint, if any
 ///
 /// # Returns
 /// A combined transaction ready to be broadcast to the Bitcoin network
 ///
 /// # Errors
-/// Returns an error if the partial transactions vector is empty or if any partial transaction is invalid
-pub fn combine_deposit_transaction(
-    mut partial_transactions: Vec<PartialDepositTx>,
-) -> anyhow::Result<Transaction> {
-    if partial_transactions.is_empty() {
-        return Err(anyhow::anyhow!("Cannot combine empty partial transactions"));
+/// Verification failures are returned as a [`DepositError`] naming the participant at fault
+pub fn combine_deposit_transaction<F>(
+    market: &PredictionMarket,
+    contributions: &[PartialDepositTx],
+    signed_psbts: Vec<Psbt>,
+    prevout_lookup: F,
+) -> anyhow::Result<Transaction>
+where
+    F: Fn(&OutPoint) -> Option<TxOut>,
+{
+    let mut signed_psbts = signed_psbts.into_iter();
+    let mut psbt = signed_psbts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Cannot combine empty signed deposit PSBTs"))?;
+    for other in signed_psbts {
+        psbt.combine(other)
+            .map_err(|e| anyhow::anyhow!("Signed PSBTs do not match: {}", e))?;
     }
 
-    // Sort partial transactions by input_index to ensure correct ordering
-    partial_transactions.sort_by_key(|partial_tx| partial_tx.input_index);
+    verify_deposit_psbt(market, &psbt, contributions, prevout_lookup)?;
+    finalize_deposit_psbt(&mut psbt)?;
+    extract_deposit_transaction(psbt)
+}
 
-    // Use the first transaction as a template for version and lock_time
-    let first_tx = &partial_transactions[0].transaction;
+/// Why a signature check failed, before it is attributed to a participant
+enum SignatureFault {
+    Unsigned,
+    Invalid,
+    WrongSighashType,
+}
 
-    let mut inputs = Vec::new();
-    let mut outputs = Vec::new();
+impl SignatureFault {
+    fn into_error(self, participant: usize, outpoint: OutPoint) -> DepositError {
+        match self {
+            Self::Unsigned => DepositError::Unsigned {
+                participant,
+                outpoint,
+            },
+            Self::Invalid => DepositError::InvalidSignature {
+                participant,
+                outpoint,
+            },
+            Self::WrongSighashType => DepositError::WrongSighashType {
+                participant,
+                outpoint,
+            },
+        }
+    }
+}
 
-    // Collect all inputs and outputs from partial transactions in sorted order
-    for partial_tx in &partial_transactions {
-        // Each partial transaction should have exactly one input and one output
-        if partial_tx.transaction.input.len() != 1 {
-            return Err(anyhow::anyhow!(
-                "Partial transaction must have exactly one input, found {}",
-                partial_tx.transaction.input.len()
-            ));
+/// Verify the signature of one deposit input, from the PSBT fields or its final witness
+fn verify_input_signature(
+    tx: &Transaction,
+    index: usize,
+    input: &psbt::Input,
+    prevout: &TxOut,
+    redeem_script: Option<&Script>,
+) -> Result<(), SignatureFault> {
+    let secp = Secp256k1::verification_only();
+    let final_witness: Vec<&[u8]> = input
+        .final_script_witness
+        .as_ref()
+        .map(|witness| witness.iter().collect())
+        .unwrap_or_default();
+    let mut cache = SighashCache::new(tx);
+
+    match DepositInputType::detect(&prevout.script_pubkey, redeem_script) {
+        Some(DepositInputType::P2tr) => {
+            let signature = match (input.tap_key_sig, final_witness.as_slice()) {
+                (Some(signature), _) => signature,
+                (None, [signature]) => taproot::Signature::from_slice(signature)
+                    .map_err(|_| SignatureFault::Invalid)?,
+                (None, []) => return Err(SignatureFault::Unsigned),
+                _ => return Err(SignatureFault::Invalid),
+            };
+            if signature.sighash_type != DEPOSIT_SIGHASH_TYPE {
+                return Err(SignatureFault::WrongSighashType);
+            }
+            let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+                .map_err(|_| SignatureFault::Invalid)?;
+            let sighash = cache
+                .taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::One(index, prevout),
+                    signature.sighash_type,
+                )
+                .map_err(|_| SignatureFault::Invalid)?;
+            let message = Message::from_digest(sighash.to_byte_array());
+            secp.verify_schnorr(&signature.signature, &message, &output_key)
+                .map_err(|_| SignatureFault::Invalid)
         }
-        if partial_tx.transaction.output.len() != 1 {
-            return Err(anyhow::anyhow!(
-                "Partial transaction must have exactly one output, found {}",
-                partial_tx.transaction.output.len()
-            ));
+        Some(input_type) => {
+            let (public_key, signature) =
+                match (input.partial_sigs.iter().next(), final_witness.as_slice()) {
+                    (Some((public_key, signature)), _) => (*public_key, *signature),
+                    (None, [signature, public_key]) => (
+                        bitcoin::PublicKey::from_slice(public_key)
+                            .map_err(|_| SignatureFault::Invalid)?,
+                        ecdsa::Signature::from_slice(signature)
+                            .map_err(|_| SignatureFault::Invalid)?,
+                    ),
+                    (None, []) => return Err(SignatureFault::Unsigned),
+                    _ => return Err(SignatureFault::Invalid),
+                };
+            if signature.sighash_type != DEPOSIT_ECDSA_SIGHASH_TYPE {
+                return Err(SignatureFault::WrongSighashType);
+            }
+            let script_code = match (input_type, redeem_script) {
+                (DepositInputType::P2shP2wpkh, Some(redeem_script)) => redeem_script,
+                _ => prevout.script_pubkey.as_script(),
+            };
+            let key_hash = public_key
+                .wpubkey_hash()
+                .map_err(|_| SignatureFault::Invalid)?;
+            if ScriptBuf::new_p2wpkh(&key_hash).as_script() != script_code {
+                return Err(SignatureFault::Invalid);
+            }
+            let sighash = cache
+                .p2wpkh_signature_hash(index, script_code, prevout.value, signature.sighash_type)
+                .map_err(|_| SignatureFault::Invalid)?;
+            let message = Message::from_digest(sighash.to_byte_array());
+            secp.verify_ecdsa(&message, &signature.signature, &public_key.inner)
+                .map_err(|_| SignatureFault::Invalid)
         }
-
-        inputs.push(partial_tx.transaction.input[0].clone());
-        outputs.push(partial_tx.transaction.output[0].clone());
+        None => Err(SignatureFault::Invalid),
     }
+}
 
-    // Create the combined transaction
-    let combined_transaction = Transaction {
-        version: first_tx.version,
-        lock_time: first_tx.lock_time,
-        input: inputs,
-        output: outputs,
-    };
-
-    Ok(combined_transaction)
+/// The single output funding the pool with every bet
+pub(crate) fn pool_output(market: &PredictionMarket) -> anyhow::Result<TxOut> {
+    Ok(TxOut {
+        value: Amount::from_sat(market.total_amount),
+        script_pubkey: generate_pool_address(market)?.script_pubkey(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bitcoin::{
-        absolute::LockTime, transaction::Version, Address, Amount, OutPoint, ScriptBuf, Sequence,
-        TxIn, TxOut, Witness,
-    };
-    use std::str::FromStr;
-
-    fn create_test_partial_tx(
-        txid: &str,
-        vout: u32,
-        amount: u64,
-        input_index: usize,
-    ) -> PartialDepositTx {
-        let input = TxIn {
-            previous_output: OutPoint {
-                txid: txid.parse().unwrap(),
-                vout,
+    use crate::test_utils::*;
+
+    /// The contributions and the deposit PSBT signed by each participant on their own copy
+    fn signed_psbts(market: &PredictionMarket) -> (Vec<PartialDepositTx>, Psbt, Vec<Psbt>) {
+        let contributions = deposit_contributions(market);
+        let partials: Vec<_> = contributions.iter().map(|(p, _)| p.clone()).collect();
+        let psbt = build_deposit_psbt(market, partials.clone()).unwrap();
+        let signed = contributions
+            .iter()
+            .map(|(partial, key)| {
+                let mut copy = psbt.clone();
+                sign_deposit_psbt(&mut copy, market, partial, key).unwrap();
+                copy
+            })
+            .collect();
+        (partials, psbt, signed)
+    }
+
+    /// Merge the signed PSBTs, the way `combine_deposit_transaction` does
+    fn merge(mut signed: Vec<Psbt>) -> Psbt {
+        let mut psbt = signed.remove(0);
+        for other in signed {
+            psbt.combine(other).unwrap();
+        }
+        psbt
+    }
+
+    fn prevouts(psbt: &Psbt) -> Vec<TxOut> {
+        psbt.inputs
+            .iter()
+            .map(|input| input.witness_utxo.clone().unwrap())
+            .collect()
+    }
+
+    fn verify_ecdsa_input(tx: &Transaction, index: usize, prevout: &TxOut, script_code: &Script) {
+        let secp = Secp256k1::new();
+        let witness = &tx.input[index].witness;
+        let signature = ecdsa::Signature::from_slice(&witness[0]).unwrap();
+        let public_key = bitcoin::PublicKey::from_slice(&witness[1]).unwrap();
+        assert_eq!(signature.sighash_type, DEPOSIT_ECDSA_SIGHASH_TYPE);
+        assert_eq!(
+            ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash().unwrap()).as_script(),
+            script_code
+        );
+        let sighash = SighashCache::new(tx)
+            .p2wpkh_signature_hash(index, script_code, prevout.value, signature.sighash_type)
+            .unwrap();
+        let message = Message::from_digest(sighash.to_byte_array());
+        secp.verify_ecdsa(&message, &signature.signature, &public_key.inner)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_create_partial_pool_tx() {
+        let market = create_test_market();
+        let bet = &market.bets[0][0];
+        let inputs = vec![
+            p2tr_input(&bettor_key(1), 1, 60000),
+            p2tr_input(&bettor_key(1), 2, 60000),
+        ];
+        let partial =
+            create_partial_pool_tx(&market, bet, 0, inputs.clone(), &change_address(1)).unwrap();
+
+        let tx = &partial.psbt.unsigned_tx;
+        assert_eq!(tx.lock_time, LockTime::ZERO);
+        assert_eq!(tx.input.len(), 2);
+        // 120000 - 100000 bet - 2 * 1000 fees
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value.to_sat(), 18000);
+        assert_eq!(
+            tx.output[0].script_pubkey,
+            change_address(1).script_pubkey()
+        );
+        assert_eq!(
+            partial.psbt.inputs[1].witness_utxo,
+            Some(inputs[1].prevout.clone())
+        );
+        assert_eq!(
+            partial.psbt.inputs[0].sighash_type,
+            Some(PsbtSighashType::from(DEPOSIT_SIGHASH_TYPE))
+        );
+
+        // The PSBT survives a wallet round trip
+        let encoded = partial.psbt.to_string();
+        assert_eq!(encoded.parse::<Psbt>().unwrap(), partial.psbt);
+    }
+
+    #[test]
+    fn test_create_partial_pool_tx_change() {
+        let market = create_test_market();
+        let bet = &market.bets[0][1];
+
+        // Dust change goes to fees
+        let inputs = vec![p2wpkh_input(&bettor_key(2), 3, 51500)];
+        let partial = create_partial_pool_tx(&market, bet, 1, inputs, &change_address(2)).unwrap();
+        assert!(partial.psbt.unsigned_tx.output.is_empty());
+
+        // The inputs must cover the bet plus the deposit fees
+        let inputs = vec![p2wpkh_input(&bettor_key(2), 3, 50999)];
+        let result = create_partial_pool_tx(&market, bet, 1, inputs, &change_address(2));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("less than the 51000 sats bet plus deposit fees"));
+
+        let result = create_partial_pool_tx(&market, bet, 1, vec![], &change_address(2));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_create_partial_pool_tx_input_types() {
+        let market = create_test_market();
+        let bet = &market.bets[1][0];
+
+        let p2sh = p2sh_p2wpkh_input(&bettor_key(3), 4, 200000);
+        assert_eq!(p2sh.input_type().unwrap(), DepositInputType::P2shP2wpkh);
+        assert!(
+            create_partial_pool_tx(&market, bet, 2, vec![p2sh.clone()], &change_address(3)).is_ok()
+        );
+
+        // P2SH inputs need their redeem script
+        let bare_p2sh = DepositInput::new(p2sh.outpoint, p2sh.prevout.clone());
+        assert!(bare_p2sh.input_type().is_err());
+        let wrong_redeem = DepositInput::with_redeem_script(
+            p2sh.outpoint,
+            p2sh.prevout,
+            p2wpkh_script(&bettor_key(4)),
+        );
+        assert!(wrong_redeem.input_type().is_err());
+
+        // Legacy inputs are not supported
+        let secp = Secp256k1::new();
+        let p2pkh = DepositInput::new(
+            test_outpoint(5),
+            TxOut {
+                value: Amount::from_sat(200000),
+                script_pubkey: ScriptBuf::new_p2pkh(&bettor_key(3).public_key(&secp).pubkey_hash()),
             },
-            script_sig: ScriptBuf::new(),
-            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
-            witness: Witness::new(),
-        };
+        );
+        let result = create_partial_pool_tx(&market, bet, 2, vec![p2pkh], &change_address(3));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("unsupported script"));
+    }
 
-        let output = TxOut {
-            value: Amount::from_sat(amount),
-            script_pubkey: Address::from_str("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
-                .unwrap()
-                .assume_checked()
-                .script_pubkey(),
-        };
+    #[test]
+    fn test_build_deposit_psbt() {
+        let market = create_test_market();
+        let contributions = deposit_contributions(&market);
+        // Provide the contributions in reverse order to test sorting
+        let psbt = build_deposit_psbt(
+            &market,
+            contributions.into_iter().rev().map(|(p, _)| p).collect(),
+        )
+        .unwrap();
 
-        let transaction = Transaction {
-            version: Version(2),
-            lock_time: LockTime::from_time(1735689600).unwrap(),
-            input: vec![input],
-            output: vec![output],
-        };
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 4);
+        assert_eq!(psbt.inputs.len(), 4);
+        assert_eq!(tx.input[0].previous_output, test_outpoint(1));
+        assert_eq!(tx.input[3].previous_output, test_outpoint(4));
 
-        PartialDepositTx {
-            transaction,
-            input_index,
-        }
+        // A single pool output holding every bet, then the change outputs
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(tx.output[0].value.to_sat(), market.total_amount);
+        assert_eq!(
+            tx.output[0].script_pubkey,
+            generate_pool_address(&market).unwrap().script_pubkey()
+        );
+        assert_eq!(
+            tx.output[1].script_pubkey,
+            change_address(1).script_pubkey()
+        );
+        assert_eq!(
+            tx.output[2].script_pubkey,
+            change_address(2).script_pubkey()
+        );
     }
 
     #[test]
     fn test_combine_deposit_transaction_success() {
-        let partial_txs = vec![
-            create_test_partial_tx(
-                "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef",
-                0,
-                100000,
-                0,
-            ),
-            create_test_partial_tx(
-                "fedcba0987654321fedcba0987654321fedcba0987654321fedcba0987654321",
-                1,
-                200000,
-                1,
-            ),
-        ];
+        let market = create_test_market();
+        let (partials, psbt, mut signed) = signed_psbts(&market);
+        let prevouts = prevouts(&psbt);
+        let utxos = utxo_set(&partials);
+        // Signatures are merged in any order
+        signed.reverse();
 
-        let result = combine_deposit_transaction(partial_txs);
-        assert!(result.is_ok());
+        let combined_tx = combine_deposit_transaction(&market, &partials, signed, |outpoint| {
+            utxos.get(outpoint).cloned()
+        })
+        .unwrap();
+        assert_eq!(combined_tx.input.len(), 4);
+        assert_eq!(combined_tx.output[0].value.to_sat(), market.total_amount);
+        assert_eq!(combined_tx.version, Version(get_tx_version(market.network)));
+        assert_eq!(combined_tx.lock_time, LockTime::ZERO);
 
-        let combined_tx = result.unwrap();
-        assert_eq!(combined_tx.input.len(), 2);
-        assert_eq!(combined_tx.output.len(), 2);
-        assert_eq!(combined_tx.version, Version(2));
+        // Every input pays the deposit fee on top of its bet
+        let input_total: u64 = prevouts.iter().map(|p| p.value.to_sat()).sum();
+        let output_total: u64 = combined_tx.output.iter().map(|o| o.value.to_sat()).sum();
         assert_eq!(
-            combined_tx.lock_time,
-            LockTime::from_time(1735689600).unwrap()
+            input_total - output_total,
+            4 * market.fees.fee_per_deposit_input
         );
+
+        // Taproot inputs
+        for index in 0..2 {
+            assert_eq!(
+                crate::interpreter::verify_taproot_spend(&combined_tx, index, &prevouts),
+                Ok(())
+            );
+        }
+        // P2WPKH input
+        verify_ecdsa_input(&combined_tx, 2, &prevouts[2], &prevouts[2].script_pubkey);
+        // P2SH-P2WPKH input
+        let redeem_script = p2wpkh_script(&bettor_key(3));
+        let script_sig = ScriptBuf::builder()
+            .push_slice(PushBytesBuf::try_from(redeem_script.to_bytes()).unwrap())
+            .into_script();
+        assert_eq!(combined_tx.input[3].script_sig, script_sig);
+        verify_ecdsa_input(&combined_tx, 3, &prevouts[3], &redeem_script);
     }
 
     #[test]
     fn test_combine_deposit_transaction_empty() {
-        let partial_txs = vec![];
-        let result = combine_deposit_transaction(partial_txs);
-        assert!(result.is_err());
+        let market = create_test_market();
+        let result = combine_deposit_transaction(&market, &[], vec![], |_| None);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Cannot combine empty signed deposit PSBTs"));
+
+        let result = build_deposit_psbt(&market, vec![]);
         assert!(result
             .unwrap_err()
             .to_string()
@@ -321,102 +1295,253 @@ mod tests {
     }
 
     #[test]
-    fn test_combine_deposit_transaction_respects_input_order() {
-        // Create partial transactions in reverse order to test sorting
-        let partial_txs = vec![
-            create_test_partial_tx(
-                "fedcba0987654321fedcba0987654321fedcba0987654321fedcba0987654321",
-                1,
-                200000,
-                2,
-            ), // index 2
-            create_test_partial_tx(
-                "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef",
-                0,
-                100000,
-                0,
-            ), // index 0
-            create_test_partial_tx(
-                "abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890",
-                2,
-                150000,
-                1,
-            ), // index 1
-        ];
+    fn test_combine_deposit_transaction_unsigned() {
+        let market = create_test_market();
+        let (partials, _, mut signed) = signed_psbts(&market);
+        let utxos = utxo_set(&partials);
+        // The P2WPKH participant never returns their signature
+        signed.remove(1);
 
-        let result = combine_deposit_transaction(partial_txs);
-        assert!(result.is_ok());
+        let result = combine_deposit_transaction(&market, &partials, signed, |outpoint| {
+            utxos.get(outpoint).cloned()
+        });
+        assert_eq!(
+            result.unwrap_err().downcast::<DepositError>().unwrap(),
+            DepositError::Unsigned {
+                participant: 1,
+                outpoint: test_outpoint(3)
+            }
+        );
+    }
 
-        let combined_tx = result.unwrap();
-        assert_eq!(combined_tx.input.len(), 3);
-        assert_eq!(combined_tx.output.len(), 3);
+    #[test]
+    fn test_verify_contributions_indices() {
+        let market = create_test_market();
+        let (partials, _, _) = signed_psbts(&market);
+        let utxos = utxo_set(&partials);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+        assert_eq!(verify_contributions(&market, &partials, lookup), Ok(()));
 
-        // Verify that inputs are ordered correctly by their original input_index
-        // First input should be from txid "1234..." (input_index 0)
+        // Duplicated bet
+        let mut duplicated = partials.clone();
+        duplicated[2].input_index = 1;
         assert_eq!(
-            combined_tx.input[0].previous_output.txid.to_string(),
-            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"
+            verify_contributions(&market, &duplicated, lookup),
+            Err(DepositError::DuplicateContribution { participant: 1 })
         );
-        assert_eq!(combined_tx.input[0].previous_output.vout, 0);
 
-        // Second input should be from txid "abcd..." (input_index 1)
+        // Missing bet
         assert_eq!(
-            combined_tx.input[1].previous_output.txid.to_string(),
-            "abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890"
+            verify_contributions(&market, &partials[..2], lookup),
+            Err(DepositError::MissingContribution { participant: 2 })
         );
-        assert_eq!(combined_tx.input[1].previous_output.vout, 2);
 
-        // Third input should be from txid "fedc..." (input_index 2)
+        // No such bet
+        let mut unknown = partials.clone();
+        unknown[2].input_index = 7;
         assert_eq!(
-            combined_tx.input[2].previous_output.txid.to_string(),
-            "fedcba0987654321fedcba0987654321fedcba0987654321fedcba0987654321"
+            verify_contributions(&market, &unknown, lookup),
+            Err(DepositError::UnknownBet { participant: 7 })
         );
-        assert_eq!(combined_tx.input[2].previous_output.vout, 1);
     }
 
     #[test]
-    fn test_combine_deposit_transaction_invalid_inputs() {
-        // Create a partial transaction with multiple inputs (invalid)
-        let mut partial_tx = create_test_partial_tx(
-            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef",
-            0,
-            100000,
-            0,
+    fn test_verify_contributions_prevouts() {
+        let market = create_test_market();
+        let (partials, _, _) = signed_psbts(&market);
+        let utxos = utxo_set(&partials);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+
+        // Spent or unknown UTXO
+        let mut spent = utxos.clone();
+        spent.remove(&test_outpoint(3));
+        let result =
+            verify_contributions(&market, &partials, |outpoint| spent.get(outpoint).cloned());
+        assert_eq!(
+            result,
+            Err(DepositError::UnknownPrevout {
+                participant: 1,
+                outpoint: test_outpoint(3)
+            })
         );
-        partial_tx
-            .transaction
-            .input
-            .push(partial_tx.transaction.input[0].clone());
 
-        let partial_txs = vec![partial_tx];
-        let result = combine_deposit_transaction(partial_txs);
-        assert!(result.is_err());
-        assert!(result
+        // The PSBT lies about the prevout amount
+        let mut inflated = partials.clone();
+        inflated[1].psbt.inputs[0]
+            .witness_utxo
+            .as_mut()
+            .unwrap()
+            .value = Amount::from_sat(1_000_000);
+        assert_eq!(
+            verify_contributions(&market, &inflated, lookup),
+            Err(DepositError::PrevoutMismatch {
+                participant: 1,
+                outpoint: test_outpoint(3)
+            })
+        );
+
+        // The same UTXO funds two bets
+        let mut reused = partials.clone();
+        let input = reused[0].psbt.unsigned_tx.input[0].clone();
+        reused[2].psbt.unsigned_tx.input[0] = input;
+        reused[2].psbt.inputs[0] = reused[0].psbt.inputs[0].clone();
+        assert_eq!(
+            verify_contributions(&market, &reused, lookup),
+            Err(DepositError::DuplicateInput {
+                participant: 2,
+                outpoint: test_outpoint(1)
+            })
+        );
+
+        // Change output larger than the inputs allow
+        let mut greedy = partials.clone();
+        greedy[0].psbt.unsigned_tx.output[0].value = Amount::from_sat(30000);
+        let result = verify_contributions(&market, &greedy, lookup);
+        assert_eq!(
+            result,
+            Err(DepositError::InsufficientFunds {
+                participant: 0,
+                available: 120000,
+                required: 132000
+            })
+        );
+        assert_eq!(result.unwrap_err().participant(), Some(0));
+    }
+
+    #[test]
+    fn test_verify_deposit_psbt_signatures() {
+        let market = create_test_market();
+        let (partials, _, signed) = signed_psbts(&market);
+        let utxos = utxo_set(&partials);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+        let psbt = merge(signed);
+        assert_eq!(
+            verify_deposit_psbt(&market, &psbt, &partials, lookup),
+            Ok(())
+        );
+
+        // Inputs finalized by a wallet are verified from their witness
+        let mut finalized = psbt.clone();
+        finalize_deposit_psbt(&mut finalized).unwrap();
+        assert_eq!(
+            verify_deposit_psbt(&market, &finalized, &partials, lookup),
+            Ok(())
+        );
+
+        // A Taproot signature for another transaction
+        let mut forged = psbt.clone();
+        forged.inputs[1].tap_key_sig = psbt.inputs[0].tap_key_sig;
+        assert_eq!(
+            verify_deposit_psbt(&market, &forged, &partials, lookup),
+            Err(DepositError::InvalidSignature {
+                participant: 0,
+                outpoint: test_outpoint(2)
+            })
+        );
+
+        // An ECDSA signature by the wrong key
+        let mut forged = psbt.clone();
+        forged.inputs[3].partial_sigs = psbt.inputs[2].partial_sigs.clone();
+        assert_eq!(
+            verify_deposit_psbt(&market, &forged, &partials, lookup),
+            Err(DepositError::InvalidSignature {
+                participant: 2,
+                outpoint: test_outpoint(4)
+            })
+        );
+
+        // A signature committing to nothing but its own input
+        let mut forged = psbt.clone();
+        let mut signature = psbt.inputs[0].tap_key_sig.unwrap();
+        signature.sighash_type = TapSighashType::NonePlusAnyoneCanPay;
+        forged.inputs[0].tap_key_sig = Some(signature);
+        assert_eq!(
+            verify_deposit_psbt(&market, &forged, &partials, lookup),
+            Err(DepositError::WrongSighashType {
+                participant: 0,
+                outpoint: test_outpoint(1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_deposit_psbt_outputs() {
+        let market = create_test_market();
+        let (partials, _, signed) = signed_psbts(&market);
+        let utxos = utxo_set(&partials);
+        let lookup = |outpoint: &OutPoint| utxos.get(outpoint).cloned();
+        let psbt = merge(signed);
+
+        // Pool output mis-addressed
+        let mut misaddressed = psbt.clone();
+        misaddressed.unsigned_tx.output[0].script_pubkey = change_address(9).script_pubkey();
+        let result = verify_deposit_psbt(&market, &misaddressed, &partials, lookup);
+        assert_eq!(result, Err(DepositError::PoolOutput));
+        assert_eq!(result.unwrap_err().participant(), None);
+
+        // Extra output siphoning fees
+        let mut extra = psbt.clone();
+        extra.unsigned_tx.output.push(TxOut {
+            value: Amount::from_sat(1000),
+            script_pubkey: change_address(9).script_pubkey(),
+        });
+        extra.outputs.push(psbt::Output::default());
+        assert_eq!(
+            verify_deposit_psbt(&market, &extra, &partials, lookup),
+            Err(DepositError::TransactionMismatch)
+        );
+    }
+
+    #[test]
+    fn test_sign_deposit_psbt_checks_contribution() {
+        let market = create_test_market();
+        let contributions = deposit_contributions(&market);
+        let psbt = build_deposit_psbt(
+            &market,
+            contributions.iter().map(|(p, _)| p.clone()).collect(),
+        )
+        .unwrap();
+        let (partial, key) = &contributions[0];
+
+        // Pool output paying less than the bets
+        let mut tampered = psbt.clone();
+        tampered.unsigned_tx.output[0].value = Amount::from_sat(1000);
+        assert!(sign_deposit_psbt(&mut tampered, &market, partial, key).is_err());
+
+        // Change output redirected
+        let mut tampered = psbt.clone();
+        tampered.unsigned_tx.output[1].script_pubkey = change_address(9).script_pubkey();
+        assert!(sign_deposit_psbt(&mut tampered, &market, partial, key)
             .unwrap_err()
             .to_string()
-            .contains("Partial transaction must have exactly one input"));
+            .contains("missing the change output"));
+
+        // Someone else's key
+        let mut copy = psbt.clone();
+        assert!(sign_deposit_psbt(&mut copy, &market, partial, &bettor_key(2)).is_err());
+
+        let mut copy = psbt;
+        assert_eq!(
+            sign_deposit_psbt(&mut copy, &market, partial, key).unwrap(),
+            2
+        );
     }
 
     #[test]
-    fn test_combine_deposit_transaction_invalid_outputs() {
-        // Create a partial transaction with multiple outputs (invalid)
-        let mut partial_tx = create_test_partial_tx(
-            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef",
-            0,
-            100000,
-            0,
-        );
-        partial_tx
-            .transaction
-            .output
-            .push(partial_tx.transaction.output[0].clone());
+    fn test_build_deposit_psbt_invalid_outputs() {
+        let market = create_test_market();
+        let mut contributions: Vec<_> = deposit_contributions(&market)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        // A contribution with multiple outputs (invalid)
+        let output = contributions[0].psbt.unsigned_tx.output[0].clone();
+        contributions[0].psbt.unsigned_tx.output.push(output);
 
-        let partial_txs = vec![partial_tx];
-        let result = combine_deposit_transaction(partial_txs);
-        assert!(result.is_err());
+        let result = build_deposit_psbt(&market, contributions);
         assert!(result
             .unwrap_err()
             .to_string()
-            .contains("Partial transaction must have exactly one output"));
+            .contains("must have at most one change output"));
     }
 }
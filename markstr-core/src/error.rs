@@ -64,15 +64,58 @@ pub enum MarketError {
     #[error("Network error: {0}")]
     Network(String),
 
+    /// Lifecycle errors (operation not allowed in the current market state)
+    #[error("Invalid market state: {0}")]
+    InvalidState(String),
+
     /// Outcome validation errors
     #[error("Invalid outcome: {0}")]
     InvalidOutcome(String),
 
+    /// Oracle attestation errors
+    #[error("Invalid attestation: {0}")]
+    Attestation(#[from] crate::attestation::AttestationError),
+
+    /// NIP-19 or hex key and id decoding errors
+    #[error(transparent)]
+    Nip19(#[from] crate::nip19::Nip19Error),
+
+    /// Nostr event errors (wrong kind, missing tags, id mismatch)
+    #[error("Invalid event: {0}")]
+    InvalidEvent(String),
+
     /// Generic error for other cases
     #[error("Market error: {0}")]
     Other(String),
 }
 
+impl MarketError {
+    /// Stable machine-readable code of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BitcoinHex(_) | Self::Bitcoin(_) => "bitcoin",
+            Self::TaprootBuilderError(_) => "taproot",
+            Self::Secp256k1(_) => "secp256k1",
+            Self::Hex(_) => "hex",
+            Self::Json(_) => "json",
+            Self::InvalidMarket(_) => "invalid_market",
+            Self::InvalidBet(_) => "invalid_bet",
+            Self::Oracle(_) => "oracle",
+            Self::Settlement(_) => "settlement",
+            Self::Payout(_) => "payout",
+            Self::InvalidAddress(_) => "invalid_address",
+            Self::InvalidSignature(_) => "invalid_signature",
+            Self::Network(_) => "network",
+            Self::InvalidState(_) => "invalid_state",
+            Self::InvalidOutcome(_) => "invalid_outcome",
+            Self::Attestation(_) => "invalid_attestation",
+            Self::Nip19(_) => "nip19",
+            Self::InvalidEvent(_) => "invalid_event",
+            Self::Other(_) => "market",
+        }
+    }
+}
+
 impl From<&str> for MarketError {
     fn from(msg: &str) -> Self {
         Self::Other(msg.to_string())
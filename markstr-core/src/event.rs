@@ -0,0 +1,396 @@
+//! # Nostr Events
+//!
+//! NIP-01 events announcing markets and the outcomes their oracle may attest.
+//!
+//! Both are parameterized replaceable events authored by the oracle, created at the
+//! settlement timestamp:
+//!
+//! - A market announcement ([`MARKET_EVENT_KIND`]) carries the question as content
+//!   and the tags `["d", <identifier>]` and `["outcomes", <outcome id>, ...]`.
+//! - An outcome template ([`OUTCOME_EVENT_KIND`]) carries the outcome description as
+//!   content and the tags `["d", <identifier>]` and `["outcome", <label>]`.
+//!
+//! The market id is the id of the announcement and each outcome id is the id of its
+//! template, which the pool payout leaves commit to. The oracle attests an outcome
+//! by signing its template.
+
+use crate::{
+    attestation::AttestationError,
+    error::Result,
+    market::{outcome_label, PredictionMarket, PredictionOutcome},
+    sha256_hash, sha256_hash_for_nostr_id, MarketError,
+};
+use bitcoin::secp256k1::{Keypair, Message, Secp256k1};
+use serde::{Deserialize, Serialize};
+
+/// Kind of market announcements
+pub const MARKET_EVENT_KIND: u16 = 30_350;
+
+/// Kind of outcome templates
+pub const OUTCOME_EVENT_KIND: u16 = 30_351;
+
+/// Kind of the outcome ids of earlier releases, see [`PredictionOutcome::legacy_nostr_id`]
+pub const LEGACY_OUTCOME_EVENT_KIND: u16 = 42;
+
+/// A Nostr event (NIP-01), unsigned until `sig` is set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NostrEvent {
+    /// Hex-encoded sha256 of the serialized event
+    pub id: String,
+    /// Author's x-only public key (hex-encoded)
+    pub pubkey: String,
+    /// Unix timestamp
+    pub created_at: u64,
+    /// Event kind
+    pub kind: u16,
+    /// Tags, each a name followed by its values
+    pub tags: Vec<Vec<String>>,
+    /// Event content
+    pub content: String,
+    /// BIP-340 signature of the id (hex-encoded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl NostrEvent {
+    /// Builds an unsigned event and computes its id.
+    pub fn new(
+        pubkey: &str,
+        created_at: u64,
+        kind: u16,
+        tags: Vec<Vec<String>>,
+        content: &str,
+    ) -> Self {
+        let mut event = Self {
+            id: String::new(),
+            pubkey: pubkey.to_string(),
+            created_at,
+            kind,
+            tags,
+            content: content.to_string(),
+            sig: None,
+        };
+        event.id = event.compute_id();
+        event
+    }
+
+    /// NIP-01 id of the event fields
+    pub fn compute_id(&self) -> String {
+        let tags: Vec<Vec<&str>> = self
+            .tags
+            .iter()
+            .map(|tag| tag.iter().map(String::as_str).collect())
+            .collect();
+        let tags: Vec<&[&str]> = tags.iter().map(Vec::as_slice).collect();
+        sha256_hash_for_nostr_id(
+            &self.content,
+            &self.pubkey,
+            self.created_at,
+            self.kind.into(),
+            &tags,
+        )
+    }
+
+    /// Whether `id` matches the event fields
+    pub fn has_valid_id(&self) -> bool {
+        self.id == self.compute_id()
+    }
+
+    /// Values of the first tag named `name`
+    pub fn tag(&self, name: &str) -> Option<&[String]> {
+        self.tags
+            .iter()
+            .find(|tag| tag.first().is_some_and(|n| n == name))
+            .map(|tag| &tag[1..])
+    }
+
+    /// Identifier (`d` tag) of a parameterized replaceable event
+    pub fn identifier(&self) -> Option<&str> {
+        self.tag("d")?.first().map(String::as_str)
+    }
+
+    /// Signs the event id with the author's key (BIP-340).
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<()> {
+        if keypair.x_only_public_key().0.to_string() != self.pubkey {
+            return Err(MarketError::InvalidSignature(
+                "Key does not match the event author".to_string(),
+            ));
+        }
+        let id: [u8; 32] = hex::decode(&self.id)?
+            .try_into()
+            .map_err(|_| MarketError::InvalidEvent("Event id must be 32 bytes".to_string()))?;
+        let signature = Secp256k1::new().sign_schnorr(&Message::from_digest(id), keypair);
+        self.sig = Some(hex::encode(signature.serialize()));
+        Ok(())
+    }
+
+    /// Verifies the id and the BIP-340 signature of the event.
+    ///
+    /// See [`crate::attestation::verify_event`].
+    pub fn verify(&self) -> std::result::Result<(), AttestationError> {
+        crate::attestation::verify_event(self)
+    }
+
+    fn expect_kind(&self, kind: u16) -> Result<()> {
+        if self.kind != kind {
+            return Err(MarketError::InvalidEvent(format!(
+                "Expected kind {kind}, got {}",
+                self.kind
+            )));
+        }
+        if !self.has_valid_id() {
+            return Err(MarketError::InvalidEvent(format!(
+                "Event id {} does not match its content",
+                self.id
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `d` tag derived from the fields that make the event unique
+fn identifier_tag(parts: &[&str]) -> Vec<String> {
+    vec!["d".to_string(), sha256_hash(&parts.join("\n"))]
+}
+
+/// Builds the template event of an outcome.
+pub fn outcome_event(outcome: &PredictionOutcome) -> NostrEvent {
+    let timestamp = outcome.timestamp.to_string();
+    NostrEvent::new(
+        &outcome.oracle,
+        outcome.timestamp,
+        OUTCOME_EVENT_KIND,
+        vec![
+            identifier_tag(&[&timestamp, &outcome.label, &outcome.outcome]),
+            vec!["outcome".to_string(), outcome.label.clone()],
+        ],
+        &outcome.outcome,
+    )
+}
+
+/// Builds the announcement event of a market.
+///
+/// # Arguments
+/// * `question` - The market question
+/// * `oracle_pubkey` - Oracle's Nostr public key (hex-encoded), author of the event
+/// * `settlement_timestamp` - When the oracle signs the outcome
+/// * `outcomes` - The market outcomes, in label order
+pub fn market_event(
+    question: &str,
+    oracle_pubkey: &str,
+    settlement_timestamp: u64,
+    outcomes: &[PredictionOutcome],
+) -> NostrEvent {
+    let outcome_ids: Vec<String> = outcomes.iter().map(|o| o.nostr_id()).collect();
+    let timestamp = settlement_timestamp.to_string();
+    let mut identifier = vec![timestamp.as_str(), question];
+    identifier.extend(outcome_ids.iter().map(String::as_str));
+
+    let mut outcomes_tag = vec!["outcomes".to_string()];
+    outcomes_tag.extend(outcome_ids.iter().cloned());
+    NostrEvent::new(
+        oracle_pubkey,
+        settlement_timestamp,
+        MARKET_EVENT_KIND,
+        vec![identifier_tag(&identifier), outcomes_tag],
+        question,
+    )
+}
+
+/// Reconstructs an outcome from its template event.
+pub fn parse_outcome_event(event: &NostrEvent) -> Result<PredictionOutcome> {
+    event.expect_kind(OUTCOME_EVENT_KIND)?;
+    let label = event
+        .tag("outcome")
+        .and_then(|values| values.first())
+        .ok_or_else(|| MarketError::InvalidEvent("Missing outcome tag".to_string()))?;
+    let outcome = PredictionOutcome::new(
+        event.content.clone(),
+        event.pubkey.clone(),
+        event.created_at,
+        label.clone(),
+    )?;
+    if outcome.nostr_id() != event.id {
+        return Err(MarketError::InvalidEvent(format!(
+            "Outcome event {} is not a template built by this library",
+            event.id
+        )));
+    }
+    Ok(outcome)
+}
+
+/// Reconstructs a market from its announcement and outcome templates.
+///
+/// The market id and every outcome id are checked against the events. The
+/// announcement does not carry the network, fees or bets, which keep their
+/// defaults.
+///
+/// # Arguments
+/// * `event` - The market announcement
+/// * `outcome_events` - The templates of the announced outcomes, in any order
+pub fn parse_market_event(
+    event: &NostrEvent,
+    outcome_events: &[NostrEvent],
+) -> Result<PredictionMarket> {
+    event.expect_kind(MARKET_EVENT_KIND)?;
+    let outcome_ids = event
+        .tag("outcomes")
+        .ok_or_else(|| MarketError::InvalidEvent("Missing outcomes tag".to_string()))?;
+
+    let mut outcomes = Vec::with_capacity(outcome_ids.len());
+    for (index, id) in outcome_ids.iter().enumerate() {
+        let outcome_event = outcome_events
+            .iter()
+            .find(|outcome| outcome.id == *id)
+            .ok_or_else(|| MarketError::InvalidEvent(format!("Missing outcome event {id}")))?;
+        let outcome = parse_outcome_event(outcome_event)?;
+        if outcome.label != outcome_label(index)
+            || outcome.oracle != event.pubkey
+            || outcome.timestamp != event.created_at
+        {
+            return Err(MarketError::InvalidEvent(format!(
+                "Outcome event {id} does not belong to market {}",
+                event.id
+            )));
+        }
+        outcomes.push(outcome.outcome);
+    }
+
+    let market = PredictionMarket::new(
+        event.content.clone(),
+        outcomes,
+        event.pubkey.clone(),
+        event.created_at,
+    )?;
+    if market.market_id != event.id {
+        return Err(MarketError::InvalidEvent(format!(
+            "Market event {} is not an announcement built by this library",
+            event.id
+        )));
+    }
+    Ok(market)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use bitcoin::secp256k1::{schnorr, XOnlyPublicKey};
+    use std::str::FromStr;
+
+    fn oracle_keypair() -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[0x11; 32]).unwrap()
+    }
+
+    fn oracle_market() -> PredictionMarket {
+        PredictionMarket::new(
+            "Who wins?".to_string(),
+            vec!["Alice".to_string(), "Bob".to_string(), "Draw".to_string()],
+            oracle_keypair().x_only_public_key().0.to_string(),
+            constants::TEST_SETTLEMENT_TIMESTAMP,
+        )
+        .unwrap()
+    }
+
+    fn outcome_events(market: &PredictionMarket) -> Vec<NostrEvent> {
+        market.outcomes.iter().map(outcome_event).collect()
+    }
+
+    #[test]
+    fn test_outcome_event() {
+        let market = oracle_market();
+        let event = outcome_event(&market.outcomes[1]);
+        assert_eq!(event.kind, OUTCOME_EVENT_KIND);
+        assert_eq!(event.id, market.outcomes[1].nostr_id());
+        assert_eq!(event.pubkey, market.oracle_pubkey);
+        assert_eq!(event.created_at, market.settlement_timestamp);
+        assert_eq!(event.content, "Bob");
+        assert_eq!(event.tag("outcome"), Some(&["B".to_string()][..]));
+        assert!(event.identifier().is_some());
+        assert!(event.sig.is_none());
+        assert_eq!(parse_outcome_event(&event).unwrap(), market.outcomes[1]);
+
+        // Other outcomes are other replaceable events
+        let other = outcome_event(&create_test_outcomes()[1]);
+        assert_ne!(other.identifier(), event.identifier());
+    }
+
+    #[test]
+    fn test_market_event_round_trip() {
+        let market = oracle_market();
+        let mut event = market.announcement_event();
+        assert_eq!(event.kind, MARKET_EVENT_KIND);
+        assert_eq!(event.id, market.market_id);
+        let ids: Vec<String> = market.outcomes.iter().map(|o| o.nostr_id()).collect();
+        assert_eq!(event.tag("outcomes"), Some(&ids[..]));
+
+        event.sign(&oracle_keypair()).unwrap();
+        let json = serde_json::to_string(&event).unwrap();
+        let event: NostrEvent = serde_json::from_str(&json).unwrap();
+
+        let mut templates = outcome_events(&market);
+        templates.reverse();
+        let parsed = PredictionMarket::from_events(&event, &templates).unwrap();
+        assert_eq!(parsed, market);
+    }
+
+    #[test]
+    fn test_signed_event() {
+        let market = oracle_market();
+        let mut event = outcome_event(&market.outcomes[0]);
+        event.sign(&oracle_keypair()).unwrap();
+
+        let signature = schnorr::Signature::from_str(event.sig.as_ref().unwrap()).unwrap();
+        let id: [u8; 32] = hex::decode(&event.id).unwrap().try_into().unwrap();
+        let pubkey = XOnlyPublicKey::from_str(&event.pubkey).unwrap();
+        assert!(Secp256k1::verification_only()
+            .verify_schnorr(&signature, &Message::from_digest(id), &pubkey)
+            .is_ok());
+
+        // Only the oracle can sign its events
+        let other = Keypair::from_seckey_slice(&Secp256k1::new(), &[0x22; 32]).unwrap();
+        assert!(event.sign(&other).is_err());
+
+        // Unsigned events serialize without a signature
+        let json = serde_json::to_value(outcome_event(&market.outcomes[0])).unwrap();
+        assert!(json.get("sig").is_none());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_events() {
+        let market = oracle_market();
+        let event = market.announcement_event();
+        let templates = outcome_events(&market);
+
+        // Tampered question
+        let mut tampered = event.clone();
+        tampered.content = "Who loses?".to_string();
+        assert!(matches!(
+            parse_market_event(&tampered, &templates),
+            Err(MarketError::InvalidEvent(_))
+        ));
+
+        // Consistent id, but not built from the announced outcomes
+        let mut forged = event.clone();
+        forged
+            .tags
+            .push(vec!["t".to_string(), "prediction-market".to_string()]);
+        forged.id = forged.compute_id();
+        assert!(matches!(
+            parse_market_event(&forged, &templates),
+            Err(MarketError::InvalidEvent(_))
+        ));
+
+        // Missing template
+        assert!(parse_market_event(&event, &templates[..2]).is_err());
+
+        // Outcome template of another oracle under the same id
+        let mut stranger = templates[0].clone();
+        stranger.pubkey = constants::TEST_ORACLE_PUBKEY.to_string();
+        assert!(parse_outcome_event(&stranger).is_err());
+
+        // Wrong kinds
+        assert!(parse_outcome_event(&event).is_err());
+        assert!(parse_market_event(&templates[0], &templates).is_err());
+    }
+}
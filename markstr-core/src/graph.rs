@@ -0,0 +1,424 @@
+//! # Transaction Graph
+//!
+//! Every transaction a market can produce, for audits: the deposit funding the
+//! pool, the payout committed in each outcome leaf, and the escape. The payout
+//! and escape transactions are fixed by their CTV templates once the bet set is
+//! frozen; their txids are final once the deposit is.
+//!
+//! [`TransactionGraph::to_dot`] renders the spend graph for Graphviz.
+
+use crate::{
+    deposit::pool_output,
+    inspect::{inspect_pool_spend, BetPayout, SpentLeaf},
+    lifecycle::MarketState,
+    pool::calculate_ctv_hash_from_transaction,
+    withdraw::{build_withdraw_template, WithdrawType},
+    PredictionMarket,
+};
+use anyhow::Result;
+use bitcoin::{
+    absolute::LockTime, consensus::encode::serialize_hex, transaction::Version, Address, Network,
+    OutPoint, ScriptBuf, Transaction, TxIn, Txid,
+};
+use serde::Serialize;
+use std::{fmt::Write, str::FromStr};
+
+/// How the deposit of a graph is known
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DepositSource {
+    /// The deposit transaction was given
+    Transaction,
+    /// Only the pool UTXO recorded in the market is known
+    Recorded,
+    /// Template spending the UTXOs recorded with the bets, without change:
+    /// the txids depending on it are placeholders until the deposit round
+    Template,
+}
+
+/// An output of a graph transaction
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct GraphOutput {
+    /// Address paid, or the hex script for non-standard outputs
+    pub address: String,
+    /// Amount in satoshis
+    pub amount: u64,
+}
+
+/// The deposit funding the pool
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct DepositNode {
+    /// How the deposit is known
+    pub source: DepositSource,
+    /// Id of the deposit transaction
+    pub txid: Txid,
+    /// Outpoints spent, empty when only the pool UTXO is recorded
+    pub inputs: Vec<OutPoint>,
+    /// Outputs, the pool first
+    pub outputs: Vec<GraphOutput>,
+    /// Raw transaction (hex), `None` when only the pool UTXO is recorded
+    pub raw: Option<String>,
+}
+
+/// A payout or escape transaction spending the pool
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawNode {
+    /// Pool leaf spent
+    pub leaf: SpentLeaf,
+    /// Id of the transaction
+    pub txid: Txid,
+    /// CTV hash committed in the leaf, hex-encoded
+    pub ctv_hash: String,
+    /// Leaf script, hex-encoded
+    pub leaf_script: String,
+    /// `nLockTime` of the transaction
+    pub lock_time: u32,
+    /// `nSequence` of the pool input
+    pub sequence: u32,
+    /// Outputs, in template order
+    pub outputs: Vec<GraphOutput>,
+    /// What each bet receives
+    pub payouts: Vec<BetPayout>,
+    /// Fee paid in satoshis
+    pub fee: u64,
+    /// Unsigned raw transaction (hex)
+    pub raw: String,
+}
+
+/// Every transaction a market can produce
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransactionGraph {
+    /// Id of the market
+    pub market_id: String,
+    /// Address of the pool
+    pub pool_address: String,
+    /// Pool UTXO spent by the payouts and the escape
+    pub pool_utxo: OutPoint,
+    /// Amount of the pool in satoshis
+    pub pool_amount: u64,
+    /// Label of each outcome ("A", "B", ...)
+    pub outcome_labels: Vec<String>,
+    /// The deposit funding the pool
+    pub deposit: DepositNode,
+    /// Payout transaction of each outcome, indexed like the outcomes
+    pub payouts: Vec<WithdrawNode>,
+    /// Escape transaction refunding every bet
+    pub escape: WithdrawNode,
+}
+
+/// Builds every transaction of a market from its bet set.
+///
+/// # Arguments
+/// * `market` - The market, with its bets registered
+/// * `deposit` - The deposit transaction, when known. Without it the pool UTXO
+///   recorded in the market is used, or a deposit template before funding.
+///
+/// # Errors
+/// Returns an error if the market has no bets, or if `deposit` does not pay the
+/// pool in its first output
+pub fn build_transaction_graph(
+    market: &PredictionMarket,
+    deposit: Option<&Transaction>,
+) -> Result<TransactionGraph> {
+    let pool_output = pool_output(market)?;
+    let (deposit, pool_utxo) = match (deposit, market.market_utxo) {
+        (Some(tx), _) => {
+            if tx.output.first() != Some(&pool_output) {
+                return Err(anyhow::anyhow!(
+                    "Deposit transaction does not fund the pool in its first output"
+                ));
+            }
+            let deposit = deposit_node(DepositSource::Transaction, tx, market.network);
+            let pool_utxo = OutPoint::new(deposit.txid, 0);
+            (deposit, pool_utxo)
+        }
+        (None, Some(pool_utxo)) => {
+            let output = graph_output(
+                &pool_output.script_pubkey,
+                pool_output.value.to_sat(),
+                market.network,
+            );
+            let deposit = DepositNode {
+                source: DepositSource::Recorded,
+                txid: pool_utxo.txid,
+                inputs: Vec::new(),
+                outputs: vec![output],
+                raw: None,
+            };
+            (deposit, pool_utxo)
+        }
+        (None, None) => {
+            let input = market
+                .all_bets()
+                .filter_map(|bet| Txid::from_str(&bet.txid).ok().map(|txid| (txid, bet.vout)))
+                .map(|(txid, vout)| TxIn {
+                    previous_output: OutPoint::new(txid, vout),
+                    ..TxIn::default()
+                })
+                .collect();
+            let tx = Transaction {
+                version: Version(crate::get_tx_version(market.network)),
+                lock_time: LockTime::ZERO,
+                input,
+                output: vec![pool_output.clone()],
+            };
+            let deposit = deposit_node(DepositSource::Template, &tx, market.network);
+            let pool_utxo = OutPoint::new(deposit.txid, 0);
+            (deposit, pool_utxo)
+        }
+    };
+
+    let pool = market.pool_spend_info()?;
+    let payouts = (0..market.outcomes.len())
+        .map(|outcome_index| {
+            let mut settled = market.clone();
+            settled.state = MarketState::Settled {
+                winning_outcome: outcome_index,
+            };
+            let tx = build_withdraw_template(&settled, &WithdrawType::Payout, pool_utxo)?;
+            withdraw_node(
+                market,
+                SpentLeaf::Payout { outcome_index },
+                &pool.payout_scripts[outcome_index],
+                &tx,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let tx = build_withdraw_template(market, &WithdrawType::Escape, pool_utxo)?;
+    let escape = withdraw_node(market, SpentLeaf::Escape, &pool.escape_script, &tx)?;
+
+    Ok(TransactionGraph {
+        market_id: market.market_id.clone(),
+        pool_address: pool.address().to_string(),
+        pool_utxo,
+        pool_amount: market.total_amount,
+        outcome_labels: market
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.label.clone())
+            .collect(),
+        deposit,
+        payouts,
+        escape,
+    })
+}
+
+fn deposit_node(source: DepositSource, tx: &Transaction, network: Network) -> DepositNode {
+    DepositNode {
+        source,
+        txid: tx.compute_txid(),
+        inputs: tx.input.iter().map(|input| input.previous_output).collect(),
+        outputs: tx
+            .output
+            .iter()
+            .map(|output| graph_output(&output.script_pubkey, output.value.to_sat(), network))
+            .collect(),
+        raw: Some(serialize_hex(tx)),
+    }
+}
+
+fn withdraw_node(
+    market: &PredictionMarket,
+    leaf: SpentLeaf,
+    leaf_script: &ScriptBuf,
+    tx: &Transaction,
+) -> Result<WithdrawNode> {
+    let inspection = inspect_pool_spend(market, tx, None)?;
+    Ok(WithdrawNode {
+        leaf,
+        txid: tx.compute_txid(),
+        ctv_hash: hex::encode(calculate_ctv_hash_from_transaction(tx)),
+        leaf_script: leaf_script.to_hex_string(),
+        lock_time: tx.lock_time.to_consensus_u32(),
+        sequence: tx.input[0].sequence.to_consensus_u32(),
+        outputs: tx
+            .output
+            .iter()
+            .map(|output| {
+                graph_output(&output.script_pubkey, output.value.to_sat(), market.network)
+            })
+            .collect(),
+        payouts: inspection.payouts,
+        fee: inspection.fee.unwrap_or(0),
+        raw: serialize_hex(tx),
+    })
+}
+
+fn graph_output(script_pubkey: &ScriptBuf, amount: u64, network: Network) -> GraphOutput {
+    GraphOutput {
+        address: Address::from_script(script_pubkey, network)
+            .map_or_else(|_| script_pubkey.to_hex_string(), |a| a.to_string()),
+        amount,
+    }
+}
+
+impl TransactionGraph {
+    /// Renders the spend graph in the Graphviz DOT language.
+    ///
+    /// Transactions are boxes, outputs are ellipses; the edges from the pool
+    /// are labeled with the leaf they spend.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph \"market {}\" {{", short(&self.market_id));
+        dot.push_str("  rankdir=LR;\n");
+        dot.push_str("  node [shape=box, fontname=\"monospace\", fontsize=10];\n");
+        dot.push_str("  edge [fontname=\"monospace\", fontsize=9];\n");
+
+        for (index, input) in self.deposit.inputs.iter().enumerate() {
+            let _ = writeln!(
+                dot,
+                "  input{index} [shape=ellipse, label=\"{}:{}\"];",
+                short(&input.txid.to_string()),
+                input.vout
+            );
+            let _ = writeln!(dot, "  input{index} -> deposit;");
+        }
+        let source = match self.deposit.source {
+            DepositSource::Transaction => "",
+            DepositSource::Recorded => "\\n(recorded pool UTXO)",
+            DepositSource::Template => "\\n(template)",
+        };
+        let _ = writeln!(
+            dot,
+            "  deposit [label=\"Deposit{source}\\ntxid {}\"];",
+            short(&self.deposit.txid.to_string())
+        );
+        let _ = writeln!(
+            dot,
+            "  pool [shape=ellipse, style=bold, label=\"Pool\\n{}\\n{} sats\"];",
+            self.pool_address, self.pool_amount
+        );
+        dot.push_str("  deposit -> pool;\n");
+        for (index, output) in self.deposit.outputs.iter().enumerate().skip(1) {
+            let _ = writeln!(
+                dot,
+                "  change{index} [shape=ellipse, label=\"{}\\n{} sats\"];",
+                output.address, output.amount
+            );
+            let _ = writeln!(dot, "  deposit -> change{index};");
+        }
+
+        for (name, node) in self
+            .payouts
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (format!("payout{index}"), node))
+            .chain([("escape".to_string(), &self.escape)])
+        {
+            let (title, edge) = match node.leaf {
+                SpentLeaf::Payout { outcome_index } => {
+                    let label = &self.outcome_labels[outcome_index];
+                    (format!("Payout {label}"), format!("payout leaf {label}"))
+                }
+                SpentLeaf::Escape => (
+                    format!(
+                        "Escape\\nlock time {}, sequence {}",
+                        node.lock_time, node.sequence
+                    ),
+                    "escape leaf".to_string(),
+                ),
+            };
+            let _ = writeln!(
+                dot,
+                "  {name} [label=\"{title}\\ntxid {}\\nCTV {}\\nfee {} sats\"];",
+                short(&node.txid.to_string()),
+                short(&node.ctv_hash),
+                node.fee
+            );
+            let _ = writeln!(dot, "  pool -> {name} [label=\"{edge}\"];");
+            for (index, output) in node.outputs.iter().enumerate() {
+                let _ = writeln!(
+                    dot,
+                    "  {name}_out{index} [shape=ellipse, label=\"{}\\n{} sats\"];",
+                    output.address, output.amount
+                );
+                let _ = writeln!(dot, "  {name} -> {name}_out{index};");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// First and last characters of a long hex string
+fn short(hex: &str) -> String {
+    if hex.len() <= 16 {
+        return hex.to_string();
+    }
+    format!("{}…{}", &hex[..8], &hex[hex.len() - 8..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_graph_from_template() {
+        let market = create_test_market();
+        let graph = build_transaction_graph(&market, None).unwrap();
+
+        assert_eq!(graph.deposit.source, DepositSource::Template);
+        assert_eq!(graph.deposit.outputs[0].amount, market.total_amount);
+        assert_eq!(graph.pool_utxo, OutPoint::new(graph.deposit.txid, 0));
+        assert_eq!(graph.payouts.len(), market.outcomes.len());
+
+        // Each leaf commits to the CTV hash of its transaction
+        let descriptor = market.pool_descriptor(crate::AddressScheme::Pool).unwrap();
+        for (node, leaf) in graph.payouts.iter().zip(&descriptor.payout_leaves) {
+            assert_eq!(&node.leaf_script, leaf);
+            assert!(node.leaf_script.contains(&node.ctv_hash));
+            let paid: u64 = node.outputs.iter().map(|output| output.amount).sum();
+            assert_eq!(paid + node.fee, market.total_amount);
+        }
+        assert!(graph.escape.leaf_script.contains(&graph.escape.ctv_hash));
+        assert_eq!(
+            Some(&graph.escape.leaf_script),
+            descriptor.escape_leaf.as_ref()
+        );
+    }
+
+    #[test]
+    fn test_graph_from_recorded_pool_utxo() {
+        let mut market = create_test_market();
+        market.market_utxo = Some(test_outpoint(7));
+        let graph = build_transaction_graph(&market, None).unwrap();
+
+        assert_eq!(graph.deposit.source, DepositSource::Recorded);
+        assert_eq!(graph.pool_utxo, test_outpoint(7));
+        let payout: Transaction =
+            bitcoin::consensus::encode::deserialize_hex(&graph.payouts[0].raw).unwrap();
+        assert_eq!(payout.input[0].previous_output, test_outpoint(7));
+        assert_eq!(payout.compute_txid(), graph.payouts[0].txid);
+    }
+
+    #[test]
+    fn test_graph_checks_deposit() {
+        let market = create_test_market();
+        let template = build_transaction_graph(&market, None).unwrap();
+        let mut deposit: Transaction =
+            bitcoin::consensus::encode::deserialize_hex(template.deposit.raw.as_ref().unwrap())
+                .unwrap();
+
+        let graph = build_transaction_graph(&market, Some(&deposit)).unwrap();
+        assert_eq!(graph.deposit.source, DepositSource::Transaction);
+        assert_eq!(graph.pool_utxo.txid, deposit.compute_txid());
+
+        deposit.output[0].value = bitcoin::Amount::from_sat(1);
+        assert!(build_transaction_graph(&market, Some(&deposit)).is_err());
+    }
+
+    #[test]
+    fn test_graph_to_dot() {
+        let market = create_test_market();
+        let graph = build_transaction_graph(&market, None).unwrap();
+        let dot = graph.to_dot();
+
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert!(dot.contains("pool -> payout0 [label=\"payout leaf A\"]"));
+        assert!(dot.contains("pool -> payout1 [label=\"payout leaf B\"]"));
+        assert!(dot.contains("pool -> escape [label=\"escape leaf\"]"));
+        assert!(dot.contains(&graph.pool_address));
+    }
+}
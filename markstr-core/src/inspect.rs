@@ -0,0 +1,484 @@
+//! # Pool Transaction Inspection
+//!
+//! Explains a transaction spending a market pool: the leaf it spends, whether
+//! it matches the CTV template committed in that leaf, what each bet receives,
+//! the fee it pays and the elements of its witness.
+//!
+//! Signed transactions name their leaf in the witness. Unsigned transactions
+//! are matched against every leaf: the leaf committing to their CTV hash is the
+//! one they can spend.
+
+use crate::{
+    pool::{
+        build_script_for_escape, build_script_for_outcome, calculate_ctv_hash_from_transaction,
+    },
+    PredictionMarket,
+};
+use anyhow::{Context, Result};
+use bitcoin::{
+    secp256k1::Secp256k1, taproot::ControlBlock, Address, OutPoint, Psbt, ScriptBuf, Transaction,
+    Txid,
+};
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Pool leaf spent by a transaction
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpentLeaf {
+    /// Payout leaf of an outcome
+    Payout {
+        /// Index of the outcome
+        outcome_index: usize,
+    },
+    /// Escape leaf refunding every bet
+    Escape,
+}
+
+/// Role of an element of the pool input witness
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WitnessRole {
+    /// Oracle signature checked by `OP_CHECKSIGFROMSTACK`
+    OracleSignature,
+    /// Script of the spent leaf
+    LeafScript,
+    /// Control block proving the leaf is part of the pool tree
+    ControlBlock,
+    /// Any other stack element
+    StackItem,
+}
+
+/// An element of the pool input witness
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct WitnessElement {
+    /// What the element is used for
+    pub role: WitnessRole,
+    /// The element, hex-encoded
+    pub hex: String,
+}
+
+/// What a bet receives from a pool spend
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct BetPayout {
+    /// Index of the bet, as listed by [`PredictionMarket::all_bets`]
+    pub bet_index: usize,
+    /// Outcome the bet is on
+    pub outcome_index: usize,
+    /// Address of the bettor
+    pub payout_address: String,
+    /// Amount bet in satoshis
+    pub amount: u64,
+    /// Amount paid to the bettor in satoshis
+    pub payout: u64,
+}
+
+/// Explanation of a transaction spending a market pool
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolSpendInspection {
+    /// Id of the transaction
+    pub txid: Txid,
+    /// Outpoint spent by the first input
+    pub pool_input: OutPoint,
+    /// Whether the first input spends the recorded pool UTXO, `None` while
+    /// the market is not funded
+    pub spends_pool_utxo: Option<bool>,
+    /// Leaf spent, `None` if the transaction matches no leaf of the pool
+    pub leaf: Option<SpentLeaf>,
+    /// CTV hash of the transaction, hex-encoded
+    pub ctv_hash: String,
+    /// Whether the CTV hash is the one committed in the spent leaf
+    pub template_matches: bool,
+    /// Whether the control block commits to the leaf script, `None` without witness
+    pub control_block_valid: Option<bool>,
+    /// Whether the oracle signature attests the payout outcome, `None` unless
+    /// the witness spends a payout leaf
+    pub oracle_signature_valid: Option<bool>,
+    /// What each bet receives, in bet order
+    pub payouts: Vec<BetPayout>,
+    /// Amount paid to the market administrator in satoshis
+    pub administrator_fee: u64,
+    /// Indexes of the outputs paying neither a bettor nor the administrator
+    pub unmatched_outputs: Vec<usize>,
+    /// Amount of the pool input in satoshis
+    pub input_amount: u64,
+    /// Sum of the outputs in satoshis
+    pub output_amount: u64,
+    /// Fee paid in satoshis, `None` if the outputs exceed the input
+    pub fee: Option<u64>,
+    /// Witness of the pool input
+    pub witness: Vec<WitnessElement>,
+}
+
+/// Inspects a transaction spending the pool of `market`.
+///
+/// # Arguments
+/// * `market` - The market whose pool is spent
+/// * `tx` - The transaction, signed or not; the pool is spent by its first input
+/// * `pool_amount` - Amount of the pool UTXO, defaults to `market.total_amount`
+pub fn inspect_pool_spend(
+    market: &PredictionMarket,
+    tx: &Transaction,
+    pool_amount: Option<u64>,
+) -> Result<PoolSpendInspection> {
+    let input = tx.input.first().context("Transaction has no input")?;
+    let pool = market.pool_spend_info()?;
+    let ctv_hash = calculate_ctv_hash_from_transaction(tx);
+    let escape_timelock = market.escape_timelock()?;
+
+    // Committed leaf script, and the one committing to this transaction
+    let leaf_scripts = |leaf: SpentLeaf| -> Result<(ScriptBuf, ScriptBuf)> {
+        Ok(match leaf {
+            SpentLeaf::Payout { outcome_index } => (
+                pool.payout_scripts[outcome_index].clone(),
+                build_script_for_outcome(
+                    ctv_hash,
+                    &market.oracle_pubkey,
+                    &market.outcome(outcome_index)?.nostr_id(),
+                )?,
+            ),
+            SpentLeaf::Escape => (
+                pool.escape_script.clone(),
+                build_script_for_escape(ctv_hash, &escape_timelock),
+            ),
+        })
+    };
+    let leaves: Vec<SpentLeaf> = (0..pool.payout_scripts.len())
+        .map(|outcome_index| SpentLeaf::Payout { outcome_index })
+        .chain([SpentLeaf::Escape])
+        .collect();
+
+    let witness: Vec<&[u8]> = input.witness.iter().collect();
+    let witness_script =
+        (witness.len() >= 2).then(|| ScriptBuf::from_bytes(witness[witness.len() - 2].to_vec()));
+    let leaf = match &witness_script {
+        Some(script) => leaves.iter().copied().find(|&leaf| match leaf {
+            SpentLeaf::Payout { outcome_index } => pool.payout_scripts[outcome_index] == *script,
+            SpentLeaf::Escape => pool.escape_script == *script,
+        }),
+        None => {
+            let mut found = None;
+            for &leaf in &leaves {
+                let (committed, expected) = leaf_scripts(leaf)?;
+                if committed == expected {
+                    found = Some(leaf);
+                    break;
+                }
+            }
+            found
+        }
+    };
+    let template_matches = match leaf {
+        Some(leaf) => {
+            let (committed, expected) = leaf_scripts(leaf)?;
+            committed == expected
+        }
+        None => false,
+    };
+
+    let control_block_valid = witness_script.as_ref().map(|script| {
+        let secp = Secp256k1::verification_only();
+        let output_key = pool.spend_info.output_key().to_x_only_public_key();
+        ControlBlock::decode(witness[witness.len() - 1])
+            .is_ok_and(|block| block.verify_taproot_commitment(&secp, output_key, script))
+    });
+    let oracle_signature = match leaf {
+        Some(SpentLeaf::Payout { outcome_index }) if witness.len() == 3 => {
+            Some((witness[0], outcome_index))
+        }
+        _ => None,
+    };
+    let oracle_signature_valid = oracle_signature.map(|(signature, outcome_index)| {
+        let outcome = market.outcomes[outcome_index].nostr_id();
+        market
+            .verify_csfs_signature(signature, &outcome)
+            .unwrap_or(false)
+    });
+
+    let witness = witness
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            let role = if index + 1 == witness.len() && witness_script.is_some() {
+                WitnessRole::ControlBlock
+            } else if index + 2 == witness.len() {
+                WitnessRole::LeafScript
+            } else if oracle_signature.is_some() && index == 0 {
+                WitnessRole::OracleSignature
+            } else {
+                WitnessRole::StackItem
+            };
+            WitnessElement {
+                role,
+                hex: hex::encode(element),
+            }
+        })
+        .collect();
+
+    let (payouts, administrator_fee, unmatched_outputs) = match_outputs(market, tx, leaf);
+    let input_amount = pool_amount.unwrap_or(market.total_amount);
+    let output_amount = tx.output.iter().map(|output| output.value.to_sat()).sum();
+
+    Ok(PoolSpendInspection {
+        txid: tx.compute_txid(),
+        pool_input: input.previous_output,
+        spends_pool_utxo: market.market_utxo.map(|utxo| utxo == input.previous_output),
+        leaf,
+        ctv_hash: hex::encode(ctv_hash),
+        template_matches,
+        control_block_valid,
+        oracle_signature_valid,
+        payouts,
+        administrator_fee,
+        unmatched_outputs,
+        input_amount,
+        output_amount,
+        fee: input_amount.checked_sub(output_amount),
+        witness,
+    })
+}
+
+/// Inspects a PSBT spending the pool of `market`.
+///
+/// The pool amount is taken from the witness UTXO of the first input, and the
+/// witness from its final script witness, when the PSBT has them.
+pub fn inspect_pool_psbt(market: &PredictionMarket, psbt: &Psbt) -> Result<PoolSpendInspection> {
+    let mut tx = psbt.unsigned_tx.clone();
+    let input = psbt.inputs.first();
+    if let (Some(tx_input), Some(witness)) = (
+        tx.input.first_mut(),
+        input.and_then(|input| input.final_script_witness.clone()),
+    ) {
+        tx_input.witness = witness;
+    }
+    let pool_amount = input
+        .and_then(|input| input.witness_utxo.as_ref())
+        .map(|utxo| utxo.value.to_sat());
+    inspect_pool_spend(market, &tx, pool_amount)
+}
+
+/// Assigns the outputs to the bets the spent leaf pays, in the order the
+/// withdraw templates list them, then to the administrator fee.
+///
+/// Returns the payout of every bet, the administrator fee and the indexes of
+/// the outputs left unassigned.
+fn match_outputs(
+    market: &PredictionMarket,
+    tx: &Transaction,
+    leaf: Option<SpentLeaf>,
+) -> (Vec<BetPayout>, u64, Vec<usize>) {
+    let script_pubkey = |address: &str| {
+        Address::from_str(address)
+            .ok()
+            .and_then(|address| address.require_network(market.network).ok())
+            .map(|address| address.script_pubkey())
+    };
+    let mut payouts: Vec<BetPayout> = market
+        .bets
+        .iter()
+        .enumerate()
+        .flat_map(|(outcome, bets)| bets.iter().map(move |bet| (outcome, bet)))
+        .enumerate()
+        .map(|(bet_index, (outcome_index, bet))| BetPayout {
+            bet_index,
+            outcome_index,
+            payout_address: bet.payout_address.clone(),
+            amount: bet.amount,
+            payout: 0,
+        })
+        .collect();
+
+    // A payout to an outcome nobody bet on refunds every bet, like the escape
+    let paid_outcome = match leaf {
+        Some(SpentLeaf::Payout { outcome_index })
+            if market
+                .bets
+                .get(outcome_index)
+                .is_some_and(|bets| !bets.is_empty()) =>
+        {
+            Some(outcome_index)
+        }
+        _ => None,
+    };
+
+    let mut outputs = tx.output.iter().enumerate().peekable();
+    for payout in payouts
+        .iter_mut()
+        .filter(|payout| paid_outcome.is_none_or(|outcome| payout.outcome_index == outcome))
+    {
+        let Some(expected) = script_pubkey(&payout.payout_address) else {
+            continue;
+        };
+        // Dust payouts are left out of the templates
+        if let Some((_, output)) = outputs.next_if(|(_, output)| output.script_pubkey == expected) {
+            payout.payout = output.value.to_sat();
+        }
+    }
+
+    let administrator = market
+        .fees
+        .administrator_address
+        .as_deref()
+        .and_then(script_pubkey);
+    let mut administrator_fee = 0;
+    let mut unmatched_outputs = Vec::new();
+    for (index, output) in outputs {
+        if Some(&output.script_pubkey) == administrator.as_ref() {
+            administrator_fee += output.value.to_sat();
+        } else {
+            unmatched_outputs.push(index);
+        }
+    }
+    (payouts, administrator_fee, unmatched_outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::{
+        lifecycle::MarketState,
+        withdraw::{
+            build_withdraw_transaction, sign_withdraw_transaction, WithdrawParams, WithdrawType,
+        },
+    };
+    use bitcoin::{Amount, TxOut};
+
+    fn settled_market() -> PredictionMarket {
+        let mut market = create_test_market();
+        market.state = MarketState::Settled { winning_outcome: 0 };
+        market.market_utxo = Some(test_outpoint(9));
+        market
+    }
+
+    fn withdraw_tx(market: &PredictionMarket, withdraw_type: WithdrawType) -> Transaction {
+        build_withdraw_transaction(params(market, withdraw_type)).unwrap()
+    }
+
+    fn params(market: &PredictionMarket, withdraw_type: WithdrawType) -> WithdrawParams {
+        WithdrawParams {
+            market: market.clone(),
+            withdraw_type,
+            pool_utxo: market.market_utxo.unwrap(),
+            fee_rate: None,
+        }
+    }
+
+    #[test]
+    fn test_inspect_unsigned_payout() {
+        let market = settled_market();
+        let tx = withdraw_tx(&market, WithdrawType::Payout);
+        let inspection = inspect_pool_spend(&market, &tx, None).unwrap();
+
+        assert_eq!(
+            inspection.leaf,
+            Some(SpentLeaf::Payout { outcome_index: 0 })
+        );
+        assert!(inspection.template_matches);
+        assert_eq!(inspection.spends_pool_utxo, Some(true));
+        assert_eq!(inspection.control_block_valid, None);
+        assert!(inspection.witness.is_empty());
+
+        // Winners are paid their template outputs, losers nothing
+        let paid: Vec<u64> = inspection.payouts.iter().map(|p| p.payout).collect();
+        assert_eq!(
+            paid,
+            vec![tx.output[0].value.to_sat(), tx.output[1].value.to_sat(), 0]
+        );
+        assert!(inspection.unmatched_outputs.is_empty());
+        assert_eq!(inspection.input_amount, market.total_amount);
+        assert_eq!(
+            inspection.fee,
+            Some(market.total_amount - inspection.output_amount)
+        );
+    }
+
+    #[test]
+    fn test_inspect_signed_transactions() {
+        let market = settled_market();
+        let signature = [0x01; 64];
+        for (withdraw_type, leaf, roles) in [
+            (
+                WithdrawType::Payout,
+                SpentLeaf::Payout { outcome_index: 0 },
+                vec![
+                    WitnessRole::OracleSignature,
+                    WitnessRole::LeafScript,
+                    WitnessRole::ControlBlock,
+                ],
+            ),
+            (
+                WithdrawType::Escape,
+                SpentLeaf::Escape,
+                vec![WitnessRole::LeafScript, WitnessRole::ControlBlock],
+            ),
+        ] {
+            let tx = withdraw_tx(&market, withdraw_type.clone());
+            let signed =
+                sign_withdraw_transaction(tx, &params(&market, withdraw_type), Some(&signature))
+                    .unwrap();
+            let inspection = inspect_pool_spend(&market, &signed, None).unwrap();
+
+            assert_eq!(inspection.leaf, Some(leaf));
+            assert!(inspection.template_matches);
+            assert_eq!(inspection.control_block_valid, Some(true));
+            let found: Vec<WitnessRole> = inspection.witness.iter().map(|w| w.role).collect();
+            assert_eq!(found, roles);
+        }
+    }
+
+    #[test]
+    fn test_inspect_escape_refunds_every_bet() {
+        let market = settled_market();
+        let tx = withdraw_tx(&market, WithdrawType::Escape);
+        let inspection = inspect_pool_spend(&market, &tx, None).unwrap();
+
+        assert_eq!(inspection.leaf, Some(SpentLeaf::Escape));
+        let fee = market.fees.fee_per_withdraw_output;
+        for payout in &inspection.payouts {
+            assert_eq!(payout.payout, payout.amount - fee);
+        }
+    }
+
+    #[test]
+    fn test_inspect_tampered_transaction() {
+        let market = settled_market();
+        let mut tx = withdraw_tx(&market, WithdrawType::Payout);
+        tx.output[0].value = Amount::from_sat(tx.output[0].value.to_sat() + 1_000);
+
+        // An unsigned transaction outside the templates spends no leaf
+        let inspection = inspect_pool_spend(&market, &tx, None).unwrap();
+        assert_eq!(inspection.leaf, None);
+        assert!(!inspection.template_matches);
+
+        // A witness names the leaf, whose template does not match
+        let (script, control_block) = market.pool_spend_info().unwrap().payout_leaf(0).unwrap();
+        tx.input[0].witness.push([0x01; 64]);
+        tx.input[0].witness.push(script.as_bytes());
+        tx.input[0].witness.push(control_block.serialize());
+        let inspection = inspect_pool_spend(&market, &tx, None).unwrap();
+        assert_eq!(
+            inspection.leaf,
+            Some(SpentLeaf::Payout { outcome_index: 0 })
+        );
+        assert!(!inspection.template_matches);
+        assert_eq!(inspection.control_block_valid, Some(true));
+        assert_eq!(inspection.oracle_signature_valid, Some(false));
+    }
+
+    #[test]
+    fn test_inspect_psbt() {
+        let market = settled_market();
+        let tx = withdraw_tx(&market, WithdrawType::Payout);
+        let mut psbt = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(market.total_amount + 5_000),
+            script_pubkey: market.pool_spend_info().unwrap().address().script_pubkey(),
+        });
+
+        let inspection = inspect_pool_psbt(&market, &psbt).unwrap();
+        assert_eq!(inspection.txid, tx.compute_txid());
+        assert!(inspection.template_matches);
+        assert_eq!(inspection.input_amount, market.total_amount + 5_000);
+    }
+}
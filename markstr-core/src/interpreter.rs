@@ -0,0 +1,811 @@
+//! # Tapscript Interpreter
+//!
+//! A small in-process interpreter validating Taproot spends of pool outputs,
+//! available with the `test-support` feature.
+//!
+//! It executes tapscript (BIP-342) with the covenant opcodes used by the pool:
+//! - `OP_CTV` (`OP_NOP4`, 0xb3) with BIP-119 semantics
+//! - `OP_CSFS` (`OP_SUCCESS204`, 0xcc) with BIP-348 semantics, messages of any length
+//!
+//! Every other `OP_SUCCESSx` succeeds and `OP_NOPx` (including `OP_NOP5`) does
+//! nothing, as on a node enforcing these BIPs. Only the opcodes needed by the
+//! pool leaves are implemented, any other opcode fails the script. Key path
+//! spends are verified as well, with the BIP-341 sighash. The signature
+//! validation weight budget of BIP-342 is not enforced.
+
+use bitcoin::{
+    hashes::{sha256, Hash},
+    key::{Secp256k1, TweakedPublicKey},
+    opcodes::{
+        all::{OP_CLTV, OP_CSV, OP_DROP, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_SHA256, OP_VERIFY},
+        Class, ClassifyContext,
+    },
+    script::Instruction,
+    secp256k1::{
+        ffi::{self, CPtr},
+        schnorr, Message, XOnlyPublicKey,
+    },
+    sighash::{Prevouts, SighashCache},
+    taproot::{ControlBlock, LeafVersion, TAPROOT_ANNEX_PREFIX},
+    Script, Sequence, Transaction, TxOut,
+};
+use thiserror::Error;
+
+use crate::pool::{calculate_ctv_hash, OP_CSFS, OP_CTV};
+
+/// Maximum size of a stack element
+const MAX_ELEMENT_SIZE: usize = 520;
+
+/// Maximum number of stack elements
+const MAX_STACK_SIZE: usize = 1000;
+
+/// BIP-68 flag disabling the relative timelock of a sequence
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+
+/// BIP-68 flag selecting 512-second intervals instead of blocks
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+
+/// Errors raised while validating a spend
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The input or its prevout does not exist
+    #[error("Input {0} does not exist or has no prevout")]
+    MissingInput(usize),
+
+    /// The prevout is not a Taproot output
+    #[error("Prevout is not a Taproot output")]
+    NotTaproot,
+
+    /// The witness has no element
+    #[error("Empty witness")]
+    EmptyWitness,
+
+    /// The control block cannot be decoded
+    #[error("Invalid control block: {0}")]
+    InvalidControlBlock(String),
+
+    /// The leaf is not committed in the output key
+    #[error("Leaf script is not committed in the output key")]
+    WrongCommitment,
+
+    /// The leaf version is not tapscript
+    #[error("Unsupported leaf version")]
+    UnsupportedLeafVersion,
+
+    /// The script cannot be decoded
+    #[error("Malformed script")]
+    MalformedScript,
+
+    /// The interpreter does not implement this opcode
+    #[error("Unsupported opcode: {0}")]
+    UnsupportedOpcode(String),
+
+    /// An opcode needed more stack elements
+    #[error("Stack underflow")]
+    StackUnderflow,
+
+    /// The stack or one of its elements is too large
+    #[error("Stack size limit exceeded")]
+    StackSize,
+
+    /// `OP_VERIFY` (or a `*VERIFY` opcode) failed
+    #[error("Verify failed")]
+    Verify,
+
+    /// A number operand is malformed
+    #[error("Invalid number operand")]
+    InvalidNumber,
+
+    /// The timelock is not satisfied by the transaction
+    #[error("Unsatisfied timelock")]
+    Timelock,
+
+    /// The transaction does not match the CTV template
+    #[error("CTV template mismatch")]
+    TemplateMismatch,
+
+    /// A non-empty signature failed to verify
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    /// The script did not end with a single true element
+    #[error("Script did not end with a single true element")]
+    CleanStack,
+}
+
+/// Validate the spend of input `input_index` of `tx`.
+///
+/// `prevouts` holds the outputs spent by every input of `tx`, in input order.
+pub fn verify_taproot_spend(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+) -> Result<(), ScriptError> {
+    let input = tx
+        .input
+        .get(input_index)
+        .ok_or(ScriptError::MissingInput(input_index))?;
+    if prevouts.len() != tx.input.len() {
+        return Err(ScriptError::MissingInput(input_index));
+    }
+    let prevout = &prevouts[input_index];
+    if !prevout.script_pubkey.is_p2tr() {
+        return Err(ScriptError::NotTaproot);
+    }
+    let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+        .map_err(|_| ScriptError::NotTaproot)?;
+
+    let mut witness: Vec<&[u8]> = input.witness.iter().collect();
+    if witness.len() >= 2
+        && witness
+            .last()
+            .is_some_and(|last| last.first() == Some(&TAPROOT_ANNEX_PREFIX))
+    {
+        witness.pop();
+    }
+
+    match witness.len() {
+        0 => Err(ScriptError::EmptyWitness),
+        1 => verify_key_spend(tx, input_index, prevouts, output_key, witness[0]),
+        _ => {
+            let control_block = ControlBlock::decode(witness[witness.len() - 1])
+                .map_err(|e| ScriptError::InvalidControlBlock(e.to_string()))?;
+            let script = Script::from_bytes(witness[witness.len() - 2]);
+            if control_block.leaf_version != LeafVersion::TapScript {
+                return Err(ScriptError::UnsupportedLeafVersion);
+            }
+            let secp = Secp256k1::verification_only();
+            if !control_block.verify_taproot_commitment(&secp, output_key, script) {
+                return Err(ScriptError::WrongCommitment);
+            }
+
+            let stack = witness[..witness.len() - 2]
+                .iter()
+                .map(|element| element.to_vec())
+                .collect();
+            execute_tapscript(tx, input_index, script, stack)
+        }
+    }
+}
+
+/// Verify a BIP-341 key path spend.
+fn verify_key_spend(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+    output_key: XOnlyPublicKey,
+    signature: &[u8],
+) -> Result<(), ScriptError> {
+    let signature = bitcoin::taproot::Signature::from_slice(signature)
+        .map_err(|_| ScriptError::InvalidSignature)?;
+    let sighash = SighashCache::new(tx)
+        .taproot_key_spend_signature_hash(
+            input_index,
+            &Prevouts::All(prevouts),
+            signature.sighash_type,
+        )
+        .map_err(|_| ScriptError::InvalidSignature)?;
+    let message = Message::from_digest(sighash.to_byte_array());
+    let output_key = TweakedPublicKey::dangerous_assume_tweaked(output_key);
+    Secp256k1::verification_only()
+        .verify_schnorr(
+            &signature.signature,
+            &message,
+            &output_key.to_x_only_public_key(),
+        )
+        .map_err(|_| ScriptError::InvalidSignature)
+}
+
+/// Execute a tapscript leaf against the initial witness `stack`.
+pub fn execute_tapscript(
+    tx: &Transaction,
+    input_index: usize,
+    script: &Script,
+    mut stack: Vec<Vec<u8>>,
+) -> Result<(), ScriptError> {
+    if stack.iter().any(|element| element.len() > MAX_ELEMENT_SIZE) {
+        return Err(ScriptError::StackSize);
+    }
+
+    // Any OP_SUCCESSx makes the script succeed unconditionally (BIP-342),
+    // except OP_SUCCESS204 which BIP-348 turned into OP_CHECKSIGFROMSTACK
+    for instruction in script.instructions() {
+        if let Instruction::Op(opcode) = instruction.map_err(|_| ScriptError::MalformedScript)? {
+            if opcode != OP_CSFS && opcode.classify(ClassifyContext::TapScript) == Class::SuccessOp
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    for instruction in script.instructions() {
+        match instruction.map_err(|_| ScriptError::MalformedScript)? {
+            Instruction::PushBytes(bytes) => stack.push(bytes.as_bytes().to_vec()),
+            Instruction::Op(opcode) => match opcode.classify(ClassifyContext::TapScript) {
+                Class::PushNum(n) => stack.push(encode_num(n as i64)),
+                _ if opcode == OP_CTV => check_template_verify(tx, input_index, &stack)?,
+                _ if opcode == OP_CSFS => {
+                    let result = check_sig_from_stack(&mut stack)?;
+                    stack.push(if result { vec![1] } else { vec![] });
+                }
+                _ if opcode == OP_CLTV => check_lock_time_verify(tx, input_index, &stack)?,
+                _ if opcode == OP_CSV => check_sequence_verify(tx, input_index, &stack)?,
+                Class::NoOp => {}
+                _ if opcode == OP_DROP => {
+                    pop(&mut stack)?;
+                }
+                _ if opcode == OP_DUP => {
+                    let top = stack.last().ok_or(ScriptError::StackUnderflow)?.clone();
+                    stack.push(top);
+                }
+                _ if opcode == OP_VERIFY => {
+                    if !cast_to_bool(&pop(&mut stack)?) {
+                        return Err(ScriptError::Verify);
+                    }
+                }
+                _ if opcode == OP_EQUAL || opcode == OP_EQUALVERIFY => {
+                    let equal = pop(&mut stack)? == pop(&mut stack)?;
+                    if opcode == OP_EQUALVERIFY {
+                        if !equal {
+                            return Err(ScriptError::Verify);
+                        }
+                    } else {
+                        stack.push(if equal { vec![1] } else { vec![] });
+                    }
+                }
+                _ if opcode == OP_SHA256 => {
+                    let element = pop(&mut stack)?;
+                    stack.push(sha256::Hash::hash(&element).to_byte_array().to_vec());
+                }
+                _ => return Err(ScriptError::UnsupportedOpcode(opcode.to_string())),
+            },
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+    }
+
+    if stack.len() == 1 && cast_to_bool(&stack[0]) {
+        Ok(())
+    } else {
+        Err(ScriptError::CleanStack)
+    }
+}
+
+/// BIP-119 `OP_CHECKTEMPLATEVERIFY`: a 32-byte top element must match the
+/// template hash of the spending transaction, other sizes are a NOP.
+fn check_template_verify(
+    tx: &Transaction,
+    input_index: usize,
+    stack: &[Vec<u8>],
+) -> Result<(), ScriptError> {
+    let top = stack.last().ok_or(ScriptError::StackUnderflow)?;
+    if top.len() != 32 {
+        return Ok(());
+    }
+    if top.as_slice() == calculate_ctv_hash(tx, input_index as u32) {
+        Ok(())
+    } else {
+        Err(ScriptError::TemplateMismatch)
+    }
+}
+
+/// BIP-348 `OP_CHECKSIGFROMSTACK`: pops a public key, a message and a
+/// signature, and returns whether the (non-empty) signature is valid.
+fn check_sig_from_stack(stack: &mut Vec<Vec<u8>>) -> Result<bool, ScriptError> {
+    let pubkey = pop(stack)?;
+    let message = pop(stack)?;
+    let signature = pop(stack)?;
+
+    if pubkey.is_empty() {
+        return Err(ScriptError::InvalidSignature);
+    }
+    if signature.is_empty() {
+        return Ok(false);
+    }
+    if pubkey.len() != 32 {
+        // Unknown public key type, upgradable
+        return Ok(true);
+    }
+
+    let pubkey = XOnlyPublicKey::from_slice(&pubkey).map_err(|_| ScriptError::InvalidSignature)?;
+    let signature =
+        schnorr::Signature::from_slice(&signature).map_err(|_| ScriptError::InvalidSignature)?;
+    if !verify_schnorr(&signature, &message, &pubkey) {
+        return Err(ScriptError::InvalidSignature);
+    }
+    Ok(true)
+}
+
+/// BIP-340 verification of a message of any length.
+///
+/// BIP-348 signs the message itself, not its hash, so it is not limited to 32
+/// bytes. `secp256k1` only exposes 32-byte messages, so libsecp256k1 is
+/// called directly.
+fn verify_schnorr(signature: &schnorr::Signature, message: &[u8], pubkey: &XOnlyPublicKey) -> bool {
+    let secp = Secp256k1::verification_only();
+    // SAFETY: the pointers are valid for the duration of the call, libsecp256k1
+    // reads 64 bytes of signature and `message.len()` bytes of message
+    unsafe {
+        ffi::secp256k1_schnorrsig_verify(
+            secp.ctx().as_ptr(),
+            signature.as_c_ptr(),
+            message.as_c_ptr(),
+            message.len(),
+            pubkey.as_c_ptr(),
+        ) == 1
+    }
+}
+
+/// BIP-65 `OP_CHECKLOCKTIMEVERIFY`.
+fn check_lock_time_verify(
+    tx: &Transaction,
+    input_index: usize,
+    stack: &[Vec<u8>],
+) -> Result<(), ScriptError> {
+    let lock_time = read_num(stack.last().ok_or(ScriptError::StackUnderflow)?, 5)?;
+    if lock_time < 0 {
+        return Err(ScriptError::Timelock);
+    }
+    let tx_lock_time = tx.lock_time.to_consensus_u32() as i64;
+    const THRESHOLD: i64 = bitcoin::absolute::LOCK_TIME_THRESHOLD as i64;
+    let same_kind = (lock_time < THRESHOLD) == (tx_lock_time < THRESHOLD);
+    if !same_kind || lock_time > tx_lock_time || tx.input[input_index].sequence == Sequence::MAX {
+        return Err(ScriptError::Timelock);
+    }
+    Ok(())
+}
+
+/// BIP-112 `OP_CHECKSEQUENCEVERIFY`.
+fn check_sequence_verify(
+    tx: &Transaction,
+    input_index: usize,
+    stack: &[Vec<u8>],
+) -> Result<(), ScriptError> {
+    let sequence = read_num(stack.last().ok_or(ScriptError::StackUnderflow)?, 5)?;
+    if sequence < 0 {
+        return Err(ScriptError::Timelock);
+    }
+    let sequence = sequence as u32;
+    if sequence & SEQUENCE_DISABLE_FLAG != 0 {
+        return Ok(());
+    }
+
+    let tx_sequence = tx.input[input_index].sequence.to_consensus_u32();
+    if tx.version.0 < 2 || tx_sequence & SEQUENCE_DISABLE_FLAG != 0 {
+        return Err(ScriptError::Timelock);
+    }
+    let mask = SEQUENCE_TYPE_FLAG | 0x0000_ffff;
+    let (required, actual) = (sequence & mask, tx_sequence & mask);
+    let same_kind = (required & SEQUENCE_TYPE_FLAG) == (actual & SEQUENCE_TYPE_FLAG);
+    if !same_kind || required > actual {
+        return Err(ScriptError::Timelock);
+    }
+    Ok(())
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, ScriptError> {
+    stack.pop().ok_or(ScriptError::StackUnderflow)
+}
+
+fn cast_to_bool(element: &[u8]) -> bool {
+    match element.split_last() {
+        None => false,
+        Some((last, rest)) => rest.iter().any(|b| *b != 0) || (*last != 0 && *last != 0x80),
+    }
+}
+
+/// Decode a minimally encoded script number of at most `max_len` bytes.
+fn read_num(element: &[u8], max_len: usize) -> Result<i64, ScriptError> {
+    if element.len() > max_len {
+        return Err(ScriptError::InvalidNumber);
+    }
+    let Some((last, rest)) = element.split_last() else {
+        return Ok(0);
+    };
+    if *last & 0x7f == 0 && rest.last().is_none_or(|b| b & 0x80 == 0) {
+        return Err(ScriptError::InvalidNumber);
+    }
+    let mut value = 0i64;
+    for (i, byte) in element.iter().enumerate() {
+        value |= (*byte as i64) << (8 * i);
+    }
+    if last & 0x80 != 0 {
+        value &= !(0x80i64 << (8 * (element.len() - 1)));
+        value = -value;
+    }
+    Ok(value)
+}
+
+fn encode_num(n: i64) -> Vec<u8> {
+    let mut result = Vec::new();
+    let negative = n < 0;
+    let mut abs = n.unsigned_abs();
+    while abs > 0 {
+        result.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    if let Some(last) = result.last_mut() {
+        if *last & 0x80 != 0 {
+            result.push(if negative { 0x80 } else { 0 });
+        } else if negative {
+            *last |= 0x80;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lifecycle::MarketState,
+        test_utils::*,
+        withdraw::{
+            build_withdraw_transaction, sign_withdraw_transaction, WithdrawParams, WithdrawType,
+        },
+        EscapeTimelock, PayoutCurve, PredictionMarket, ScalarMarket, ScalarSide,
+    };
+    use bitcoin::{
+        absolute::LockTime, opcodes::all::OP_NOP5, relative, script::Builder, script::PushBytesBuf,
+        Amount, OutPoint, Witness,
+    };
+    use std::str::FromStr;
+
+    const ORACLE_SECRET: [u8; 32] = [0x11; 32];
+
+    fn oracle_market() -> PredictionMarket {
+        let secp = Secp256k1::new();
+        let keypair = bitcoin::key::Keypair::from_seckey_slice(&secp, &ORACLE_SECRET).unwrap();
+        let mut market = create_test_market();
+        market.oracle_pubkey = keypair.x_only_public_key().0.to_string();
+        market
+    }
+
+    fn pool_prevout(market: &PredictionMarket) -> Vec<TxOut> {
+        vec![TxOut {
+            value: Amount::from_sat(market.total_amount),
+            script_pubkey: market.pool_spend_info().unwrap().address().script_pubkey(),
+        }]
+    }
+
+    fn signed_withdraw(
+        market: &PredictionMarket,
+        withdraw_type: WithdrawType,
+        oracle_signature: Option<&[u8]>,
+    ) -> Transaction {
+        let params = WithdrawParams {
+            market: market.clone(),
+            withdraw_type,
+            pool_utxo: OutPoint::null(),
+            fee_rate: None,
+        };
+        let tx = build_withdraw_transaction(params.clone()).unwrap();
+        sign_withdraw_transaction(tx, &params, oracle_signature).unwrap()
+    }
+
+    #[test]
+    fn test_payout_paths_validate() {
+        for winning_outcome in 0..2 {
+            let mut market = oracle_market();
+            market.state = MarketState::Settled { winning_outcome };
+            let outcome_id = market.outcomes[winning_outcome].nostr_id();
+            let signature = market
+                .create_csfs_signature(&ORACLE_SECRET, &outcome_id)
+                .unwrap();
+
+            let tx = signed_withdraw(&market, WithdrawType::Payout, Some(&signature));
+            assert_eq!(verify_taproot_spend(&tx, 0, &pool_prevout(&market)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_payout_requires_oracle_signature() {
+        let mut market = oracle_market();
+        market.state = MarketState::Settled { winning_outcome: 0 };
+        let prevouts = pool_prevout(&market);
+
+        // Signature for the other outcome
+        let other_id = market.outcomes[1].nostr_id();
+        let signature = market
+            .create_csfs_signature(&ORACLE_SECRET, &other_id)
+            .unwrap();
+        let tx = signed_withdraw(&market, WithdrawType::Payout, Some(&signature));
+        assert_eq!(
+            verify_taproot_spend(&tx, 0, &prevouts),
+            Err(ScriptError::InvalidSignature)
+        );
+
+        // An empty signature must not bypass the oracle
+        let tx = signed_withdraw(&market, WithdrawType::Payout, Some(&[]));
+        assert_eq!(
+            verify_taproot_spend(&tx, 0, &prevouts),
+            Err(ScriptError::Verify)
+        );
+    }
+
+    #[test]
+    fn test_payout_template_mismatch() {
+        let mut market = oracle_market();
+        market.state = MarketState::Settled { winning_outcome: 0 };
+        let outcome_id = market.outcomes[0].nostr_id();
+        let signature = market
+            .create_csfs_signature(&ORACLE_SECRET, &outcome_id)
+            .unwrap();
+
+        let mut tx = signed_withdraw(&market, WithdrawType::Payout, Some(&signature));
+        tx.output[0].value = Amount::from_sat(tx.output[0].value.to_sat() - 1);
+        assert_eq!(
+            verify_taproot_spend(&tx, 0, &pool_prevout(&market)),
+            Err(ScriptError::TemplateMismatch)
+        );
+    }
+
+    #[test]
+    fn test_escape_path_validates() {
+        let mut market = oracle_market();
+        market.state = MarketState::Funded;
+        let tx = signed_withdraw(&market, WithdrawType::Escape, None);
+        assert_eq!(verify_taproot_spend(&tx, 0, &pool_prevout(&market)), Ok(()));
+
+        // The escape leaf enforces its own timelock
+        let mut early = tx.clone();
+        early.lock_time = LockTime::from_time(market.settlement_timestamp as u32).unwrap();
+        assert_eq!(
+            verify_taproot_spend(&early, 0, &pool_prevout(&market)),
+            Err(ScriptError::Timelock)
+        );
+    }
+
+    #[test]
+    fn test_relative_escape_path_validates() {
+        let mut market = oracle_market();
+        market.state = MarketState::Funded;
+        market.escape_timelock = Some(EscapeTimelock::Relative(relative::LockTime::from_height(
+            144,
+        )));
+        let tx = signed_withdraw(&market, WithdrawType::Escape, None);
+        assert_eq!(verify_taproot_spend(&tx, 0, &pool_prevout(&market)), Ok(()));
+
+        let mut early = tx.clone();
+        early.input[0].sequence = Sequence::from_height(143);
+        assert_eq!(
+            verify_taproot_spend(&early, 0, &pool_prevout(&market)),
+            Err(ScriptError::Timelock)
+        );
+    }
+
+    #[test]
+    fn test_wrong_leaf_commitment() {
+        let mut market = oracle_market();
+        market.state = MarketState::Funded;
+        let mut tx = signed_withdraw(&market, WithdrawType::Escape, None);
+
+        let other = create_test_market_with_outcomes(&[vec![10000], vec![20000]]);
+        assert_eq!(
+            verify_taproot_spend(&tx, 0, &pool_prevout(&other)),
+            Err(ScriptError::WrongCommitment)
+        );
+
+        tx.input[0].witness = Witness::new();
+        assert_eq!(
+            verify_taproot_spend(&tx, 0, &pool_prevout(&market)),
+            Err(ScriptError::EmptyWitness)
+        );
+    }
+
+    /// BIP-340 signature of a message of any length by the oracle
+    fn sign_message(message: &[u8]) -> Vec<u8> {
+        let secp = Secp256k1::new();
+        let keypair = bitcoin::key::Keypair::from_seckey_slice(&secp, &ORACLE_SECRET).unwrap();
+        let mut signature = [0u8; 64];
+        // SAFETY: the pointers are valid for the duration of the call, the
+        // default nonce function is used when the extra parameters are null
+        let signed = unsafe {
+            ffi::secp256k1_schnorrsig_sign_custom(
+                secp.ctx().as_ptr(),
+                signature.as_mut_c_ptr(),
+                message.as_c_ptr(),
+                message.len(),
+                keypair.as_c_ptr(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(signed, 1);
+        signature.to_vec()
+    }
+
+    #[test]
+    fn test_csfs_messages_of_any_length() {
+        let market = oracle_market();
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: Vec::new(),
+        };
+        let pubkey = XOnlyPublicKey::from_str(&market.oracle_pubkey).unwrap();
+        let leaf = |message: &[u8]| {
+            Builder::new()
+                .push_slice(PushBytesBuf::try_from(message.to_vec()).unwrap())
+                .push_x_only_key(&pubkey)
+                .push_opcode(OP_CSFS)
+                .into_script()
+        };
+
+        for len in [0, 1, 17, 32, 100] {
+            let message = vec![0x99; len];
+            let signature = sign_message(&message);
+            assert_eq!(
+                execute_tapscript(&tx, 0, &leaf(&message), vec![signature.clone()]),
+                Ok(()),
+                "{len}-byte message"
+            );
+            assert_eq!(
+                execute_tapscript(&tx, 0, &leaf(&[0x98; 17]), vec![signature]),
+                Err(ScriptError::InvalidSignature),
+                "{len}-byte message"
+            );
+        }
+
+        // Signatures of the oracle over 32-byte messages verify the same way
+        let outcome_id = market.outcomes[0].nostr_id();
+        let signature = market
+            .create_csfs_signature(&ORACLE_SECRET, &outcome_id)
+            .unwrap();
+        let message = sha256::Hash::hash(outcome_id.as_bytes()).to_byte_array();
+        assert_eq!(
+            execute_tapscript(&tx, 0, &leaf(&message), vec![signature]),
+            Ok(())
+        );
+
+        // An empty signature pushes false
+        assert_eq!(
+            execute_tapscript(&tx, 0, &leaf(&message), vec![vec![]]),
+            Err(ScriptError::CleanStack)
+        );
+    }
+
+    #[test]
+    fn test_nop5_leaf_fails() {
+        // Payout leaf with OP_NOP5 in place of OP_CHECKSIGFROMSTACK
+        let mut market = oracle_market();
+        market.state = MarketState::Settled { winning_outcome: 0 };
+        let outcome_id = market.outcomes[0].nostr_id();
+        let signature = market
+            .create_csfs_signature(&ORACLE_SECRET, &outcome_id)
+            .unwrap();
+        let tx = signed_withdraw(&market, WithdrawType::Payout, Some(&signature));
+        let pubkey = XOnlyPublicKey::from_str(&market.oracle_pubkey).unwrap();
+        let nop5_leaf = Builder::new()
+            .push_slice(sha256::Hash::hash(outcome_id.as_bytes()).as_byte_array())
+            .push_x_only_key(&pubkey)
+            .push_opcode(OP_NOP5)
+            .push_opcode(OP_VERIFY)
+            .push_slice(calculate_ctv_hash(&tx, 0))
+            .push_opcode(OP_CTV)
+            .into_script();
+
+        // OP_NOP5 checks nothing and leaves the signature and message on the stack
+        assert_eq!(
+            execute_tapscript(&tx, 0, &nop5_leaf, vec![signature]),
+            Err(ScriptError::CleanStack)
+        );
+        assert_eq!(
+            execute_tapscript(&tx, 0, &nop5_leaf, vec![]),
+            Err(ScriptError::CleanStack)
+        );
+    }
+
+    fn oracle_scalar_market() -> ScalarMarket {
+        let secp = Secp256k1::new();
+        let keypair = bitcoin::key::Keypair::from_seckey_slice(&secp, &ORACLE_SECRET).unwrap();
+        let curve = PayoutCurve {
+            base: 10,
+            num_digits: 3,
+            floor: 100,
+            cap: 200,
+            precision: 10,
+        };
+        let mut market = ScalarMarket::new(
+            "BTC/USD price in thousands?".to_string(),
+            keypair.x_only_public_key().0.to_string(),
+            1735689600,
+            curve,
+        )
+        .unwrap();
+        market.network = bitcoin::Network::Regtest;
+        for (index, side) in [ScalarSide::Long, ScalarSide::Short]
+            .into_iter()
+            .enumerate()
+        {
+            market
+                .place_bet(
+                    side,
+                    100000,
+                    create_valid_regtest_address(index as u8 + 1),
+                    "txid".to_string(),
+                    index as u32,
+                )
+                .unwrap();
+        }
+        market
+    }
+
+    /// Oracle signatures of every digit prefix of `value`
+    fn scalar_attestation(market: &ScalarMarket, value: u64) -> Vec<Vec<u8>> {
+        market
+            .attestation_ids(value)
+            .unwrap()
+            .iter()
+            .map(|id| sign_message(sha256::Hash::hash(id.as_bytes()).as_byte_array()))
+            .collect()
+    }
+
+    #[test]
+    fn test_scalar_paths_validate() {
+        let market = oracle_scalar_market();
+        let prevouts = vec![TxOut {
+            value: Amount::from_sat(market.total_amount),
+            script_pubkey: market.pool_address().unwrap().script_pubkey(),
+        }];
+
+        for value in [0, 157, 999] {
+            let step = market.payout_step(value);
+            let tx = market
+                .build_payout_transaction(step, OutPoint::null())
+                .unwrap();
+            let signatures = scalar_attestation(&market, value);
+            let signed = market
+                .sign_payout_transaction(tx.clone(), value, &signatures)
+                .unwrap();
+            assert_eq!(
+                verify_taproot_spend(&signed, 0, &prevouts),
+                Ok(()),
+                "{value}"
+            );
+
+            // The attestation of a value in another leaf does not unlock this one
+            let other = scalar_attestation(&market, (value + 500) % 1000);
+            let signed = market.sign_payout_transaction(tx, value, &other).unwrap();
+            assert_eq!(
+                verify_taproot_spend(&signed, 0, &prevouts),
+                Err(ScriptError::InvalidSignature),
+                "{value}"
+            );
+        }
+
+        // The payout of another step does not match the leaf of the value
+        let tx = market
+            .build_payout_transaction(4, OutPoint::null())
+            .unwrap();
+        assert!(market
+            .sign_payout_transaction(tx, 157, &scalar_attestation(&market, 157))
+            .is_err());
+
+        let escape = market.build_escape_transaction(OutPoint::null()).unwrap();
+        let signed = market.sign_escape_transaction(escape).unwrap();
+        assert_eq!(verify_taproot_spend(&signed, 0, &prevouts), Ok(()));
+    }
+
+    #[test]
+    fn test_script_numbers() {
+        for n in [
+            0,
+            1,
+            -1,
+            127,
+            128,
+            -128,
+            255,
+            256,
+            500_000_000,
+            1_735_776_000,
+        ] {
+            assert_eq!(read_num(&encode_num(n), 5), Ok(n));
+        }
+        assert_eq!(read_num(&[0x01, 0x00], 5), Err(ScriptError::InvalidNumber));
+        assert!(!cast_to_bool(&[0x00, 0x80]));
+        assert!(cast_to_bool(&[0x00, 0x01]));
+    }
+}
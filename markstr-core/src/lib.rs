@@ -10,11 +10,18 @@
 //!
 //! ## Features
 //!
-//! - **Market Creation**: Create binary prediction markets with oracle-based settlement
+//! - **Market Creation**: Create categorical (2 to 32 outcomes) prediction markets with oracle-based settlement
+//! - **Scalar Markets**: Numeric-range markets settled from digit-decomposed oracle attestations
 //! - **Betting System**: Place bets on market outcomes with Bitcoin transactions
+//! - **Deposit Coordination**: Transport-agnostic state machine for the PSBT deposit round
+//! - **Nostr Transport**: Gift-wrapped deposit round messages over relays (feature `nostr`)
+//! - **Market Store**: Markets, deposit sessions and attestations saved in a JSON
+//!   directory or SQLite (feature `sqlite`)
 //! - **Oracle Integration**: Nostr-based oracle system for outcome verification
 //! - **CSFS Verification**: Cryptographic verification of oracle signatures
 //! - **Payout Distribution**: Proportional payout calculation and distribution
+//! - **Transaction Inspection**: Explain the leaf, CTV template, payouts and fee of a pool spend
+//! - **Transaction Graph**: Every transaction of a market, exported with a Graphviz rendering
 //!
 //! ## Examples
 //!
@@ -22,37 +29,77 @@
 //! use markstr_core::PredictionMarket;
 //!
 //! // Create a new prediction market
-//! let market = PredictionMarket::new(
+//! let mut market = PredictionMarket::new(
 //!     "Who will win the 2024 election?".to_string(),
-//!     "Candidate A".to_string(),
-//!     "Candidate B".to_string(),
+//!     vec!["Candidate A".to_string(), "Candidate B".to_string()],
 //!     "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
 //!     1735689600, // Settlement timestamp
 //! )?;
 //!
-//! // Get the market's Bitcoin address for betting
+//! // Register bets, the pool address commits to them
+//! market.open_registration()?;
+//! market.place_bet(
+//!     0,
+//!     100_000,
+//!     "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx".to_string(),
+//!     "funding_txid".to_string(),
+//!     0,
+//! )?;
+//!
+//! // Get the market's pool address
 //! let market_address = market.get_market_address()?;
-//! println!("Send bets to: {}", market_address);
+//! println!("Pool address: {}", market_address);
 //! Ok::<(), markstr_core::MarketError>(())
 //! ```
 
+pub mod attestation;
+pub mod coordinator;
 pub mod deposit;
 pub mod error;
+pub mod event;
+pub mod graph;
+pub mod inspect;
+#[cfg(any(test, feature = "test-support"))]
+pub mod interpreter;
+pub mod lifecycle;
 pub mod market;
+pub mod network;
+pub mod nip19;
 pub mod pool;
+pub mod scalar;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
 #[cfg(test)]
 pub mod test_utils;
+#[cfg(feature = "nostr")]
+pub mod transport;
 pub mod utils;
+pub mod wire;
 pub mod withdraw;
 
+pub use attestation::{Attestation, AttestationError};
 use bitcoin::Network;
+pub use coordinator::DepositCoordinator;
 pub use error::{MarketError, Result};
-pub use market::{Bet, MarketFees, PredictionMarket};
+pub use event::NostrEvent;
+pub use lifecycle::MarketState;
+pub use market::{Bet, MarketFees, PredictionMarket, PredictionOutcome};
+pub use pool::{AddressScheme, EscapeTimelock, PoolDescriptor};
+pub use scalar::{PayoutCurve, ScalarMarket, ScalarSide};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+pub use store::{JsonStore, MarketStore, StoreError};
 pub use utils::*;
+pub use wire::WireMessage;
 
 /// Default fee for market transactions (1000 satoshis)
 pub const DEFAULT_MARKET_FEE: u64 = 1000;
 
+/// Outputs of this amount or less are dust and are not created (546 satoshis,
+/// the dust limit of P2PKH, the highest of the standard output types)
+pub const DUST_THRESHOLD: u64 = 546;
+
 /// ```OP_CHECKSIGFROMSTACK``` opcode (0xcc)
 pub const OP_CHECKSIGFROMSTACK: u8 = 0xcc;
 
@@ -0,0 +1,181 @@
+//! # Market Lifecycle
+//!
+//! Typed lifecycle of a prediction market with checked transitions:
+//!
+//! ```text
+//! Draft -> Open -> DepositSigning -> Funded -> Settled -> PaidOut
+//!           ^            |              |          |
+//!           +------------+              +----------+--> Escaped
+//! ```
+//!
+//! Every frontend renders the same [`MarketState`], either through its
+//! [`Display`](std::fmt::Display) implementation or its stable [`MarketState::name`].
+
+use crate::{error::Result, market::outcome_label, MarketError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Lifecycle state of a prediction market
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MarketState {
+    /// Market parameters are being defined, no bets accepted yet
+    #[default]
+    Draft,
+    /// Open for bet registration
+    Open,
+    /// Bet set is frozen, bettors sign their deposits into the pool
+    DepositSigning,
+    /// Pool deposit transaction is confirmed
+    Funded,
+    /// Oracle attested the winning outcome
+    Settled {
+        /// Index of the winning outcome
+        winning_outcome: usize,
+    },
+    /// Funds were returned to every bettor through the escape path
+    Escaped,
+    /// Winnings were paid out to the bets on the winning outcome
+    PaidOut {
+        /// Index of the winning outcome
+        winning_outcome: usize,
+    },
+}
+
+impl MarketState {
+    /// Stable machine-readable name of the state
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Open => "open",
+            Self::DepositSigning => "deposit_signing",
+            Self::Funded => "funded",
+            Self::Settled { .. } => "settled",
+            Self::Escaped => "escaped",
+            Self::PaidOut { .. } => "paid_out",
+        }
+    }
+
+    /// Index of the winning outcome, once the oracle has attested it
+    pub fn winning_outcome(&self) -> Option<usize> {
+        match self {
+            Self::Settled { winning_outcome } | Self::PaidOut { winning_outcome } => {
+                Some(*winning_outcome)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether bets can be registered
+    pub fn accepts_bets(&self) -> bool {
+        matches!(self, Self::Open)
+    }
+
+    /// Whether the bet set is frozen (the pool address is final)
+    pub fn bets_frozen(&self) -> bool {
+        !matches!(self, Self::Draft | Self::Open)
+    }
+
+    /// Whether the pool has been spent and the market is over
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Escaped | Self::PaidOut { .. })
+    }
+
+    /// Whether the market can move from this state to `next`
+    pub fn can_transition_to(&self, next: &MarketState) -> bool {
+        use MarketState::*;
+        match (self, next) {
+            (Draft, Open)
+            | (Open, DepositSigning)
+            | (DepositSigning, Open)
+            | (DepositSigning, Funded)
+            | (Funded, Settled { .. })
+            | (Funded, Escaped)
+            | (Settled { .. }, Escaped) => true,
+            (
+                Settled { winning_outcome },
+                PaidOut {
+                    winning_outcome: paid,
+                },
+            ) => winning_outcome == paid,
+            _ => false,
+        }
+    }
+
+    /// Move to `next`, refusing illegal transitions
+    pub fn transition(self, next: MarketState) -> Result<MarketState> {
+        if self.can_transition_to(&next) {
+            Ok(next)
+        } else {
+            Err(MarketError::InvalidState(format!(
+                "cannot move from {} to {}",
+                self.name(),
+                next.name()
+            )))
+        }
+    }
+}
+
+impl fmt::Display for MarketState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Draft => write!(f, "Draft"),
+            Self::Open => write!(f, "Open for registration"),
+            Self::DepositSigning => write!(f, "Deposit signing"),
+            Self::Funded => write!(f, "Funded - Awaiting oracle settlement"),
+            Self::Settled { winning_outcome } => {
+                write!(
+                    f,
+                    "Settled - Outcome {} won",
+                    outcome_label(*winning_outcome)
+                )
+            }
+            Self::Escaped => write!(f, "Escaped - Bets refunded"),
+            Self::PaidOut { winning_outcome } => write!(
+                f,
+                "Paid out - Outcome {} won",
+                outcome_label(*winning_outcome)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_happy_path_transitions() {
+        let state = MarketState::Draft
+            .transition(MarketState::Open)
+            .and_then(|s| s.transition(MarketState::DepositSigning))
+            .and_then(|s| s.transition(MarketState::Funded))
+            .and_then(|s| s.transition(MarketState::Settled { winning_outcome: 2 }))
+            .and_then(|s| s.transition(MarketState::PaidOut { winning_outcome: 2 }))
+            .unwrap();
+        assert!(state.is_final());
+        assert_eq!(state.winning_outcome(), Some(2));
+    }
+
+    #[test]
+    fn test_illegal_transitions() {
+        assert!(MarketState::Draft.transition(MarketState::Funded).is_err());
+        assert!(MarketState::Open
+            .transition(MarketState::Settled { winning_outcome: 0 })
+            .is_err());
+        assert!(MarketState::Settled { winning_outcome: 0 }
+            .transition(MarketState::PaidOut { winning_outcome: 1 })
+            .is_err());
+        assert!(MarketState::Escaped.transition(MarketState::Open).is_err());
+        assert!(MarketState::Funded.transition(MarketState::Escaped).is_ok());
+    }
+
+    #[test]
+    fn test_state_serialization() {
+        let state = MarketState::Settled { winning_outcome: 1 };
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(json, r#"{"status":"settled","winning_outcome":1}"#);
+        assert_eq!(serde_json::from_str::<MarketState>(&json).unwrap(), state);
+        assert_eq!(state.to_string(), "Settled - Outcome B won");
+    }
+}
@@ -3,7 +3,16 @@
 //! This module implements the core prediction market functionality using Bitcoin
 //! Taproot and CSFS (```CheckSigFromStack```) for oracle-based settlement.
 
-use crate::{error::Result, MarketError, DEFAULT_MARKET_FEE, OP_CHECKSIGFROMSTACK};
+use crate::{
+    error::Result,
+    event::NostrEvent,
+    lifecycle::MarketState,
+    pool::{
+        balanced_leaf_depths, generate_pool_spend_info, AddressScheme, EscapeTimelock,
+        PoolDescriptor, PoolSpendCache, PoolSpendInfo,
+    },
+    MarketError, DEFAULT_MARKET_FEE, OP_CHECKSIGFROMSTACK,
+};
 use bitcoin::{
     hashes::{sha256, Hash},
     secp256k1::{Keypair, Message, Secp256k1, XOnlyPublicKey},
@@ -15,15 +24,16 @@ use serde::{Deserialize, Serialize};
 /// Configuration for all fees in the prediction market
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct MarketFees {
-    /// Fee per output for the deposit transaction (in satoshis)
-    pub fee_per_deposit_output: u64,
-    
+    /// Fee per input of the deposit transaction, paid by the bettor on top of the bet (in satoshis)
+    #[serde(alias = "fee_per_deposit_output")]
+    pub fee_per_deposit_input: u64,
+
     /// Fee per output for the withdraw/payout transaction (in satoshis)
     pub fee_per_withdraw_output: u64,
-    
+
     /// Administrator fee - paid as an extra output in payout transactions (in satoshis)
     pub administrator_fee: u64,
-    
+
     /// Administrator address to receive the fee (optional, if None no admin fee is charged)
     pub administrator_address: Option<String>,
 }
@@ -31,7 +41,7 @@ pub struct MarketFees {
 impl Default for MarketFees {
     fn default() -> Self {
         Self {
-            fee_per_deposit_output: DEFAULT_MARKET_FEE,
+            fee_per_deposit_input: DEFAULT_MARKET_FEE,
             fee_per_withdraw_output: DEFAULT_MARKET_FEE,
             administrator_fee: 0,
             administrator_address: None,
@@ -42,9 +52,9 @@ impl Default for MarketFees {
 impl MarketFees {
     /// Calculate total fees for a deposit transaction with given number of inputs
     pub fn total_deposit_fees(&self, num_inputs: usize) -> u64 {
-        self.fee_per_deposit_output * num_inputs as u64
+        self.fee_per_deposit_input * num_inputs as u64
     }
-    
+
     /// Calculate total fees for a payout transaction with given number of outputs
     pub fn total_payout_fees(&self, num_outputs: usize) -> u64 {
         let withdraw_fees = self.fee_per_withdraw_output * num_outputs as u64;
@@ -54,21 +64,55 @@ impl MarketFees {
             withdraw_fees
         }
     }
-    
+
     /// Calculate pool amount after all fees are deducted
     pub fn pool_after_fees(&self, pool_size: u64, num_winning_outputs: usize) -> u64 {
         pool_size.saturating_sub(self.total_payout_fees(num_winning_outputs))
     }
 }
 
+/// Minimum number of outcomes in a market
+pub const MIN_OUTCOMES: usize = 2;
+
+/// Maximum number of outcomes in a market
+pub const MAX_OUTCOMES: usize = 32;
+
+/// Get the label of the outcome at `index` ('A', 'B', ..., 'Z', 'AA', 'AB', ...).
+pub fn outcome_label(index: usize) -> String {
+    let mut label = Vec::new();
+    let mut n = index + 1;
+    while n > 0 {
+        n -= 1;
+        label.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    label.reverse();
+    String::from_utf8(label).unwrap_or_default()
+}
+
+/// Parse an outcome label ('A', 'b', 'AA', ...) back into an outcome index.
+pub fn outcome_index(label: &str) -> Result<usize> {
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(MarketError::InvalidOutcome(format!(
+            "Invalid outcome label: {label}"
+        )));
+    }
+    let index = label
+        .to_ascii_uppercase()
+        .bytes()
+        .try_fold(0usize, |acc, c| {
+            acc.checked_mul(26)?.checked_add((c - b'A') as usize + 1)
+        })
+        .ok_or_else(|| MarketError::InvalidOutcome(format!("Invalid outcome label: {label}")))?;
+    Ok(index - 1)
+}
+
 /// Represents a prediction outcome that will be used to predefine the market.
 /// This outcome should be verifiably immutable.
-/// We can standardize outcome format to a Nostr event.
-/// The outcome description can be the Nostr event content.
-/// The outcome timestamp can be the Nostr event created_at.
-/// The oracle pubkey can be the Nostr event pubkey.
-/// We can set a static kind XXXX for the event (42 for now).
-/// And a static tag as the character of the outcome.
+/// It is published as a Nostr event template (see [`crate::event::outcome_event`]):
+/// the outcome description is the event content, the outcome timestamp its
+/// created_at and the oracle pubkey its author. The event has the dedicated kind
+/// [`crate::event::OUTCOME_EVENT_KIND`] and an `outcome` tag with the label.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct PredictionOutcome {
     /// The outcome description
@@ -77,12 +121,12 @@ pub struct PredictionOutcome {
     pub oracle: String,
     /// The timestamp of the outcome
     pub timestamp: u64,
-    /// The character of the outcome
-    pub character: char,
+    /// The label of the outcome ("A", "B", ...)
+    pub label: String,
 }
 
 impl PredictionOutcome {
-    pub fn new(outcome: String, oracle: String, timestamp: u64, character: char) -> Result<Self> {
+    pub fn new(outcome: String, oracle: String, timestamp: u64, label: String) -> Result<Self> {
         if outcome.is_empty() {
             return Err(MarketError::InvalidOutcome(
                 "Outcome cannot be empty".to_string(),
@@ -98,44 +142,55 @@ impl PredictionOutcome {
             outcome,
             oracle,
             timestamp,
-            character,
+            label,
         })
     }
+    /// Id of the outcome's template event, the message the oracle signs
     pub fn nostr_id(&self) -> String {
+        self.nostr_event().id
+    }
+    /// Template event of the outcome, signed by the oracle to attest it
+    pub fn nostr_event(&self) -> NostrEvent {
+        crate::event::outcome_event(self)
+    }
+    /// Outcome id of earlier releases: a kind 42 event tagged `["outcome", <label>]`.
+    ///
+    /// Frozen for [`AddressScheme::Legacy`], whose leaves commit to it.
+    pub fn legacy_nostr_id(&self) -> String {
         crate::sha256_hash_for_nostr_id(
             &self.outcome,
             &self.oracle,
             self.timestamp,
-            42,
-            &[&["outcome", &self.character.to_string()]],
+            crate::event::LEGACY_OUTCOME_EVENT_KIND.into(),
+            &[&["outcome", &self.label]],
         )
     }
+    /// Id of the outcome's template event as `note`
+    pub fn note(&self) -> Result<String> {
+        Ok(crate::nip19::encode_event_id(&self.nostr_id())?)
+    }
     pub fn verify_signature(&self, signature: &str) -> Result<bool> {
         crate::verify_signature(&self.nostr_id(), signature, &self.oracle)
     }
 }
 
-/// Represents a binary prediction market using Nostr oracles and CSFS verification.
+/// Represents a categorical prediction market using Nostr oracles and CSFS verification.
 ///
-/// The market creates a Taproot address with two script paths:
-/// - Path A: Verifies oracle signature for outcome A
-/// - Path B: Verifies oracle signature for outcome B
+/// The market creates a Taproot address with one script path per outcome,
+/// each verifying the oracle signature for that outcome.
 ///
 /// Participants bet by sending funds to the market address. Winners claim
 /// proportional payouts by providing the oracle's signed outcome.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct PredictionMarket {
-    /// Unique market identifier (8-character hex)
+    /// Unique market identifier (NIP-01 id of the announcement event, 64-character hex)
     pub market_id: String,
 
     /// Market question/description
     pub question: String,
 
-    /// Binary outcome A (e.g., "Team A wins", "Yes")
-    pub outcome_a: PredictionOutcome,
-
-    /// Binary outcome B (e.g., "Team B wins", "No")
-    pub outcome_b: PredictionOutcome,
+    /// Possible outcomes (e.g., "Team A wins", "Draw", "Team B wins")
+    pub outcomes: Vec<PredictionOutcome>,
 
     /// Oracle's Nostr public key (hex-encoded)
     pub oracle_pubkey: String,
@@ -143,32 +198,38 @@ pub struct PredictionMarket {
     /// Deadline timestamp for oracle to sign outcome (Unix timestamp)
     pub settlement_timestamp: u64,
 
-    /// Bitcoin network (Signet for testing)
+    /// Bitcoin network, Signet unless selected with [`Self::set_network`]
     pub network: Network,
 
+    /// Challenge script of a custom signet, `None` on every other network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signet_challenge: Option<ScriptBuf>,
+
     /// Market funding UTXO (if funded)
     pub market_utxo: Option<OutPoint>,
 
     /// Total amount in the market (in satoshis)
     pub total_amount: u64,
 
-    /// Bets placed on outcome A
-    pub bets_a: Vec<Bet>,
-
-    /// Bets placed on outcome B
-    pub bets_b: Vec<Bet>,
+    /// Bets placed on each outcome, indexed like `outcomes`
+    pub bets: Vec<Vec<Bet>>,
 
-    /// Whether the market has been settled
-    pub settled: bool,
-
-    /// Winning outcome (if settled)
-    pub winning_outcome: Option<char>, // 'A' or 'B'
+    /// Lifecycle state of the market
+    pub state: MarketState,
 
     /// Timeout for withdrawals after settlement (in case of oracle failure)
     pub withdraw_timeout: u32,
-    
+
+    /// Escape timelock, defaults to `settlement_timestamp + withdraw_timeout`
+    #[serde(default)]
+    pub escape_timelock: Option<EscapeTimelock>,
+
     /// Fee configuration for the market
     pub fees: MarketFees,
+
+    /// Pool spend info, cached once the bet set is frozen
+    #[serde(skip)]
+    pub(crate) pool_cache: PoolSpendCache,
 }
 
 /// Represents a bet placed by a participant
@@ -192,62 +253,112 @@ impl PredictionMarket {
     ///
     /// # Arguments
     /// * `question` - The market question (e.g., "Who will win the 2024 election?")
-    /// * `outcome_a` - First possible outcome (e.g., "Candidate A wins")
-    /// * `outcome_b` - Second possible outcome (e.g., "Candidate B wins")
-    /// * `oracle_pubkey` - Oracle's Nostr public key (hex-encoded)
+    /// * `outcomes` - Possible outcomes, between 2 and 32 (e.g., "Candidate A wins")
+    /// * `oracle_pubkey` - Oracle's Nostr public key (hex or `npub`)
     /// * `settlement_timestamp` - When oracle should sign outcome (Unix timestamp)
     ///
     /// # Returns
     /// A new `PredictionMarket` instance ready for betting
     pub fn new(
         question: String,
-        outcome_a: String,
-        outcome_b: String,
+        outcomes: Vec<String>,
         oracle_pubkey: String,
         settlement_timestamp: u64,
     ) -> Result<Self> {
-        // Generate the outcomes
-        let outcome_a =
-            PredictionOutcome::new(outcome_a, oracle_pubkey.clone(), settlement_timestamp, 'A')?;
-        let outcome_b =
-            PredictionOutcome::new(outcome_b, oracle_pubkey.clone(), settlement_timestamp, 'B')?;
-
-        // Market Id is a Nostr Note ID(sha256) of the question, oracle pubkey, and settlement timestamp
-        // with the tag "outcome" and the outcome nostr_ids
-        let market_id = crate::sha256_hash_for_nostr_id(
-            &question,
-            &oracle_pubkey,
-            settlement_timestamp,
-            42,
-            &[&["outcomes", &outcome_a.nostr_id(), &outcome_b.nostr_id()]],
-        );
-
-        // Validate oracle pubkey format
-        if hex::decode(&oracle_pubkey).is_err() || hex::decode(&oracle_pubkey)?.len() != 32 {
-            return Err(MarketError::InvalidMarket(
-                "Oracle pubkey must be 32-byte hex string".to_string(),
-            ));
+        if !(MIN_OUTCOMES..=MAX_OUTCOMES).contains(&outcomes.len()) {
+            return Err(MarketError::InvalidMarket(format!(
+                "Market must have between {MIN_OUTCOMES} and {MAX_OUTCOMES} outcomes, got {}",
+                outcomes.len()
+            )));
         }
 
+        // The oracle key is stored as hex
+        let oracle_pubkey = crate::nip19::parse_public_key(&oracle_pubkey)?;
+
+        // Generate the outcomes
+        let outcomes = outcomes
+            .into_iter()
+            .enumerate()
+            .map(|(i, outcome)| {
+                PredictionOutcome::new(
+                    outcome,
+                    oracle_pubkey.clone(),
+                    settlement_timestamp,
+                    outcome_label(i),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Market Id is the id of the market announcement event: the question, oracle pubkey
+        // and settlement timestamp with the tag "outcomes" and the outcome nostr_ids
+        let market_id =
+            crate::event::market_event(&question, &oracle_pubkey, settlement_timestamp, &outcomes)
+                .id;
+
         Ok(Self {
             market_id,
             question,
-            outcome_a,
-            outcome_b,
+            bets: vec![Vec::new(); outcomes.len()],
+            outcomes,
             oracle_pubkey,
             settlement_timestamp,
             network: Network::Signet,
+            signet_challenge: None,
             market_utxo: None,
             total_amount: 0,
-            bets_a: Vec::new(),
-            bets_b: Vec::new(),
-            settled: false,
-            winning_outcome: None,
+            state: MarketState::Draft,
             withdraw_timeout: 60 * 60 * 24, // 1 day
+            escape_timelock: None,
             fees: MarketFees::default(),
+            pool_cache: PoolSpendCache::default(),
         })
     }
 
+    /// Announcement event of the market, whose id is the market id.
+    pub fn announcement_event(&self) -> NostrEvent {
+        crate::event::market_event(
+            &self.question,
+            &self.oracle_pubkey,
+            self.settlement_timestamp,
+            &self.outcomes,
+        )
+    }
+
+    /// Oracle public key as `npub`.
+    pub fn oracle_npub(&self) -> Result<String> {
+        Ok(crate::nip19::encode_public_key(&self.oracle_pubkey)?)
+    }
+
+    /// Market id as `nevent`, with the oracle and the announcement kind.
+    pub fn nevent(&self) -> Result<String> {
+        Ok(crate::nip19::EventPointer {
+            id: self.market_id.clone(),
+            author: Some(self.oracle_pubkey.clone()),
+            kind: Some(crate::event::MARKET_EVENT_KIND),
+            relays: Vec::new(),
+        }
+        .encode()?)
+    }
+
+    /// Address of the announcement event as `naddr`.
+    pub fn naddr(&self) -> Result<String> {
+        let event = self.announcement_event();
+        Ok(crate::nip19::AddressPointer {
+            identifier: event.identifier().unwrap_or_default().to_string(),
+            author: self.oracle_pubkey.clone(),
+            kind: event.kind,
+            relays: Vec::new(),
+        }
+        .encode()?)
+    }
+
+    /// Reconstruct a market from its announcement and outcome template events.
+    ///
+    /// See [`crate::event::parse_market_event`].
+    pub fn from_events(event: &NostrEvent, outcome_events: &[NostrEvent]) -> Result<Self> {
+        crate::event::parse_market_event(event, outcome_events)
+    }
+
     /// Generate NUMS (Nothing Up My Sleeve) point for Taproot internal key.
     pub fn nums_point() -> Result<XOnlyPublicKey> {
         let nums_bytes = [
@@ -259,14 +370,13 @@ impl PredictionMarket {
         XOnlyPublicKey::from_slice(&nums_bytes)
             .map_err(|e| MarketError::InvalidAddress(format!("Failed to create NUMS point: {e}")))
     }
-    
+
     /// Creates a new prediction market with custom fee configuration.
     ///
     /// # Arguments
     /// * `question` - The market question (e.g., "Who will win the 2024 election?")
-    /// * `outcome_a` - First possible outcome (e.g., "Candidate A wins")
-    /// * `outcome_b` - Second possible outcome (e.g., "Candidate B wins")
-    /// * `oracle_pubkey` - Oracle's Nostr public key (hex-encoded)
+    /// * `outcomes` - Possible outcomes, between 2 and 32 (e.g., "Candidate A wins")
+    /// * `oracle_pubkey` - Oracle's Nostr public key (hex or `npub`)
     /// * `settlement_timestamp` - When oracle should sign outcome (Unix timestamp)
     /// * `fees` - Custom fee configuration for the market
     ///
@@ -274,21 +384,50 @@ impl PredictionMarket {
     /// A new `PredictionMarket` instance with custom fees
     pub fn new_with_fees(
         question: String,
-        outcome_a: String,
-        outcome_b: String,
+        outcomes: Vec<String>,
         oracle_pubkey: String,
         settlement_timestamp: u64,
         fees: MarketFees,
     ) -> Result<Self> {
-        let mut market = Self::new(question, outcome_a, outcome_b, oracle_pubkey, settlement_timestamp)?;
+        let mut market = Self::new(question, outcomes, oracle_pubkey, settlement_timestamp)?;
         market.fees = fees;
         Ok(market)
     }
 
-    /// Create CSFS script for a specific outcome.
+    /// Select the network of a draft market.
     ///
-    /// The script verifies that the provided signature (from witness) matches
-    /// the expected oracle signature for the given outcome.
+    /// `signet_challenge` selects a custom signet and requires `Network::Signet`.
+    pub fn set_network(
+        &mut self,
+        network: Network,
+        signet_challenge: Option<ScriptBuf>,
+    ) -> Result<()> {
+        if self.state != MarketState::Draft {
+            return Err(MarketError::InvalidState(format!(
+                "cannot change the network of a market in {}",
+                self.state.name()
+            )));
+        }
+        if signet_challenge.is_some() && network != Network::Signet {
+            return Err(MarketError::Network(format!(
+                "A signet challenge requires signet, not {}",
+                crate::network::network_name(network)
+            )));
+        }
+        self.network = network;
+        self.signet_challenge = signet_challenge;
+        Ok(())
+    }
+
+    /// Refuse markets on networks where CTV and CSFS are not known to be active
+    pub fn require_covenants(&self) -> Result<()> {
+        crate::network::require_covenants(self.network, self.signet_challenge.as_deref())
+    }
+
+    /// Create the legacy CSFS script for a specific outcome.
+    ///
+    /// Used by [`AddressScheme::Legacy`] only. Pool leaves are built by
+    /// [`crate::pool::build_script_for_outcome`].
     ///
     /// # Script Structure
     /// ```text
@@ -326,78 +465,264 @@ impl PredictionMarket {
         Ok(ScriptBuf::from_bytes(script_bytes))
     }
 
-    /// Generate the market's Taproot address with dual outcome scripts.
+    /// Get the market's pool address.
     ///
-    /// Creates a Taproot address with two script paths:
-    /// - Path 0: CSFS verification for outcome A
-    /// - Path 1: CSFS verification for outcome B
+    /// This is the canonical address every frontend advertises: the CSFS + CTV
+    /// pool of [`crate::pool`], spendable by the withdraw module. The payout
+    /// templates commit to the bets, so the address is only defined once bets
+    /// are registered and final once the bet set is frozen.
     ///
     /// # Returns
-    /// The market's bech32m Taproot address where bets are sent
+    /// The market's bech32m Taproot pool address
     pub fn get_market_address(&self) -> Result<String> {
-        let script_a = self.create_outcome_script(&self.outcome_a.nostr_id())?;
-        let script_b = self.create_outcome_script(&self.outcome_b.nostr_id())?;
+        Ok(self.pool_descriptor(AddressScheme::Pool)?.address)
+    }
+
+    /// Describe the market pool (address, internal key and leaf scripts).
+    ///
+    /// [`AddressScheme::Legacy`] reproduces the raw `OP_CHECKSIGFROMSTACK`
+    /// address advertised by earlier releases, to recover funds sent there.
+    pub fn pool_descriptor(&self, scheme: AddressScheme) -> Result<PoolDescriptor> {
+        match scheme {
+            AddressScheme::Pool => Ok(self.pool_spend_info()?.descriptor()),
+            AddressScheme::Legacy => self.legacy_pool_descriptor(),
+        }
+    }
+
+    /// Legacy Taproot tree with one [`Self::create_outcome_script`] leaf per outcome,
+    /// committing to [`PredictionOutcome::legacy_nostr_id`].
+    fn legacy_pool_descriptor(&self) -> Result<PoolDescriptor> {
+        let scripts = self
+            .outcomes
+            .iter()
+            .map(|outcome| self.create_outcome_script(&outcome.legacy_nostr_id()))
+            .collect::<Result<Vec<_>>>()?;
         let nums_point = Self::nums_point()?;
         let secp = Secp256k1::new();
 
-        let spend_info = TaprootBuilder::new()
-            .add_leaf(1, script_a)?
-            .add_leaf(1, script_b)?
-            .finalize(&secp, nums_point)
-            .map_err(|e| {
-                MarketError::InvalidAddress(format!("Failed to finalize taproot: {e:?}"))
-            })?;
+        let mut builder = TaprootBuilder::new();
+        for (script, depth) in scripts
+            .iter()
+            .cloned()
+            .zip(balanced_leaf_depths(self.outcomes.len()))
+        {
+            builder = builder.add_leaf(depth, script)?;
+        }
+        let spend_info = builder.finalize(&secp, nums_point).map_err(|e| {
+            MarketError::InvalidAddress(format!("Failed to finalize taproot: {e:?}"))
+        })?;
 
         let address = Address::p2tr_tweaked(spend_info.output_key(), self.network);
-        Ok(address.to_string())
+        Ok(PoolDescriptor {
+            scheme: AddressScheme::Legacy,
+            address: address.to_string(),
+            internal_key: nums_point.to_string(),
+            payout_leaves: scripts
+                .iter()
+                .map(|script| script.to_hex_string())
+                .collect(),
+            escape_leaf: None,
+        })
+    }
+
+    /// Timelock enforced by the escape leaf.
+    ///
+    /// Unless set explicitly, the escape path opens at `settlement_timestamp +
+    /// withdraw_timeout` (median-time-past).
+    pub fn escape_timelock(&self) -> Result<EscapeTimelock> {
+        match self.escape_timelock {
+            Some(timelock) => Ok(timelock),
+            None => {
+                EscapeTimelock::after_settlement(self.settlement_timestamp, self.withdraw_timeout)
+                    .map_err(|e| MarketError::InvalidMarket(format!("{e:#}")))
+            }
+        }
+    }
+
+    /// Get the outcome at `index`.
+    pub fn outcome(&self, index: usize) -> Result<&PredictionOutcome> {
+        self.outcomes.get(index).ok_or_else(|| {
+            MarketError::InvalidOutcome(format!(
+                "Outcome index {index} out of range, market has {} outcomes",
+                self.outcomes.len()
+            ))
+        })
+    }
+
+    /// Iterate over all bets, in outcome order.
+    pub fn all_bets(&self) -> impl Iterator<Item = &Bet> {
+        self.bets.iter().flatten()
+    }
+
+    /// Index of the winning outcome (if settled)
+    pub fn winning_outcome(&self) -> Option<usize> {
+        self.state.winning_outcome()
+    }
+
+    /// Whether the oracle has settled the market
+    pub fn is_settled(&self) -> bool {
+        self.winning_outcome().is_some()
+    }
+
+    /// Get the bets placed on the winning outcome (if settled)
+    pub fn winning_bets(&self) -> Option<&[Bet]> {
+        self.winning_outcome()
+            .and_then(|outcome| self.bets.get(outcome))
+            .map(Vec::as_slice)
+    }
+
+    /// Open the market for bet registration.
+    pub fn open_registration(&mut self) -> Result<()> {
+        self.state = self.state.transition(MarketState::Open)?;
+        Ok(())
+    }
+
+    /// Freeze the bet set and start the deposit signing round.
+    ///
+    /// From here on the pool address and the CTV templates are final, so the
+    /// pool spend info is computed and cached.
+    pub fn freeze_bets(&mut self) -> Result<()> {
+        if self.all_bets().next().is_none() {
+            return Err(MarketError::InvalidState(
+                "cannot freeze a market without bets".to_string(),
+            ));
+        }
+        let next = self.state.transition(MarketState::DepositSigning)?;
+        let pool = generate_pool_spend_info(self)
+            .map_err(|e| MarketError::InvalidMarket(format!("Failed to build pool: {e:#}")))?;
+        self.pool_cache = PoolSpendCache::new(self, pool)?;
+        self.state = next;
+        Ok(())
+    }
+
+    /// Taproot spend info of the market pool.
+    ///
+    /// Returns the value cached when the bet set was frozen, unless the fields
+    /// it was built from changed since, and computes it from the current bets
+    /// otherwise.
+    pub fn pool_spend_info(&self) -> Result<PoolSpendInfo> {
+        match self.pool_cache.get(self)? {
+            Some(pool) => Ok(pool.clone()),
+            None => generate_pool_spend_info(self)
+                .map_err(|e| MarketError::InvalidMarket(format!("Failed to build pool: {e:#}"))),
+        }
+    }
+
+    /// Abort the deposit signing round and reopen bet registration.
+    pub fn reopen_registration(&mut self) -> Result<()> {
+        if self.state != MarketState::DepositSigning {
+            return Err(MarketError::InvalidState(format!(
+                "cannot reopen registration from {}",
+                self.state.name()
+            )));
+        }
+        self.state = self.state.transition(MarketState::Open)?;
+        self.pool_cache = PoolSpendCache::default();
+        Ok(())
+    }
+
+    /// Record the confirmed pool UTXO.
+    pub fn mark_funded(&mut self, market_utxo: OutPoint) -> Result<()> {
+        self.state = self.state.transition(MarketState::Funded)?;
+        self.market_utxo = Some(market_utxo);
+        Ok(())
+    }
+
+    /// Record that the payout transaction spent the pool.
+    pub fn mark_paid_out(&mut self) -> Result<()> {
+        let winning_outcome = self.winning_outcome().ok_or_else(|| {
+            MarketError::InvalidState(format!("cannot pay out from {}", self.state.name()))
+        })?;
+        self.state = self
+            .state
+            .transition(MarketState::PaidOut { winning_outcome })?;
+        Ok(())
+    }
+
+    /// Record that the escape transaction refunded the pool.
+    pub fn mark_escaped(&mut self) -> Result<()> {
+        self.state = self.state.transition(MarketState::Escaped)?;
+        Ok(())
     }
 
     /// Place a bet on a specific outcome.
     ///
     /// # Arguments
-    /// * `outcome` - Which outcome to bet on ('A' or 'B')
+    /// * `outcome` - Index of the outcome to bet on (0 for 'A', 1 for 'B', ...)
     /// * `amount` - Amount to bet in satoshis
     /// * `payout_address` - Where to send winnings if this bet wins
     /// * `txid` - Transaction ID of the funding transaction
     /// * `vout` - Output index in the funding transaction
     pub fn place_bet(
         &mut self,
-        outcome: char,
+        outcome: usize,
         amount: u64,
         payout_address: String,
         txid: String,
         vout: u32,
     ) -> Result<()> {
-        if self.settled {
-            return Err(MarketError::InvalidBet(
-                "Market has already been settled".to_string(),
-            ));
+        if !self.state.accepts_bets() {
+            return Err(MarketError::InvalidBet(format!(
+                "Market is not open for bets ({})",
+                self.state
+            )));
         }
 
-        let bet = Bet {
+        let num_outcomes = self.outcomes.len();
+        let bets = self.bets.get_mut(outcome).ok_or_else(|| {
+            MarketError::InvalidBet(format!(
+                "Outcome index {outcome} out of range, market has {num_outcomes} outcomes"
+            ))
+        })?;
+
+        bets.push(Bet {
             payout_address,
             amount,
             txid,
             vout,
-        };
+        });
+        self.total_amount += amount;
 
-        match outcome.to_ascii_uppercase() {
-            'A' => {
-                self.bets_a.push(bet);
-                self.total_amount += amount;
-            }
-            'B' => {
-                self.bets_b.push(bet);
-                self.total_amount += amount;
-            }
-            _ => {
-                return Err(MarketError::InvalidBet(
-                    "Outcome must be 'A' or 'B'".to_string(),
-                ))
+        Ok(())
+    }
+
+    /// Remove bets from a market open for bets.
+    ///
+    /// # Arguments
+    /// * `indices` - Indices of the bets in [`Self::all_bets`] order
+    ///
+    /// Returns the removed bets with their outcome index, in `all_bets` order.
+    pub fn remove_bets(&mut self, indices: &[usize]) -> Result<Vec<(usize, Bet)>> {
+        if !self.state.accepts_bets() {
+            return Err(MarketError::InvalidBet(format!(
+                "Market is not open for bets ({})",
+                self.state
+            )));
+        }
+        let num_bets = self.all_bets().count();
+        if let Some(index) = indices.iter().find(|&&index| index >= num_bets) {
+            return Err(MarketError::InvalidBet(format!(
+                "Bet index {index} out of range, market has {num_bets} bets"
+            )));
+        }
+
+        let mut removed = Vec::new();
+        let mut index = 0;
+        for (outcome, bets) in self.bets.iter_mut().enumerate() {
+            let mut kept = Vec::with_capacity(bets.len());
+            for bet in bets.drain(..) {
+                if indices.contains(&index) {
+                    removed.push((outcome, bet));
+                } else {
+                    kept.push(bet);
+                }
+                index += 1;
             }
+            *bets = kept;
         }
+        self.total_amount -= removed.iter().map(|(_, bet)| bet.amount).sum::<u64>();
 
-        Ok(())
+        Ok(removed)
     }
 
     /// Calculate payout for a winning bet.
@@ -410,34 +735,35 @@ impl PredictionMarket {
         }
 
         // Get winning bets to calculate number of outputs
-        let winning_bets = match self.winning_outcome {
-            Some('A') => &self.bets_a,
-            Some('B') => &self.bets_b,
-            _ => return 0, // Market not settled yet or invalid outcome
+        let Some(winning_bets) = self.winning_bets() else {
+            return 0; // Market not settled yet or invalid outcome
         };
-        
+
         let num_winning_outputs = winning_bets.len();
 
         // Winner's share = (their_bet / total_winning_bets) * total_pool
         // Subtract fees from total pool
-        let pool_after_fees = self.fees.pool_after_fees(self.total_amount, num_winning_outputs);
+        let pool_after_fees = self
+            .fees
+            .pool_after_fees(self.total_amount, num_winning_outputs);
         (bet_amount * pool_after_fees) / winning_side_total
     }
 
     /// Settle the market with oracle signature.
     ///
     /// # Arguments
-    /// * `oracle_event` - The Nostr event signed by the oracle
-    /// * `outcome` - Which outcome won ('A' or 'B')
+    /// * `outcome` - The outcome signed by the oracle
+    /// * `outcome_signature` - The oracle's signature over the outcome
     pub fn settle_market(
         &mut self,
         outcome: &PredictionOutcome,
         outcome_signature: &str,
     ) -> Result<()> {
-        if self.settled {
-            return Err(MarketError::Settlement(
-                "Market already settled".to_string(),
-            ));
+        if self.state != MarketState::Funded {
+            return Err(MarketError::Settlement(format!(
+                "Market cannot be settled ({})",
+                self.state
+            )));
         }
 
         // Verify oracle signature
@@ -460,13 +786,8 @@ impl PredictionMarket {
         }
 
         // Verify outcome message format
-        let expected_outcome = match outcome.character.to_ascii_uppercase() {
-            'A' => &self.outcome_a,
-            'B' => &self.outcome_b,
-            _ => return Err(MarketError::InvalidBet("Invalid outcome".to_string())),
-        };
-
-        let expected_message = expected_outcome.nostr_id();
+        let outcome_index = outcome_index(&outcome.label)?;
+        let expected_message = self.outcome(outcome_index)?.nostr_id();
         if outcome.nostr_id() != expected_message {
             return Err(MarketError::Oracle(
                 "Oracle message doesn't match expected format".to_string(),
@@ -474,44 +795,51 @@ impl PredictionMarket {
         }
 
         // Mark market as settled
-        self.settled = true;
-        self.winning_outcome = Some(outcome.character.to_ascii_uppercase());
+        self.state = self.state.transition(MarketState::Settled {
+            winning_outcome: outcome_index,
+        })?;
 
         Ok(())
     }
 
-    /// Get total amount bet on outcome A
-    pub fn get_total_a(&self) -> u64 {
-        self.bets_a.iter().map(|b| b.amount).sum()
-    }
-
-    /// Get total amount bet on outcome B
-    pub fn get_total_b(&self) -> u64 {
-        self.bets_b.iter().map(|b| b.amount).sum()
+    /// Settle the market with the oracle's signed outcome event.
+    ///
+    /// The event must be the template of one of the market outcomes (see
+    /// [`PredictionOutcome::nostr_event`]), signed by the oracle.
+    pub fn settle_with_attestation(&mut self, event: &NostrEvent) -> Result<()> {
+        let outcome = self
+            .outcomes
+            .iter()
+            .find(|outcome| outcome.nostr_id() == event.id)
+            .cloned()
+            .ok_or_else(|| {
+                MarketError::Oracle(format!(
+                    "Event {} does not attest an outcome of this market",
+                    event.id
+                ))
+            })?;
+        crate::attestation::verify_attestation(event, &outcome)?;
+        let signature = event.sig.clone().unwrap_or_default();
+        self.settle_market(&outcome, &signature)
     }
 
-    /// Get current odds for outcome A (as a ratio)
-    pub fn get_odds_a(&self) -> f64 {
-        let total_a = self.get_total_a() as f64;
-        let total_b = self.get_total_b() as f64;
-
-        if total_a == 0.0 {
-            return 1.0;
-        }
-
-        (total_a + total_b) / total_a
+    /// Get total amount bet on an outcome
+    pub fn get_total(&self, outcome: usize) -> u64 {
+        self.bets
+            .get(outcome)
+            .map_or(0, |bets| bets.iter().map(|b| b.amount).sum())
     }
 
-    /// Get current odds for outcome B (as a ratio)
-    pub fn get_odds_b(&self) -> f64 {
-        let total_a = self.get_total_a() as f64;
-        let total_b = self.get_total_b() as f64;
+    /// Get current odds for an outcome (as a ratio)
+    pub fn get_odds(&self, outcome: usize) -> f64 {
+        let total_outcome = self.get_total(outcome) as f64;
+        let total = self.all_bets().map(|b| b.amount).sum::<u64>() as f64;
 
-        if total_b == 0.0 {
+        if total_outcome == 0.0 {
             return 1.0;
         }
 
-        (total_a + total_b) / total_b
+        total / total_outcome
     }
 
     /// Check if market is past settlement time
@@ -524,18 +852,9 @@ impl PredictionMarket {
         now >= self.settlement_timestamp
     }
 
-    /// Get market status summary
-    pub fn get_status(&self) -> String {
-        if self.settled {
-            self.winning_outcome.map_or_else(
-                || "Settled - No outcome set".to_string(),
-                |outcome| format!("Settled - Outcome {outcome} won"),
-            )
-        } else if self.is_past_settlement() {
-            "Awaiting oracle settlement".to_string()
-        } else {
-            "Active - Accepting bets".to_string()
-        }
+    /// Get market lifecycle state
+    pub fn get_status(&self) -> MarketState {
+        self.state
     }
 
     /// Verify CSFS signature against outcome message.
@@ -545,7 +864,7 @@ impl PredictionMarket {
     ///
     /// # Arguments
     /// * `signature` - The oracle's signature bytes
-    /// * `outcome` - The outcome being verified ('A' or 'B')
+    /// * `outcome` - The outcome being verified (its Nostr id)
     ///
     /// # Returns
     /// `true` if the signature is valid for the outcome, `false` otherwise
@@ -589,7 +908,7 @@ impl PredictionMarket {
     ///
     /// # Arguments
     /// * `oracle_secret_key` - The oracle's secret key
-    /// * `outcome` - The outcome being signed ('A' or 'B')
+    /// * `outcome` - The outcome being signed (its Nostr id)
     ///
     /// # Returns
     /// 64-byte signature that can be used in the witness stack
@@ -625,122 +944,572 @@ impl PredictionMarket {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORACLE: &str = "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a";
+
+    fn outcomes(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("Candidate {i}")).collect()
+    }
+
+    #[test]
+    fn test_outcome_labels() {
+        assert_eq!(outcome_label(0), "A");
+        assert_eq!(outcome_label(1), "B");
+        assert_eq!(outcome_label(25), "Z");
+        assert_eq!(outcome_label(26), "AA");
+        assert_eq!(outcome_label(31), "AF");
+        for index in 0..100 {
+            assert_eq!(outcome_index(&outcome_label(index)).unwrap(), index);
+        }
+        assert_eq!(outcome_index("b").unwrap(), 1);
+        assert!(outcome_index("").is_err());
+        assert!(outcome_index("A1").is_err());
+    }
+
+    #[test]
+    fn test_new_categorical_market() {
+        let market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(32),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        assert_eq!(market.outcomes.len(), 32);
+        assert_eq!(market.bets.len(), 32);
+        assert_eq!(market.outcomes[31].label, "AF");
+        assert!(market.pool_descriptor(AddressScheme::Legacy).is_ok());
+
+        assert!(PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(1),
+            ORACLE.to_string(),
+            1735689600
+        )
+        .is_err());
+        assert!(PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(33),
+            ORACLE.to_string(),
+            1735689600
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_binary_market_id_is_stable() {
+        // The binary market keeps the `["outcomes", <id A>, <id B>]` tag layout
+        let market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(2),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        let event = market.announcement_event();
+        let expected = crate::sha256_hash_for_nostr_id(
+            "Who wins?",
+            ORACLE,
+            1735689600,
+            crate::event::MARKET_EVENT_KIND.into(),
+            &[
+                &["d", event.identifier().unwrap()],
+                &[
+                    "outcomes",
+                    &market.outcomes[0].nostr_id(),
+                    &market.outcomes[1].nostr_id(),
+                ],
+            ],
+        );
+        assert_eq!(market.market_id, expected);
+    }
+
+    #[test]
+    fn test_legacy_outcome_ids_are_frozen() {
+        // Ids of earlier releases, the legacy address commits to them
+        let market = PredictionMarket::new(
+            "Q?".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e".to_string(),
+            1735689600,
+        )
+        .unwrap();
+        assert_eq!(
+            market.outcomes[0].legacy_nostr_id(),
+            "3f55cf40aa91a74fbf8666ed98fe5baaddcd98e0bf01c5bc90cb704cf3592521"
+        );
+        assert_eq!(
+            market.outcomes[1].legacy_nostr_id(),
+            "4bc012cbf831f8fe8b6e434ac65cc90aa374d598956c2e958f4345ecbb6a8e6c"
+        );
+        assert_ne!(
+            market.outcomes[0].legacy_nostr_id(),
+            market.outcomes[0].nostr_id()
+        );
+    }
+
+    #[test]
+    fn test_legacy_address_vector() {
+        // Address advertised by earlier releases for this market
+        let market = PredictionMarket::new(
+            "Q?".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e".to_string(),
+            1735689600,
+        )
+        .unwrap();
+        let legacy = market.pool_descriptor(AddressScheme::Legacy).unwrap();
+        assert_eq!(
+            legacy.address,
+            "tb1pum0cdt96e43rv6thw4cqqyrgdaj08pvem9gy7skk70kt50dey2sqwdam27"
+        );
+        assert_eq!(legacy.payout_leaves.len(), 2);
+    }
+
+    #[test]
+    fn test_set_network() {
+        let mut market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(2),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        assert_eq!(market.network, Network::Signet);
+        assert!(market.require_covenants().is_ok());
+
+        market.set_network(Network::Bitcoin, None).unwrap();
+        assert!(matches!(
+            market.require_covenants(),
+            Err(MarketError::Network(_))
+        ));
+        let challenge = ScriptBuf::from_hex("51").unwrap();
+        assert!(market
+            .set_network(Network::Regtest, Some(challenge.clone()))
+            .is_err());
+        market
+            .set_network(Network::Signet, Some(challenge))
+            .unwrap();
+        assert!(market.require_covenants().is_err());
+
+        // The challenge survives a round trip, and is omitted on other networks
+        let json = serde_json::to_string(&market).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PredictionMarket>(&json).unwrap(),
+            market
+        );
+        market.set_network(Network::Regtest, None).unwrap();
+        assert!(!serde_json::to_string(&market)
+            .unwrap()
+            .contains("signet_challenge"));
+
+        // Fixed once bets are registered
+        market.open_registration().unwrap();
+        assert!(matches!(
+            market.set_network(Network::Signet, None),
+            Err(MarketError::InvalidState(_))
+        ));
+    }
+
+    #[test]
+    fn test_nip19_encodings() {
+        use crate::nip19::{self, AddressPointer, EventPointer, Nip19Error};
+
+        let market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(2),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+
+        // The oracle may be given as npub, it is stored as hex
+        let npub = market.oracle_npub().unwrap();
+        let from_npub =
+            PredictionMarket::new("Who wins?".to_string(), outcomes(2), npub, 1735689600).unwrap();
+        assert_eq!(from_npub, market);
+
+        let nevent = EventPointer::decode(&market.nevent().unwrap()).unwrap();
+        assert_eq!(nevent.id, market.market_id);
+        assert_eq!(nevent.author.as_deref(), Some(ORACLE));
+        let naddr = AddressPointer::decode(&market.naddr().unwrap()).unwrap();
+        assert_eq!(
+            Some(naddr.identifier.as_str()),
+            market.announcement_event().identifier()
+        );
+        assert_eq!(naddr.kind, crate::event::MARKET_EVENT_KIND);
+        let note = market.outcomes[0].note().unwrap();
+        assert_eq!(
+            nip19::parse_event_id(&note).unwrap(),
+            market.outcomes[0].nostr_id()
+        );
+
+        // Errors name the rejected encoding
+        let err = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(2),
+            "npub1invalid".to_string(),
+            1735689600,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            MarketError::Nip19(Nip19Error::InvalidEncoding {
+                expected: "npub",
+                ..
+            })
+        ));
+        let err = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(2),
+            ORACLE[..62].to_string(),
+            1735689600,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("hex public key"), "{err}");
+    }
+
+    #[test]
+    fn test_place_bet_categorical() {
+        let mut market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(3),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        market.open_registration().unwrap();
+        market
+            .place_bet(2, 30000, "addr".to_string(), "txid".to_string(), 0)
+            .unwrap();
+        market
+            .place_bet(0, 10000, "addr".to_string(), "txid".to_string(), 1)
+            .unwrap();
+        assert!(market
+            .place_bet(3, 10000, "addr".to_string(), "txid".to_string(), 2)
+            .is_err());
+
+        assert_eq!(market.total_amount, 40000);
+        assert_eq!(market.get_total(2), 30000);
+        assert_eq!(market.get_total(1), 0);
+        assert_eq!(market.get_odds(0), 4.0);
+        assert_eq!(market.get_odds(1), 1.0);
+        assert_eq!(market.all_bets().count(), 2);
+    }
+
+    #[test]
+    fn test_remove_bets() {
+        let mut market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(3),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        market.open_registration().unwrap();
+        for (outcome, amount) in [(0, 10000), (0, 20000), (1, 30000), (2, 40000)] {
+            market
+                .place_bet(outcome, amount, "addr".to_string(), "txid".to_string(), 0)
+                .unwrap();
+        }
+
+        assert!(market.remove_bets(&[4]).is_err());
+        let removed = market.remove_bets(&[2, 0]).unwrap();
+        let removed: Vec<_> = removed.iter().map(|(o, bet)| (*o, bet.amount)).collect();
+        assert_eq!(removed, vec![(0, 10000), (1, 30000)]);
+        assert_eq!(market.total_amount, 60000);
+        let amounts: Vec<_> = market.all_bets().map(|bet| bet.amount).collect();
+        assert_eq!(amounts, vec![20000, 40000]);
+
+        market.state = MarketState::DepositSigning;
+        assert!(market.remove_bets(&[0]).is_err());
+        assert_eq!(market.total_amount, 60000);
+    }
+
+    #[test]
+    fn test_market_lifecycle() {
+        let mut market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(2),
+            ORACLE.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        market.network = Network::Regtest;
+        let addr = crate::test_utils::create_valid_regtest_address(1);
+        assert_eq!(market.get_status(), MarketState::Draft);
+
+        // Bets are only accepted while registration is open
+        assert!(market
+            .place_bet(0, 10000, addr.clone(), "txid".to_string(), 0)
+            .is_err());
+        assert!(market.freeze_bets().is_err());
+        market.open_registration().unwrap();
+        assert!(market.freeze_bets().is_err(), "Cannot freeze without bets");
+        market
+            .place_bet(0, 10000, addr.clone(), "txid".to_string(), 0)
+            .unwrap();
+        market.freeze_bets().unwrap();
+        assert_eq!(
+            market.pool_spend_info().unwrap(),
+            generate_pool_spend_info(&market).unwrap()
+        );
+        assert!(market
+            .place_bet(1, 10000, addr.clone(), "txid".to_string(), 1)
+            .is_err());
+
+        // Settlement and payout require a funded pool
+        assert!(market.mark_paid_out().is_err());
+        market.mark_funded(OutPoint::null()).unwrap();
+        assert_eq!(market.market_utxo, Some(OutPoint::null()));
+        assert!(market.mark_funded(OutPoint::null()).is_err());
+        market.state = MarketState::Settled { winning_outcome: 0 };
+        market.mark_paid_out().unwrap();
+        assert!(market.state.is_final());
+        assert!(market.mark_escaped().is_err());
+    }
+
+    #[test]
+    fn test_pool_cache_is_not_stale() {
+        let mut market = crate::test_utils::create_test_market();
+        market.freeze_bets().unwrap();
+        let frozen = market.clone();
+        let pool = market.pool_spend_info().unwrap();
+
+        // Editing the fields the pool is built from bypasses the cache
+        market.fees.fee_per_withdraw_output += 100;
+        let edited = market.pool_spend_info().unwrap();
+        assert_ne!(edited.address(), pool.address());
+        assert_eq!(edited, generate_pool_spend_info(&market).unwrap());
+        market.fees = frozen.fees.clone();
+        assert_eq!(market.pool_spend_info().unwrap(), pool);
+
+        // A pool cached from other fields makes otherwise equal markets differ
+        market.reopen_registration().unwrap();
+        market.escape_timelock = Some(EscapeTimelock::Relative(
+            bitcoin::relative::LockTime::from_height(144),
+        ));
+        market.freeze_bets().unwrap();
+        market.escape_timelock = frozen.escape_timelock;
+        assert_ne!(market, frozen);
+        let mut uncached = market.clone();
+        uncached.pool_cache = PoolSpendCache::default();
+        assert_eq!(uncached, frozen);
+    }
+
+    #[test]
+    fn test_settle_with_attestation() {
+        let secp = Secp256k1::new();
+        let oracle = Keypair::from_seckey_slice(&secp, &[0x11; 32]).unwrap();
+        let mut market = PredictionMarket::new(
+            "Who wins?".to_string(),
+            outcomes(3),
+            oracle.x_only_public_key().0.to_string(),
+            1735689600,
+        )
+        .unwrap();
+        let mut event = market.outcomes[1].nostr_event();
+        event.sign(&oracle).unwrap();
+
+        // Not funded yet
+        assert!(market.settle_with_attestation(&event).is_err());
+        market.state = MarketState::Funded;
+
+        // Unsigned, or signed by somebody else
+        let mut unsigned = event.clone();
+        unsigned.sig = None;
+        assert!(matches!(
+            market.settle_with_attestation(&unsigned),
+            Err(MarketError::Attestation(_))
+        ));
+        let mut forged = event.clone();
+        forged.sig = Some(hex::encode([1u8; 64]));
+        assert!(market.settle_with_attestation(&forged).is_err());
+
+        // An event of another market
+        let other = crate::test_utils::create_test_outcomes()[0].nostr_event();
+        assert!(matches!(
+            market.settle_with_attestation(&other),
+            Err(MarketError::Oracle(_))
+        ));
+
+        market.settle_with_attestation(&event).unwrap();
+        assert_eq!(market.state, MarketState::Settled { winning_outcome: 1 });
+
+        // The signature alone settles the market as well
+        let mut market = market.clone();
+        market.state = MarketState::Funded;
+        let outcome = market.outcomes[2].clone();
+        let mut event = outcome.nostr_event();
+        event.sign(&oracle).unwrap();
+        assert!(market
+            .settle_market(&outcome, &forged.sig.unwrap())
+            .is_err());
+        market.settle_market(&outcome, &event.sig.unwrap()).unwrap();
+        assert_eq!(market.winning_outcome(), Some(2));
+    }
+}
+
 #[cfg(test)]
 mod fee_tests {
     use super::*;
     fn create_test_market_with_fees() -> PredictionMarket {
         // Use a fixed test oracle public key
-        let oracle_pubkey = "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string();
-        
+        let oracle_pubkey =
+            "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string();
+
         let fees = MarketFees {
-            fee_per_deposit_output: 500,
+            fee_per_deposit_input: 500,
             fee_per_withdraw_output: 600,
             administrator_fee: 2000,
             administrator_address: Some("tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string()),
         };
-        
-        PredictionMarket::new_with_fees(
+
+        let mut market = PredictionMarket::new_with_fees(
             "Test market with custom fees".to_string(),
-            "Yes".to_string(),
-            "No".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
             oracle_pubkey,
             1735689600,
             fees,
-        ).unwrap()
+        )
+        .unwrap();
+        market.open_registration().unwrap();
+        market
     }
 
     #[test]
     fn test_market_fees_calculation() {
         let fees = MarketFees {
-            fee_per_deposit_output: 500,
+            fee_per_deposit_input: 500,
             fee_per_withdraw_output: 600,
             administrator_fee: 2000,
             administrator_address: Some("tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string()),
         };
-        
+
         // Test deposit fees
         assert_eq!(fees.total_deposit_fees(1), 500);
         assert_eq!(fees.total_deposit_fees(5), 2500);
-        
+
         // Test payout fees (with admin fee)
         assert_eq!(fees.total_payout_fees(1), 600 + 2000);
         assert_eq!(fees.total_payout_fees(5), 3000 + 2000);
-        
+
         // Test pool after fees
         assert_eq!(fees.pool_after_fees(100000, 5), 100000 - 5000);
     }
-    
+
     #[test]
     fn test_market_fees_no_admin() {
         let fees = MarketFees {
-            fee_per_deposit_output: 500,
+            fee_per_deposit_input: 500,
             fee_per_withdraw_output: 600,
             administrator_fee: 2000,
             administrator_address: None, // No admin address
         };
-        
+
         // Test payout fees (without admin fee since no address)
         assert_eq!(fees.total_payout_fees(1), 600);
         assert_eq!(fees.total_payout_fees(5), 3000);
     }
-    
+
     #[test]
     fn test_calculate_payout_with_custom_fees() {
         let mut market = create_test_market_with_fees();
-        
+
         // Add some bets
-        market.place_bet(
-            'A',
-            100000,
-            "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
-            "abc123".to_string(),
-            0,
-        ).unwrap();
-        
-        market.place_bet(
-            'A',
-            50000,
-            "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
-            "def456".to_string(),
-            0,
-        ).unwrap();
-        
-        market.place_bet(
-            'B',
-            80000,
-            "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
-            "ghi789".to_string(),
-            0,
-        ).unwrap();
-        
+        market
+            .place_bet(
+                0,
+                100000,
+                "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
+                "abc123".to_string(),
+                0,
+            )
+            .unwrap();
+
+        market
+            .place_bet(
+                0,
+                50000,
+                "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
+                "def456".to_string(),
+                0,
+            )
+            .unwrap();
+
+        market
+            .place_bet(
+                1,
+                80000,
+                "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
+                "ghi789".to_string(),
+                0,
+            )
+            .unwrap();
+
         // Total pool: 230000
         market.total_amount = 230000;
-        market.winning_outcome = Some('A');
-        
+        market.state = MarketState::Settled { winning_outcome: 0 };
+
         // Calculate payout for a winning bet
         let payout = market.calculate_payout(100000, 150000);
-        
+
         // Expected: pool_after_fees = 230000 - (2 outputs * 600) - 2000 = 226800
         // Winner's share = (100000 / 150000) * 226800 = 151200
         assert_eq!(payout, 151200);
     }
-    
+
+    #[test]
+    fn test_deposit_fee_is_paid_per_input() {
+        use crate::deposit::create_partial_pool_tx;
+        use crate::test_utils::{bettor_key, change_address, p2tr_input};
+
+        let mut market = create_test_market_with_fees();
+        market
+            .place_bet(
+                0,
+                10000,
+                "tb1q0ywfmmk5d0es7chp5xqnw7x5l6nlanvnqcgnzn".to_string(),
+                "abc123".to_string(),
+                0,
+            )
+            .unwrap();
+        let bet = market.all_bets().next().unwrap().clone();
+
+        // Two inputs of 6000: the bet is deposited in full and each input costs 500
+        let key = bettor_key(1);
+        let inputs = vec![p2tr_input(&key, 1, 6000), p2tr_input(&key, 2, 6000)];
+        let partial = create_partial_pool_tx(&market, &bet, 0, inputs, &change_address(1)).unwrap();
+
+        let outputs = &partial.psbt.unsigned_tx.output;
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].value.to_sat(), 12000 - 10000 - 2 * 500);
+    }
+
     #[test]
-    fn test_deposit_amount_after_fees() {
-        let market = create_test_market_with_fees();
-        
-        // Test that deposit amount is reduced by fee
-        let bet_amount = 10000;
-        let amount_after_fee = bet_amount - market.fees.fee_per_deposit_output;
-        
-        assert_eq!(amount_after_fee, 9500);
-    }
-    
+    fn test_fee_per_deposit_output_alias() {
+        let json = r#"{
+            "fee_per_deposit_output": 700,
+            "fee_per_withdraw_output": 600,
+            "administrator_fee": 0,
+            "administrator_address": null
+        }"#;
+        let fees: MarketFees = serde_json::from_str(json).unwrap();
+
+        assert_eq!(fees.fee_per_deposit_input, 700);
+        assert!(serde_json::to_string(&fees)
+            .unwrap()
+            .contains("\"fee_per_deposit_input\":700"));
+    }
+
     #[test]
     fn test_default_fees() {
         let fees = MarketFees::default();
-        
-        assert_eq!(fees.fee_per_deposit_output, DEFAULT_MARKET_FEE);
+
+        assert_eq!(fees.fee_per_deposit_input, DEFAULT_MARKET_FEE);
         assert_eq!(fees.fee_per_withdraw_output, DEFAULT_MARKET_FEE);
         assert_eq!(fees.administrator_fee, 0);
         assert_eq!(fees.administrator_address, None);
@@ -0,0 +1,126 @@
+//! # Networks
+//!
+//! The pool leaves rely on OP_CHECKTEMPLATEVERIFY (BIP-119, `OP_NOP4` 0xb3) and
+//! OP_CHECKSIGFROMSTACK (BIP-348, `OP_SUCCESS204` 0xcc). Neither is active on
+//! mainnet or the public testnets: a payout leaf would not check the oracle and
+//! funds sent to a pool there could be spent by anyone. Both are enforced on the
+//! default signet, which follows the Bitcoin Inquisition rules. Regtest rules
+//! depend on the node: a stock `bitcoind -regtest` enforces neither, only
+//! Inquisition builds (or the image of `bitcoind-tests`) do, so regtest is not
+//! known to be safe either. A custom signet is identified by its challenge
+//! script, and its rules are unknown.
+
+use crate::{MarketError, Result};
+use bitcoin::{Network, Script, ScriptBuf};
+
+/// Challenge script of the default signet
+pub const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+/// Names accepted by [`parse_network`]
+pub const NETWORK_NAMES: &[&str] = &["mainnet", "testnet", "testnet4", "signet", "regtest"];
+
+/// Parse a network name: `mainnet` (or `bitcoin`), `testnet` (or `testnet3`),
+/// `testnet4`, `signet` or `regtest`
+pub fn parse_network(name: &str) -> Result<Network> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "mainnet" | "bitcoin" => Ok(Network::Bitcoin),
+        "testnet" | "testnet3" => Ok(Network::Testnet),
+        "testnet4" => Ok(Network::Testnet4),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        _ => Err(MarketError::Network(format!(
+            "Unknown network {name}, expected one of {}",
+            NETWORK_NAMES.join(", ")
+        ))),
+    }
+}
+
+/// Name of a network, as accepted by [`parse_network`]
+pub fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "mainnet",
+        Network::Testnet => "testnet",
+        Network::Testnet4 => "testnet4",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+/// Parse the hex challenge script of a custom signet
+pub fn parse_signet_challenge(challenge: &str) -> Result<ScriptBuf> {
+    ScriptBuf::from_hex(challenge.trim())
+        .ok()
+        .filter(|script| !script.is_empty())
+        .ok_or_else(|| {
+            MarketError::Network(format!("Invalid signet challenge script: {challenge}"))
+        })
+}
+
+/// Whether CTV (0xb3) and CSFS (0xcc) are known to be active.
+///
+/// Only the default signet qualifies: regtest depends on the node build.
+/// `signet_challenge` is `None` for the default signet, a challenge equal to the
+/// default one is the default signet too.
+pub fn covenants_active(network: Network, signet_challenge: Option<&Script>) -> bool {
+    match network {
+        Network::Signet => signet_challenge
+            .is_none_or(|challenge| challenge.to_hex_string() == DEFAULT_SIGNET_CHALLENGE),
+        Network::Regtest | Network::Bitcoin | Network::Testnet | Network::Testnet4 => false,
+    }
+}
+
+/// Refuse networks where CTV and CSFS are not known to be active
+pub fn require_covenants(network: Network, signet_challenge: Option<&Script>) -> Result<()> {
+    if covenants_active(network, signet_challenge) {
+        return Ok(());
+    }
+    let name = match (network, signet_challenge) {
+        (Network::Signet, Some(_)) => "this custom signet",
+        (Network::Regtest, _) => "regtest (only Bitcoin Inquisition builds enforce them)",
+        _ => network_name(network),
+    };
+    Err(MarketError::Network(format!(
+        "CTV and CSFS are not known to be active on {name}, pool funds could be stolen"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_network() {
+        for name in NETWORK_NAMES {
+            assert_eq!(network_name(parse_network(name).unwrap()), *name);
+        }
+        assert_eq!(parse_network("Bitcoin").unwrap(), Network::Bitcoin);
+        assert_eq!(parse_network("testnet3").unwrap(), Network::Testnet);
+        assert!(matches!(
+            parse_network("liquid"),
+            Err(MarketError::Network(_))
+        ));
+        assert!(parse_signet_challenge("").is_err());
+        assert!(parse_signet_challenge("zz").is_err());
+    }
+
+    #[test]
+    fn test_covenants_active() {
+        let default = parse_signet_challenge(DEFAULT_SIGNET_CHALLENGE).unwrap();
+        let custom = parse_signet_challenge("51").unwrap();
+
+        assert!(require_covenants(Network::Signet, None).is_ok());
+        assert!(require_covenants(Network::Signet, Some(&default)).is_ok());
+        assert!(require_covenants(Network::Signet, Some(&custom)).is_err());
+        for network in [
+            Network::Regtest,
+            Network::Bitcoin,
+            Network::Testnet,
+            Network::Testnet4,
+        ] {
+            assert!(matches!(
+                require_covenants(network, None),
+                Err(MarketError::Network(_))
+            ));
+        }
+    }
+}
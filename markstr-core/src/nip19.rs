@@ -0,0 +1,434 @@
+//! # NIP-19 Encodings
+//!
+//! Bech32 encodings of Nostr keys and ids, accepted wherever the raw hex is.
+//!
+//! | Prefix   | Entity                                             |
+//! |----------|----------------------------------------------------|
+//! | `npub`   | x-only public key                                  |
+//! | `nsec`   | secret key                                         |
+//! | `note`   | event id                                           |
+//! | `nevent` | event id with author, kind and relay hints (TLV)   |
+//! | `naddr`  | addressable event: `d` tag, author and kind (TLV)  |
+//!
+//! Keys and ids are stored as lowercase hex in the rest of the crate, the parse
+//! functions normalize to that form. Errors name the encoding that was rejected.
+
+use bitcoin::bech32::{self, Bech32, Hrp};
+use thiserror::Error;
+
+/// Human-readable part of public keys
+pub const NPUB: &str = "npub";
+/// Human-readable part of secret keys
+pub const NSEC: &str = "nsec";
+/// Human-readable part of event ids
+pub const NOTE: &str = "note";
+/// Human-readable part of event pointers
+pub const NEVENT: &str = "nevent";
+/// Human-readable part of addressable event pointers
+pub const NADDR: &str = "naddr";
+
+// TLV types of `nevent` and `naddr`
+const TLV_SPECIAL: u8 = 0;
+const TLV_RELAY: u8 = 1;
+const TLV_AUTHOR: u8 = 2;
+const TLV_KIND: u8 = 3;
+
+/// Why a NIP-19 or hex value was rejected
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Nip19Error {
+    /// The value is not a valid encoding of the expected entity
+    #[error("Invalid {expected} encoding: {reason}")]
+    InvalidEncoding {
+        expected: &'static str,
+        reason: String,
+    },
+
+    /// The value encodes another entity
+    #[error("Expected {expected}, got {found}")]
+    WrongPrefix {
+        expected: &'static str,
+        found: String,
+    },
+}
+
+fn invalid(expected: &'static str, reason: impl ToString) -> Nip19Error {
+    Nip19Error::InvalidEncoding {
+        expected,
+        reason: reason.to_string(),
+    }
+}
+
+/// Pointer to an event (`nevent`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventPointer {
+    /// Hex event id
+    pub id: String,
+    /// Hex public key of the author
+    pub author: Option<String>,
+    /// Kind of the event
+    pub kind: Option<u16>,
+    /// Relays where the event may be found
+    pub relays: Vec<String>,
+}
+
+/// Pointer to an addressable event (`naddr`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressPointer {
+    /// The `d` tag of the event
+    pub identifier: String,
+    /// Hex public key of the author
+    pub author: String,
+    /// Kind of the event
+    pub kind: u16,
+    /// Relays where the event may be found
+    pub relays: Vec<String>,
+}
+
+/// Decodes a bech32 value with the human-readable part `hrp`
+fn decode_bech32(value: &str, hrp: &'static str) -> Result<Vec<u8>, Nip19Error> {
+    let (found, data) = bech32::decode(value).map_err(|e| invalid(hrp, e))?;
+    if found.as_str() != hrp {
+        return Err(Nip19Error::WrongPrefix {
+            expected: hrp,
+            found: found.to_string(),
+        });
+    }
+    Ok(data)
+}
+
+/// Encodes bytes in bech32 with the human-readable part `hrp`
+fn encode_bech32(bytes: &[u8], hrp: &'static str) -> String {
+    bech32::encode::<Bech32>(Hrp::parse_unchecked(hrp), bytes)
+        .expect("NIP-19 entities fit in a bech32 string")
+}
+
+fn to_array(bytes: &[u8], expected: &'static str) -> Result<[u8; 32], Nip19Error> {
+    bytes
+        .try_into()
+        .map_err(|_| invalid(expected, format!("expected 32 bytes, got {}", bytes.len())))
+}
+
+/// Decodes 32 hex-encoded bytes
+fn decode_hex(value: &str, expected: &'static str) -> Result<[u8; 32], Nip19Error> {
+    to_array(
+        &hex::decode(value).map_err(|e| invalid(expected, e))?,
+        expected,
+    )
+}
+
+/// Parses 32 bytes given as hex or with the bech32 prefix `hrp`
+fn parse_32_bytes(
+    value: &str,
+    hrp: &'static str,
+    hex_name: &'static str,
+) -> Result<[u8; 32], Nip19Error> {
+    let value = value.trim();
+    if value.starts_with(hrp) {
+        to_array(&decode_bech32(value, hrp)?, hrp)
+    } else {
+        decode_hex(value, hex_name)
+    }
+}
+
+/// Parses a secret key given as `nsec` or 64 hex characters.
+pub fn parse_secret_key(value: &str) -> Result<[u8; 32], Nip19Error> {
+    parse_32_bytes(value, NSEC, "hex secret key")
+}
+
+/// Encodes a secret key as `nsec`.
+pub fn encode_secret_key(secret_key: &[u8; 32]) -> String {
+    encode_bech32(secret_key, NSEC)
+}
+
+/// Parses a public key given as `npub` or hex, returns the hex key.
+pub fn parse_public_key(value: &str) -> Result<String, Nip19Error> {
+    parse_32_bytes(value, NPUB, "hex public key").map(hex::encode)
+}
+
+/// Encodes a hex public key as `npub`.
+pub fn encode_public_key(pubkey: &str) -> Result<String, Nip19Error> {
+    Ok(encode_bech32(&decode_hex(pubkey, "hex public key")?, NPUB))
+}
+
+/// Parses an event id given as `note`, `nevent` or hex, returns the hex id.
+pub fn parse_event_id(value: &str) -> Result<String, Nip19Error> {
+    let value = value.trim();
+    if value.starts_with(NEVENT) {
+        return Ok(EventPointer::decode(value)?.id);
+    }
+    parse_32_bytes(value, NOTE, "hex event id").map(hex::encode)
+}
+
+/// Encodes a hex event id as `note`.
+pub fn encode_event_id(id: &str) -> Result<String, Nip19Error> {
+    Ok(encode_bech32(&decode_hex(id, "hex event id")?, NOTE))
+}
+
+fn push_tlv(
+    bytes: &mut Vec<u8>,
+    kind: u8,
+    value: &[u8],
+    expected: &'static str,
+) -> Result<(), Nip19Error> {
+    let len = u8::try_from(value.len())
+        .map_err(|_| invalid(expected, format!("TLV value of {} bytes", value.len())))?;
+    bytes.push(kind);
+    bytes.push(len);
+    bytes.extend_from_slice(value);
+    Ok(())
+}
+
+/// Splits TLV-encoded bytes into `(type, value)` entries
+fn read_tlv<'a>(
+    mut bytes: &'a [u8],
+    expected: &'static str,
+) -> Result<Vec<(u8, &'a [u8])>, Nip19Error> {
+    let mut entries = Vec::new();
+    while let [kind, len, rest @ ..] = bytes {
+        let len = usize::from(*len);
+        if rest.len() < len {
+            return Err(invalid(expected, "truncated TLV entry"));
+        }
+        entries.push((*kind, &rest[..len]));
+        bytes = &rest[len..];
+    }
+    if !bytes.is_empty() {
+        return Err(invalid(expected, "truncated TLV entry"));
+    }
+    Ok(entries)
+}
+
+fn read_kind(value: &[u8], expected: &'static str) -> Result<u16, Nip19Error> {
+    let kind: [u8; 4] = value
+        .try_into()
+        .map_err(|_| invalid(expected, "kind must be 4 bytes"))?;
+    let kind = u32::from_be_bytes(kind);
+    u16::try_from(kind).map_err(|_| invalid(expected, format!("kind {kind} out of range")))
+}
+
+fn read_string(value: &[u8], expected: &'static str) -> Result<String, Nip19Error> {
+    String::from_utf8(value.to_vec()).map_err(|e| invalid(expected, e))
+}
+
+impl EventPointer {
+    /// Encodes the pointer as `nevent`.
+    pub fn encode(&self) -> Result<String, Nip19Error> {
+        let mut bytes = Vec::new();
+        let id = decode_hex(&self.id, "hex event id")?;
+        push_tlv(&mut bytes, TLV_SPECIAL, &id, NEVENT)?;
+        for relay in &self.relays {
+            push_tlv(&mut bytes, TLV_RELAY, relay.as_bytes(), NEVENT)?;
+        }
+        if let Some(author) = &self.author {
+            let author = decode_hex(author, "hex public key")?;
+            push_tlv(&mut bytes, TLV_AUTHOR, &author, NEVENT)?;
+        }
+        if let Some(kind) = self.kind {
+            push_tlv(&mut bytes, TLV_KIND, &u32::from(kind).to_be_bytes(), NEVENT)?;
+        }
+        Ok(encode_bech32(&bytes, NEVENT))
+    }
+
+    /// Decodes an `nevent`, unknown TLV entries are ignored.
+    pub fn decode(value: &str) -> Result<Self, Nip19Error> {
+        let bytes = decode_bech32(value.trim(), NEVENT)?;
+        let (mut id, mut author, mut kind) = (None, None, None);
+        let mut relays = Vec::new();
+        for (tlv, value) in read_tlv(&bytes, NEVENT)? {
+            match tlv {
+                TLV_SPECIAL => id = Some(hex::encode(to_array(value, NEVENT)?)),
+                TLV_RELAY => relays.push(read_string(value, NEVENT)?),
+                TLV_AUTHOR => author = Some(hex::encode(to_array(value, NEVENT)?)),
+                TLV_KIND => kind = Some(read_kind(value, NEVENT)?),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            id: id.ok_or_else(|| invalid(NEVENT, "missing event id"))?,
+            author,
+            kind,
+            relays,
+        })
+    }
+}
+
+impl AddressPointer {
+    /// Encodes the pointer as `naddr`.
+    pub fn encode(&self) -> Result<String, Nip19Error> {
+        let mut bytes = Vec::new();
+        push_tlv(&mut bytes, TLV_SPECIAL, self.identifier.as_bytes(), NADDR)?;
+        for relay in &self.relays {
+            push_tlv(&mut bytes, TLV_RELAY, relay.as_bytes(), NADDR)?;
+        }
+        let author = decode_hex(&self.author, "hex public key")?;
+        push_tlv(&mut bytes, TLV_AUTHOR, &author, NADDR)?;
+        push_tlv(
+            &mut bytes,
+            TLV_KIND,
+            &u32::from(self.kind).to_be_bytes(),
+            NADDR,
+        )?;
+        Ok(encode_bech32(&bytes, NADDR))
+    }
+
+    /// Decodes an `naddr`, unknown TLV entries are ignored.
+    pub fn decode(value: &str) -> Result<Self, Nip19Error> {
+        let bytes = decode_bech32(value.trim(), NADDR)?;
+        let (mut identifier, mut author, mut kind) = (None, None, None);
+        let mut relays = Vec::new();
+        for (tlv, value) in read_tlv(&bytes, NADDR)? {
+            match tlv {
+                TLV_SPECIAL => identifier = Some(read_string(value, NADDR)?),
+                TLV_RELAY => relays.push(read_string(value, NADDR)?),
+                TLV_AUTHOR => author = Some(hex::encode(to_array(value, NADDR)?)),
+                TLV_KIND => kind = Some(read_kind(value, NADDR)?),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            identifier: identifier.ok_or_else(|| invalid(NADDR, "missing identifier"))?,
+            author: author.ok_or_else(|| invalid(NADDR, "missing author"))?,
+            kind: kind.ok_or_else(|| invalid(NADDR, "missing kind"))?,
+            relays,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NIP-19 test vectors
+    const NPUB_VECTOR: &str = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+    const PUBKEY_HEX: &str = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+    const NSEC_VECTOR: &str = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+    const SECRET_HEX: &str = "67dea2ed018072d675f5415ecfaed7d2597555e202d85b3d65ea4e58d2d92ffa";
+
+    #[test]
+    fn test_parse_secret_key() {
+        let secret = parse_secret_key(NSEC_VECTOR).unwrap();
+        assert_eq!(hex::encode(secret), SECRET_HEX);
+        assert_eq!(parse_secret_key(SECRET_HEX).unwrap(), secret);
+        assert_eq!(
+            parse_secret_key(&format!("{NSEC_VECTOR}\n")).unwrap(),
+            secret
+        );
+        assert_eq!(encode_secret_key(&secret), NSEC_VECTOR);
+    }
+
+    #[test]
+    fn test_rejects_invalid_secret_keys() {
+        // Checksum error
+        let mut corrupted = NSEC_VECTOR.to_string();
+        corrupted.replace_range(10..11, "q");
+        assert!(matches!(
+            parse_secret_key(&corrupted),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "nsec",
+                ..
+            })
+        ));
+
+        // Too short
+        assert!(matches!(
+            parse_secret_key(&SECRET_HEX[..62]),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "hex secret key",
+                ..
+            })
+        ));
+        assert!(parse_secret_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_public_keys_and_notes() {
+        assert_eq!(parse_public_key(NPUB_VECTOR).unwrap(), PUBKEY_HEX);
+        assert_eq!(parse_public_key(PUBKEY_HEX).unwrap(), PUBKEY_HEX);
+        assert_eq!(encode_public_key(PUBKEY_HEX).unwrap(), NPUB_VECTOR);
+
+        let note = encode_event_id(SECRET_HEX).unwrap();
+        assert!(note.starts_with("note1"));
+        assert_eq!(parse_event_id(&note).unwrap(), SECRET_HEX);
+        assert_eq!(parse_event_id(SECRET_HEX).unwrap(), SECRET_HEX);
+
+        // A note is not a public key, and an npub with another prefix fails its checksum
+        assert!(matches!(
+            parse_public_key(&note),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "hex public key",
+                ..
+            })
+        ));
+        let npub_as_note = format!("note{}", &NPUB_VECTOR[4..]);
+        assert!(matches!(
+            parse_event_id(&npub_as_note),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "note",
+                ..
+            })
+        ));
+        assert_eq!(
+            decode_bech32(NSEC_VECTOR, NPUB),
+            Err(Nip19Error::WrongPrefix {
+                expected: "npub",
+                found: "nsec".to_string()
+            })
+        );
+        assert!(matches!(
+            encode_public_key("abcd"),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "hex public key",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_event_pointers() {
+        let pointer = EventPointer {
+            id: SECRET_HEX.to_string(),
+            author: Some(PUBKEY_HEX.to_string()),
+            kind: Some(30_350),
+            relays: vec!["wss://relay.example.com".to_string()],
+        };
+        let nevent = pointer.encode().unwrap();
+        assert!(nevent.starts_with("nevent1"));
+        assert_eq!(EventPointer::decode(&nevent).unwrap(), pointer);
+        assert_eq!(parse_event_id(&nevent).unwrap(), SECRET_HEX);
+
+        let address = AddressPointer {
+            identifier: "ed4f3597c1dc54b71996b564aae4f5cec635d9ce52753c4982492bcf9a614138"
+                .to_string(),
+            author: PUBKEY_HEX.to_string(),
+            kind: 30_350,
+            relays: Vec::new(),
+        };
+        let naddr = address.encode().unwrap();
+        assert!(naddr.starts_with("naddr1"));
+        assert_eq!(AddressPointer::decode(&naddr).unwrap(), address);
+
+        assert!(matches!(
+            AddressPointer::decode(&nevent),
+            Err(Nip19Error::WrongPrefix {
+                expected: "naddr",
+                ..
+            })
+        ));
+        // Missing author and kind
+        assert!(matches!(
+            AddressPointer::decode(&encode_bech32(&[0, 1, b'd'], NADDR)),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "naddr",
+                ..
+            })
+        ));
+        // Truncated TLV entry
+        assert!(matches!(
+            EventPointer::decode(&encode_bech32(&[0, 32, 1], NEVENT)),
+            Err(Nip19Error::InvalidEncoding {
+                expected: "nevent",
+                ..
+            })
+        ));
+    }
+}
@@ -6,62 +6,257 @@
 //! Reuses code from https://github.com/stutxo/op_ctv_payment_pool
 
 use crate::{
-    withdraw::{build_withdraw_transaction, WithdrawParams, WithdrawType},
+    lifecycle::MarketState,
+    withdraw::{build_withdraw_template, WithdrawType},
     PredictionMarket,
 };
 use anyhow::Context;
 use bitcoin::OutPoint;
 use bitcoin::{
-    consensus::Encodable,
+    absolute,
+    consensus::serialize,
     hashes::{sha256, Hash},
     key::Secp256k1,
-    opcodes::all::{OP_DROP, OP_NOP4, OP_NOP5},
+    opcodes::all::{OP_CLTV, OP_CSV, OP_DROP, OP_NOP4, OP_RETURN_204, OP_VERIFY},
+    relative,
     script::Builder,
-    taproot::TaprootBuilder,
-    Address, Opcode, ScriptBuf, Sequence, Transaction, XOnlyPublicKey,
+    taproot::{ControlBlock, LeafVersion, TaprootBuilder, TaprootSpendInfo},
+    Address, Network, Opcode, ScriptBuf, Sequence, Transaction, XOnlyPublicKey,
 };
+use serde::{Deserialize, Serialize};
 
 /// The Check Template Verify opcode.
 pub const OP_CTV: Opcode = OP_NOP4;
 /// The Check Signature From Stack opcode.
-pub const OP_CSFS: Opcode = OP_NOP5;
+///
+/// BIP-348 defines it as `OP_SUCCESS204` (0xcc) in tapscript, not as an `OP_NOPx`.
+pub const OP_CSFS: Opcode = OP_RETURN_204;
+
+/// Taproot spend information of a market pool.
+///
+/// Holds the leaf scripts next to the finalized [`TaprootSpendInfo`], so the
+/// control block of every spending path can be derived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolSpendInfo {
+    /// Finalized Taproot tree of the pool
+    pub spend_info: TaprootSpendInfo,
+    /// Payout leaf of each outcome, indexed like `PredictionMarket::outcomes`
+    /// (like `ScalarMarket::leaves` for a scalar market)
+    pub payout_scripts: Vec<ScriptBuf>,
+    /// Escape (withdrawal) leaf
+    pub escape_script: ScriptBuf,
+    /// Network of the pool address
+    pub network: Network,
+}
+
+impl PoolSpendInfo {
+    /// Address of the pool.
+    pub fn address(&self) -> Address {
+        Address::p2tr_tweaked(self.spend_info.output_key(), self.network)
+    }
+
+    /// Leaf script and control block paying out the bets on `outcome`.
+    pub fn payout_leaf(&self, outcome: usize) -> anyhow::Result<(ScriptBuf, ControlBlock)> {
+        let script = self
+            .payout_scripts
+            .get(outcome)
+            .ok_or_else(|| anyhow::anyhow!("Invalid outcome index: {}", outcome))?;
+        self.leaf(script)
+    }
+
+    /// Leaf script and control block of the escape path.
+    pub fn escape_leaf(&self) -> anyhow::Result<(ScriptBuf, ControlBlock)> {
+        self.leaf(&self.escape_script)
+    }
+
+    fn leaf(&self, script: &ScriptBuf) -> anyhow::Result<(ScriptBuf, ControlBlock)> {
+        let control_block = self
+            .spend_info
+            .control_block(&(script.clone(), LeafVersion::TapScript))
+            .ok_or_else(|| anyhow::anyhow!("Leaf script is not part of the pool tree"))?;
+        Ok((script.clone(), control_block))
+    }
+}
+
+impl PoolSpendInfo {
+    /// Frontend-facing description of the pool.
+    pub fn descriptor(&self) -> PoolDescriptor {
+        PoolDescriptor {
+            scheme: AddressScheme::Pool,
+            address: self.address().to_string(),
+            internal_key: self.spend_info.internal_key().to_string(),
+            payout_leaves: self
+                .payout_scripts
+                .iter()
+                .map(|script| script.to_hex_string())
+                .collect(),
+            escape_leaf: Some(self.escape_script.to_hex_string()),
+        }
+    }
+}
+
+/// Address derivation of a market.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressScheme {
+    /// CSFS (0xcc) + CTV payout leaves and an escape leaf, spendable by the withdraw module
+    #[default]
+    Pool,
+    /// Raw `OP_CHECKSIGFROMSTACK` (0xcc) leaves without CTV, advertised by earlier releases.
+    ///
+    /// Only meant to locate funds sent to such an address: without CTV any
+    /// transaction carrying the oracle signature can spend these leaves, and on
+    /// nodes without BIP-348 0xcc is still `OP_SUCCESS204`.
+    Legacy,
+}
+
+/// Frontend-facing description of a market pool.
+///
+/// Every frontend (CLI, WASM, web) renders the pool from this struct, so they
+/// all advertise the same address.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolDescriptor {
+    /// Address derivation used
+    pub scheme: AddressScheme,
+    /// Bech32m Taproot address of the pool
+    pub address: String,
+    /// Taproot internal key (the NUMS point), hex-encoded
+    pub internal_key: String,
+    /// Payout leaf script of each outcome, hex-encoded
+    pub payout_leaves: Vec<String>,
+    /// Escape leaf script, hex-encoded (absent for the legacy scheme)
+    pub escape_leaf: Option<String>,
+}
+
+/// [`PoolSpendInfo`] cached alongside a market.
+///
+/// The cache is derived from the market, so it is not serialized. It holds the
+/// fingerprint of the market fields the pool was built from and is only served
+/// while they are unchanged: editing the bets, fees or escape timelock of a
+/// frozen market rebuilds the pool instead of returning a stale one.
+#[derive(Debug, Clone, Default)]
+pub struct PoolSpendCache(pub(crate) Option<(sha256::Hash, PoolSpendInfo)>);
+
+impl PoolSpendCache {
+    /// Caches `pool`, built from the current fields of `market`.
+    pub(crate) fn new(market: &PredictionMarket, pool: PoolSpendInfo) -> crate::Result<Self> {
+        Ok(Self(Some((pool_fingerprint(market)?, pool))))
+    }
+
+    /// The cached pool, if it was built from the current fields of `market`.
+    pub(crate) fn get(&self, market: &PredictionMarket) -> crate::Result<Option<&PoolSpendInfo>> {
+        match &self.0 {
+            Some((fingerprint, pool)) if *fingerprint == pool_fingerprint(market)? => {
+                Ok(Some(pool))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// An empty cache matches any market. Two filled caches of otherwise equal
+/// markets differ only if one of them is stale.
+impl PartialEq for PoolSpendCache {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some((fingerprint, _)), Some((other, _))) => fingerprint == other,
+            _ => true,
+        }
+    }
+}
+
+impl Eq for PoolSpendCache {}
+
+/// Hash of the market fields the pool is built from.
+fn pool_fingerprint(market: &PredictionMarket) -> crate::Result<sha256::Hash> {
+    let fields = serde_json::to_vec(&(
+        &market.outcomes,
+        &market.oracle_pubkey,
+        market.settlement_timestamp,
+        market.network,
+        &market.signet_challenge,
+        market.total_amount,
+        &market.bets,
+        market.withdraw_timeout,
+        &market.escape_timelock,
+        &market.fees,
+    ))?;
+    Ok(sha256::Hash::hash(&fields))
+}
 
 /// Generate the pool address for a market.
 ///
 /// The pool address is a Taproot address with the following structure:
-/// - Path 0: CSFS verification for outcome A
-/// - Path 1: CSFS verification for outcome B
-/// - Path 2: Escape (withdrawal) branch
+/// - Paths 0..N: CSFS verification for each of the N outcomes
+/// - Path N: Escape (withdrawal) branch
+///
+/// The leaves are laid out as a balanced tree, see [`balanced_leaf_depths`].
 pub fn generate_pool_address(market: &PredictionMarket) -> anyhow::Result<Address> {
-    let escape_ctv_hash = calculate_ctv_hash_for_escape_tx(market)?;
-    let escape_script = build_script_for_escape(escape_ctv_hash);
+    Ok(generate_pool_spend_info(market)?.address())
+}
 
-    let outcome_a_ctv_hash = calculate_ctv_hash_for_payout_tx(market, 'A')?;
-    let outcome_a_script = build_script_for_outcome(
-        outcome_a_ctv_hash,
-        &market.oracle_pubkey,
-        &market.outcome_a.nostr_id(),
-    )?;
+/// Generate the full Taproot spend information of the pool of a market.
+///
+/// See [`generate_pool_address`] for the layout of the tree.
+pub fn generate_pool_spend_info(market: &PredictionMarket) -> anyhow::Result<PoolSpendInfo> {
+    let mut payout_scripts = Vec::with_capacity(market.outcomes.len());
+    for (index, outcome) in market.outcomes.iter().enumerate() {
+        let outcome_ctv_hash = calculate_ctv_hash_for_payout_tx(market, index)?;
+        payout_scripts.push(build_script_for_outcome(
+            outcome_ctv_hash,
+            &market.oracle_pubkey,
+            &outcome.nostr_id(),
+        )?);
+    }
 
-    let outcome_b_ctv_hash = calculate_ctv_hash_for_payout_tx(market, 'B')?;
-    let outcome_b_script = build_script_for_outcome(
-        outcome_b_ctv_hash,
-        &market.oracle_pubkey,
-        &market.outcome_b.nostr_id(),
-    )?;
+    let escape_ctv_hash = calculate_ctv_hash_for_escape_tx(market)?;
+    let escape_script = build_script_for_escape(escape_ctv_hash, &market.escape_timelock()?);
+
+    let mut scripts = payout_scripts.clone();
+    scripts.push(escape_script.clone());
+    let spend_info = build_balanced_spend_info(scripts)?;
+
+    Ok(PoolSpendInfo {
+        spend_info,
+        payout_scripts,
+        escape_script,
+        network: market.network,
+    })
+}
 
+/// Build the Taproot spend info of a pool holding `scripts` as leaves.
+///
+/// The internal key is the NUMS point, so the pool can only be spent through
+/// one of its script paths. The leaves are laid out as a balanced tree.
+pub fn build_balanced_spend_info(scripts: Vec<ScriptBuf>) -> anyhow::Result<TaprootSpendInfo> {
     let nums_point = PredictionMarket::nums_point()?;
     let secp = Secp256k1::new();
 
-    let spend_info = TaprootBuilder::new()
-        .add_leaf(2, outcome_a_script)?
-        .add_leaf(2, outcome_b_script)?
-        .add_leaf(1, escape_script)?
+    let depths = balanced_leaf_depths(scripts.len());
+    let mut builder = TaprootBuilder::new();
+    for (script, depth) in scripts.into_iter().zip(depths) {
+        builder = builder.add_leaf(depth, script)?;
+    }
+    builder
         .finalize(&secp, nums_point)
-        .map_err(|e| anyhow::anyhow!("Failed to finalize taproot: {e:?}"))?;
+        .map_err(|e| anyhow::anyhow!("Failed to finalize taproot: {e:?}"))
+}
 
-    let address = Address::p2tr_tweaked(spend_info.output_key(), market.network);
-    Ok(address)
+/// Compute the leaf depths of a balanced Taproot tree with `count` leaves.
+///
+/// The depths are returned in the order the leaves must be added to a
+/// [`TaprootBuilder`]. The tree is complete: every leaf sits at depth `d` or
+/// `d - 1` where `d = ceil(log2(count))`, and the shallower leaves come last.
+/// For the binary market (two outcomes plus escape) this gives `[2, 2, 1]`.
+pub fn balanced_leaf_depths(count: usize) -> Vec<u8> {
+    if count <= 1 {
+        return vec![0; count];
+    }
+    let depth = count.next_power_of_two().trailing_zeros() as u8;
+    let shallow = count.next_power_of_two() - count;
+    let mut depths = vec![depth; count - shallow];
+    depths.extend(std::iter::repeat_n(depth - 1, shallow));
+    depths
 }
 
 /// Build the script for a successful (payout based on the winning outcome) branch.
@@ -85,42 +280,95 @@ pub fn build_script_for_outcome(
         .push_slice(outcome_hash.as_byte_array())
         .push_x_only_key(&oracle_pubkey)
         .push_opcode(OP_CSFS)
-        .push_opcode(OP_DROP)
+        .push_opcode(OP_VERIFY)
         .push_slice(ctv_hash)
         .push_opcode(OP_CTV)
         .into_script();
     Ok(script)
 }
 
+/// Timelock enforced by the escape leaf.
+///
+/// The escape path refunds every bet when the oracle fails to attest. It is
+/// either locked until an absolute block height or median-time-past
+/// (`OP_CHECKLOCKTIMEVERIFY`), or for a number of blocks or 512-second
+/// intervals after the pool funding confirms (`OP_CHECKSEQUENCEVERIFY`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EscapeTimelock {
+    /// Absolute timelock, at a block height or a median-time-past
+    Absolute(absolute::LockTime),
+    /// Relative timelock from the pool funding, in blocks or 512-second intervals
+    Relative(relative::LockTime),
+}
+
+impl EscapeTimelock {
+    /// Absolute median-time-past timelock at `settlement_timestamp + withdraw_timeout`.
+    pub fn after_settlement(
+        settlement_timestamp: u64,
+        withdraw_timeout: u32,
+    ) -> anyhow::Result<Self> {
+        let escape_time: u32 = (settlement_timestamp + withdraw_timeout as u64)
+            .try_into()
+            .with_context(|| "Escape time does not fit a locktime")?;
+        let lock_time = absolute::LockTime::from_time(escape_time)
+            .with_context(|| format!("Invalid escape time: {}", escape_time))?;
+        Ok(Self::Absolute(lock_time))
+    }
+
+    /// `nLockTime` of the escape transaction.
+    pub fn lock_time(&self) -> absolute::LockTime {
+        match self {
+            Self::Absolute(lock_time) => *lock_time,
+            Self::Relative(_) => absolute::LockTime::ZERO,
+        }
+    }
+
+    /// `nSequence` of the pool input of the escape transaction.
+    pub fn sequence(&self) -> Sequence {
+        match self {
+            // Any non-final sequence enables nLockTime
+            Self::Absolute(_) => Sequence::ENABLE_RBF_NO_LOCKTIME,
+            Self::Relative(lock_time) => lock_time.to_sequence(),
+        }
+    }
+}
+
 /// Build the script for an escape (withdrawal) branch.
-pub fn build_script_for_escape(ctv_hash: [u8; 32]) -> ScriptBuf {
-    Builder::new()
+///
+/// The leaf checks the timelock itself, then the CTV template of the escape transaction:
+/// ```text
+/// <timelock> OP_CHECKLOCKTIMEVERIFY|OP_CHECKSEQUENCEVERIFY OP_DROP <ctv_hash> OP_CTV
+/// ```
+pub fn build_script_for_escape(ctv_hash: [u8; 32], timelock: &EscapeTimelock) -> ScriptBuf {
+    let builder = match timelock {
+        EscapeTimelock::Absolute(lock_time) => Builder::new()
+            .push_lock_time(*lock_time)
+            .push_opcode(OP_CLTV),
+        EscapeTimelock::Relative(lock_time) => Builder::new()
+            .push_sequence(lock_time.to_sequence())
+            .push_opcode(OP_CSV),
+    };
+    builder
+        .push_opcode(OP_DROP)
         .push_slice(ctv_hash)
         .push_opcode(OP_CTV)
         .into_script()
 }
 
-/// Calculate the CTV hash for a payout to the winning bets.
+/// Calculate the CTV hash for a payout to the bets on `winning_outcome` (an outcome index).
 /// Builds a complete transaction to ensure consistency with withdraw.rs
 pub fn calculate_ctv_hash_for_payout_tx(
     market: &PredictionMarket,
-    winning_outcome: char,
+    winning_outcome: usize,
 ) -> anyhow::Result<[u8; 32]> {
     // Create a market copy with the winning outcome set for CTV calculation
     let mut market_copy = market.clone();
-    market_copy.settled = true;
-    market_copy.winning_outcome = Some(winning_outcome);
-
-    // Create withdraw params for a payout transaction
-    let params = WithdrawParams {
-        market: market_copy,
-        withdraw_type: WithdrawType::Payout,
-        pool_utxo: OutPoint::null(), // Dummy UTXO for CTV calculation
-        fee_rate: None,
-    };
+    market_copy.state = MarketState::Settled { winning_outcome };
 
     // Build the transaction and extract CTV hash
-    let tx = build_withdraw_transaction(params)?;
+    // Dummy UTXO for CTV calculation
+    let tx = build_withdraw_template(&market_copy, &WithdrawType::Payout, OutPoint::null())?;
     let hash = calculate_ctv_hash_from_transaction(&tx);
     Ok(hash)
 }
@@ -128,49 +376,55 @@ pub fn calculate_ctv_hash_for_payout_tx(
 /// Calculate the CTV hash for an escape (withdrawal) transaction.
 /// Builds a complete transaction to ensure consistency with withdraw.rs
 pub fn calculate_ctv_hash_for_escape_tx(market: &PredictionMarket) -> anyhow::Result<[u8; 32]> {
-    // Create withdraw params for an escape transaction
-    let params = WithdrawParams {
-        market: market.clone(),
-        withdraw_type: WithdrawType::Escape,
-        pool_utxo: OutPoint::null(), // Dummy UTXO for CTV calculation
-        fee_rate: None,
-    };
-
     // Build the transaction and extract CTV hash
-    let tx = build_withdraw_transaction(params)?;
+    // Dummy UTXO for CTV calculation
+    let tx = build_withdraw_template(market, &WithdrawType::Escape, OutPoint::null())?;
     let hash = calculate_ctv_hash_from_transaction(&tx);
     Ok(hash)
 }
 
-/// Calculate CTV hash from a complete transaction
+/// Calculate the CTV hash of a pool spend (the pool UTXO is the input at index 0).
 pub fn calculate_ctv_hash_from_transaction(tx: &Transaction) -> [u8; 32] {
+    calculate_ctv_hash(tx, 0)
+}
+
+/// Calculate the BIP-119 `DefaultCheckTemplateVerifyHash` of `tx` for the input at `input_index`.
+///
+/// The hash commits to the version, locktime, scriptSigs (only when one of
+/// them is non-empty), the number of inputs and their sequences, the outputs,
+/// and the index of the input executing `OP_CTV`.
+pub fn calculate_ctv_hash(tx: &Transaction, input_index: u32) -> [u8; 32] {
     let mut buffer = Vec::new();
-    buffer.extend((tx.version.0 as i32).to_le_bytes()); // version
+    buffer.extend(tx.version.0.to_le_bytes()); // version
     buffer.extend(tx.lock_time.to_consensus_u32().to_le_bytes()); // locktime
-    buffer.extend(1_u32.to_le_bytes()); // inputs len (always 1 for our case)
 
-    // Calculate sequence hash
-    let seq_hash = if tx.input.is_empty() {
-        sha256::Hash::hash(&Sequence::ENABLE_RBF_NO_LOCKTIME.0.to_le_bytes())
-    } else {
-        sha256::Hash::hash(&tx.input[0].sequence.0.to_le_bytes())
-    };
-    buffer.extend(seq_hash.to_byte_array()); // sequences
+    // The scriptSigs hash is only committed when one of them is non-empty
+    if tx.input.iter().any(|input| !input.script_sig.is_empty()) {
+        let script_sigs: Vec<u8> = tx
+            .input
+            .iter()
+            .flat_map(|input| serialize(&input.script_sig))
+            .collect();
+        buffer.extend(sha256::Hash::hash(&script_sigs).to_byte_array()); // scriptSigs hash
+    }
 
-    let outputs_len = tx.output.len() as u32;
-    buffer.extend(outputs_len.to_le_bytes()); // outputs len
+    buffer.extend((tx.input.len() as u32).to_le_bytes()); // inputs len
 
-    let mut output_bytes: Vec<u8> = Vec::new();
-    for output in &tx.output {
-        output.consensus_encode(&mut output_bytes).unwrap();
-    }
-    buffer.extend(sha256::Hash::hash(&output_bytes).to_byte_array()); // outputs hash
+    let sequences: Vec<u8> = tx
+        .input
+        .iter()
+        .flat_map(|input| input.sequence.to_consensus_u32().to_le_bytes())
+        .collect();
+    buffer.extend(sha256::Hash::hash(&sequences).to_byte_array()); // sequences hash
+
+    buffer.extend((tx.output.len() as u32).to_le_bytes()); // outputs len
 
-    // We have a single input in our case, which is pooled UTXO
-    buffer.extend(0_u32.to_le_bytes()); // inputs index
+    let outputs: Vec<u8> = tx.output.iter().flat_map(serialize).collect();
+    buffer.extend(sha256::Hash::hash(&outputs).to_byte_array()); // outputs hash
 
-    let hash = sha256::Hash::hash(&buffer);
-    hash.to_byte_array()
+    buffer.extend(input_index.to_le_bytes()); // input index
+
+    sha256::Hash::hash(&buffer).to_byte_array()
 }
 
 #[cfg(test)]
@@ -206,8 +460,7 @@ mod tests {
     #[test]
     fn test_generate_pool_address_empty_bets() {
         let mut market = create_test_market();
-        market.bets_a.clear();
-        market.bets_b.clear();
+        market.bets.iter_mut().for_each(Vec::clear);
         market.total_amount = 0;
 
         let result = generate_pool_address(&market);
@@ -224,11 +477,17 @@ mod tests {
         assert!(result.is_err(), "Should fail with invalid oracle pubkey");
     }
 
+    #[test]
+    fn test_calculate_ctv_hash_for_payout_tx_invalid_outcome() {
+        let market = create_test_market();
+        assert!(calculate_ctv_hash_for_payout_tx(&market, 2).is_err());
+    }
+
     #[test]
     fn test_calculate_ctv_hash_for_payout_tx_empty_bets() {
         // Create a market with no bets
         let market = create_empty_test_market();
-        let result = calculate_ctv_hash_for_payout_tx(&market, 'A');
+        let result = calculate_ctv_hash_for_payout_tx(&market, 0);
         assert!(result.is_err(), "Should fail with empty winning bets");
     }
 
@@ -248,5 +507,231 @@ mod tests {
             script_a, script_b,
             "Different outcomes should produce different scripts"
         );
+
+        // BIP-348 puts OP_CHECKSIGFROMSTACK at 0xcc, OP_NOP5 is a plain NOP
+        let opcodes: Vec<_> = script_a
+            .instructions()
+            .filter_map(|i| i.unwrap().opcode())
+            .collect();
+        assert_eq!(opcodes, vec![OP_CSFS, OP_VERIFY, OP_CTV]);
+        assert_eq!(OP_CSFS.to_u8(), crate::OP_CHECKSIGFROMSTACK);
+    }
+
+    #[test]
+    fn test_pool_spend_info_control_blocks() {
+        let market =
+            create_test_market_with_outcomes(&[vec![10000], vec![20000], vec![5000, 5000]]);
+        let pool = generate_pool_spend_info(&market).unwrap();
+        assert_eq!(pool.address(), generate_pool_address(&market).unwrap());
+
+        let secp = Secp256k1::verification_only();
+        let output_key = pool.spend_info.output_key().to_x_only_public_key();
+        let mut leaves: Vec<_> = (0..3).map(|i| pool.payout_leaf(i).unwrap()).collect();
+        leaves.push(pool.escape_leaf().unwrap());
+        for (script, control_block) in &leaves {
+            assert!(control_block.verify_taproot_commitment(&secp, output_key, script));
+        }
+        assert!(pool.payout_leaf(3).is_err());
+    }
+
+    #[test]
+    fn test_pool_descriptor() {
+        let market = create_test_market();
+        let descriptor = market.pool_descriptor(AddressScheme::Pool).unwrap();
+        assert_eq!(
+            descriptor.address,
+            generate_pool_address(&market).unwrap().to_string()
+        );
+        assert_eq!(descriptor.address, market.get_market_address().unwrap());
+        assert_eq!(descriptor.payout_leaves.len(), 2);
+        assert!(descriptor.escape_leaf.is_some());
+
+        let legacy = market.pool_descriptor(AddressScheme::Legacy).unwrap();
+        assert_eq!(legacy.scheme, AddressScheme::Legacy);
+        assert_ne!(legacy.address, descriptor.address);
+        assert_eq!(legacy.internal_key, descriptor.internal_key);
+        assert!(legacy.escape_leaf.is_none());
+
+        // Without bets there is nothing to commit to, only the legacy address exists
+        let empty = create_empty_test_market();
+        assert!(empty.get_market_address().is_err());
+        assert!(empty.pool_descriptor(AddressScheme::Legacy).is_ok());
+    }
+
+    #[test]
+    fn test_escape_timelocks() {
+        let ctv_hash = [0x42; 32];
+        let absolute = EscapeTimelock::after_settlement(1735689600, 86400).unwrap();
+        assert_eq!(
+            absolute.lock_time(),
+            absolute::LockTime::from_time(1735776000).unwrap()
+        );
+        assert!(absolute.sequence().enables_absolute_lock_time());
+        let script = build_script_for_escape(ctv_hash, &absolute);
+        assert!(script
+            .instructions()
+            .any(|i| i.unwrap().opcode() == Some(OP_CLTV)));
+
+        let relative = EscapeTimelock::Relative(relative::LockTime::from_height(144));
+        assert_eq!(relative.lock_time(), absolute::LockTime::ZERO);
+        assert_eq!(relative.sequence(), Sequence::from_height(144));
+        let script = build_script_for_escape(ctv_hash, &relative);
+        assert!(script
+            .instructions()
+            .any(|i| i.unwrap().opcode() == Some(OP_CSV)));
+
+        // A block height timelock changes the pool address
+        let mut market = create_test_market();
+        let address = generate_pool_address(&market).unwrap();
+        market.escape_timelock = Some(EscapeTimelock::Absolute(
+            absolute::LockTime::from_height(900_000).unwrap(),
+        ));
+        assert_ne!(generate_pool_address(&market).unwrap(), address);
+
+        assert!(EscapeTimelock::after_settlement(u32::MAX as u64, 1).is_err());
+
+        for timelock in [absolute, relative] {
+            let json = serde_json::to_string(&timelock).unwrap();
+            assert_eq!(
+                serde_json::from_str::<EscapeTimelock>(&json).unwrap(),
+                timelock
+            );
+        }
+    }
+
+    #[test]
+    fn test_ctv_hash_preimage() {
+        use bitcoin::{Amount, OutPoint, TxIn, TxOut, Witness};
+
+        let input = |sequence: u32| TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence(sequence),
+            witness: Witness::new(),
+        };
+        let mut tx = Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: absolute::LockTime::from_consensus(600_000),
+            input: vec![input(0xfffffffd), input(7)],
+            output: vec![TxOut {
+                value: Amount::from_sat(5000),
+                script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+            }],
+        };
+
+        // Assemble the BIP-119 preimage field by field
+        let mut sequences = Vec::new();
+        sequences.extend(0xfffffffd_u32.to_le_bytes());
+        sequences.extend(7_u32.to_le_bytes());
+        let mut outputs = Vec::new();
+        outputs.extend(5000_u64.to_le_bytes());
+        outputs.extend([0x01, 0x51]);
+        let mut preimage = Vec::new();
+        preimage.extend(2_i32.to_le_bytes());
+        preimage.extend(600_000_u32.to_le_bytes());
+        preimage.extend(2_u32.to_le_bytes());
+        preimage.extend(sha256::Hash::hash(&sequences).to_byte_array());
+        preimage.extend(1_u32.to_le_bytes());
+        preimage.extend(sha256::Hash::hash(&outputs).to_byte_array());
+        preimage.extend(1_u32.to_le_bytes());
+        assert_eq!(
+            calculate_ctv_hash(&tx, 1),
+            sha256::Hash::hash(&preimage).to_byte_array()
+        );
+
+        // The input index is committed
+        assert_ne!(calculate_ctv_hash(&tx, 0), calculate_ctv_hash(&tx, 1));
+        assert_eq!(
+            calculate_ctv_hash_from_transaction(&tx),
+            calculate_ctv_hash(&tx, 0)
+        );
+
+        // A non-empty scriptSig adds the scriptSigs hash right after the locktime
+        tx.input[1].script_sig = ScriptBuf::from_bytes(vec![0x00]);
+        let script_sigs = [0x00, 0x01, 0x00];
+        let mut with_script_sigs = preimage[..8].to_vec();
+        with_script_sigs.extend(sha256::Hash::hash(&script_sigs).to_byte_array());
+        with_script_sigs.extend(&preimage[8..]);
+        assert_eq!(
+            calculate_ctv_hash(&tx, 1),
+            sha256::Hash::hash(&with_script_sigs).to_byte_array()
+        );
+
+        // Witnesses are not committed
+        tx.input[0].witness.push([0x01]);
+        assert_eq!(
+            calculate_ctv_hash(&tx, 1),
+            sha256::Hash::hash(&with_script_sigs).to_byte_array()
+        );
+    }
+
+    #[test]
+    fn test_ctv_hash_bip119_vectors() {
+        // Official vectors, bitcoin/bips bip-0119/vectors/ctvhash.json: a
+        // description string, then transactions with their hash at each index
+        let vectors: Vec<serde_json::Value> =
+            serde_json::from_str(include_str!("../tests/data/ctvhash.json")).unwrap();
+        let mut checked = 0;
+        for vector in vectors.iter().filter(|vector| vector.is_object()) {
+            let tx: Transaction =
+                bitcoin::consensus::encode::deserialize_hex(vector["hex_tx"].as_str().unwrap())
+                    .unwrap();
+            let indexes = vector["spend_index"].as_array().unwrap();
+            let results = vector["result"].as_array().unwrap();
+            assert_eq!(indexes.len(), results.len());
+            for (index, result) in indexes.iter().zip(results) {
+                let index = index.as_u64().unwrap() as u32;
+                assert_eq!(
+                    hex::encode(calculate_ctv_hash(&tx, index)),
+                    result.as_str().unwrap(),
+                    "{} at index {index}",
+                    vector["hex_tx"]
+                );
+                checked += 1;
+            }
+        }
+        assert_eq!(checked, 400);
+    }
+
+    #[test]
+    fn test_balanced_leaf_depths() {
+        assert_eq!(balanced_leaf_depths(1), vec![0]);
+        assert_eq!(balanced_leaf_depths(2), vec![1, 1]);
+        assert_eq!(balanced_leaf_depths(3), vec![2, 2, 1]);
+        assert_eq!(balanced_leaf_depths(4), vec![2, 2, 2, 2]);
+        assert_eq!(balanced_leaf_depths(5), vec![3, 3, 2, 2, 2]);
+        assert_eq!(
+            balanced_leaf_depths(33).iter().filter(|d| **d == 6).count(),
+            2
+        );
+
+        // Every layout must describe a complete tree
+        for count in 1..=64 {
+            let weight: f64 = balanced_leaf_depths(count)
+                .iter()
+                .map(|d| 0.5f64.powi(*d as i32))
+                .sum();
+            assert_eq!(weight, 1.0, "Incomplete tree for {count} leaves");
+        }
+    }
+
+    #[test]
+    fn test_generate_pool_address_categorical() {
+        let market =
+            create_test_market_with_outcomes(&[vec![10000], vec![20000], vec![], vec![5000, 5000]]);
+        let address = generate_pool_address(&market).expect("categorical pool address");
+        assert!(address.to_string().starts_with("bcrt1p"));
+
+        // Every outcome gets its own template, an outcome without bets refunds everyone
+        let hashes: Vec<_> = (0..4)
+            .map(|i| calculate_ctv_hash_for_payout_tx(&market, i).unwrap())
+            .collect();
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(hashes[i + 1..].iter().all(|other| other != hash));
+        }
+        assert_ne!(
+            hashes[2],
+            calculate_ctv_hash_for_escape_tx(&market).unwrap()
+        );
     }
 }
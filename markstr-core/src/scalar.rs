@@ -0,0 +1,888 @@
+//! # Scalar Markets
+//!
+//! Numeric-range markets where the oracle attests a number (e.g. "BTC price on
+//! date X") and the pool is split between a long and a short side according to
+//! a payout curve interpolated between a floor and a cap.
+//!
+//! The attested value is decomposed into `num_digits` digits in a fixed `base`,
+//! in the style of DLC numeric outcomes. For a value `d0 d1 ... dn` the oracle
+//! signs one message per digit prefix (`d0`, `d0 d1`, ..., `d0 d1 ... dn`).
+//! The payout curve is compiled into a set of CSFS + CTV leaves, each committing
+//! to one digit prefix: every value starting with that prefix pays out the same
+//! way, so a handful of leaves cover the whole numeric range.
+
+use crate::{
+    error::Result,
+    event::{NostrEvent, MARKET_EVENT_KIND, OUTCOME_EVENT_KIND},
+    market::{Bet, MarketFees},
+    pool::{
+        build_balanced_spend_info, build_script_for_escape, build_script_for_outcome,
+        calculate_ctv_hash_from_transaction, EscapeTimelock, PoolSpendInfo,
+    },
+    withdraw::{
+        build_pool_spend_transaction, create_escape_witness, create_outcome_witness,
+        generate_escape_outputs, WithdrawType,
+    },
+    MarketError, DUST_THRESHOLD,
+};
+use anyhow::Context;
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Largest number of payout steps of a curve, each one a payout template of the pool
+pub const MAX_SCALAR_PRECISION: u64 = 1000;
+
+/// Largest number of digit-prefix leaves of a pool.
+///
+/// Keeps the balanced Taproot tree far below its depth limit of 128 and the
+/// pool quick to build.
+pub const MAX_SCALAR_LEAVES: usize = 1 << 16;
+
+/// Side of a bet in a scalar market
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarSide {
+    /// Wins more as the attested value goes up
+    Long,
+    /// Wins more as the attested value goes down
+    Short,
+}
+
+/// A leaf of the scalar market pool: a digit prefix and the payout it unlocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScalarLeaf {
+    /// Digits (most significant first) every matching value starts with
+    pub prefix: Vec<u32>,
+    /// Payout step of the long side, out of `precision`
+    pub step: u64,
+}
+
+/// Digit decomposition of the attested value and payout curve of a scalar market.
+///
+/// The long side's share of the pool grows linearly from 0 at `floor` to the
+/// whole pool at `cap`, rounded down to one of `precision` steps.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PayoutCurve {
+    /// Numeric base of the oracle's digit decomposition
+    pub base: u32,
+    /// Number of digits attested by the oracle
+    pub num_digits: u32,
+    /// At or below this value the short side takes the whole pool
+    pub floor: u64,
+    /// At or above this value the long side takes the whole pool
+    pub cap: u64,
+    /// Number of payout steps between floor and cap
+    pub precision: u64,
+}
+
+/// Represents a scalar prediction market using digit-decomposition oracle attestations.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScalarMarket {
+    /// Unique market identifier (Nostr id of the market parameters)
+    pub market_id: String,
+
+    /// Market question/description
+    pub question: String,
+
+    /// Oracle's Nostr public key (hex-encoded)
+    pub oracle_pubkey: String,
+
+    /// Deadline timestamp for oracle to attest the value (Unix timestamp)
+    pub settlement_timestamp: u64,
+
+    /// Bitcoin network (Signet for testing)
+    pub network: Network,
+
+    /// Digit decomposition and payout curve
+    pub curve: PayoutCurve,
+
+    /// Total amount in the market (in satoshis)
+    pub total_amount: u64,
+
+    /// Bets placed on the long side
+    pub long_bets: Vec<Bet>,
+
+    /// Bets placed on the short side
+    pub short_bets: Vec<Bet>,
+
+    /// Timeout for withdrawals after settlement (in case of oracle failure)
+    pub withdraw_timeout: u32,
+
+    /// Escape timelock, defaults to `settlement_timestamp + withdraw_timeout`
+    #[serde(default)]
+    pub escape_timelock: Option<EscapeTimelock>,
+
+    /// Fee configuration for the market
+    pub fees: MarketFees,
+}
+
+impl ScalarMarket {
+    /// Creates a new scalar market.
+    ///
+    /// # Arguments
+    /// * `question` - The market question (e.g., "BTC/USD price on 2025-01-01?")
+    /// * `oracle_pubkey` - Oracle's Nostr public key (hex or `npub`)
+    /// * `settlement_timestamp` - When oracle should attest the value (Unix timestamp)
+    /// * `curve` - Digit decomposition and payout curve, the attested value is
+    ///   bounded to `base^num_digits - 1`. At most [`MAX_SCALAR_PRECISION`] steps,
+    ///   compiled into at most [`MAX_SCALAR_LEAVES`] leaves.
+    pub fn new(
+        question: String,
+        oracle_pubkey: String,
+        settlement_timestamp: u64,
+        curve: PayoutCurve,
+    ) -> Result<Self> {
+        let PayoutCurve {
+            base,
+            num_digits,
+            floor,
+            cap,
+            precision,
+        } = curve;
+        if base < 2 {
+            return Err(MarketError::InvalidMarket(
+                "Base must be at least 2".to_string(),
+            ));
+        }
+        let max_value = (base as u64)
+            .checked_pow(num_digits)
+            .map(|v| v - 1)
+            .ok_or_else(|| {
+                MarketError::InvalidMarket(format!(
+                    "{num_digits} digits in base {base} do not fit in 64 bits"
+                ))
+            })?;
+        if num_digits == 0 || floor >= cap || cap > max_value {
+            return Err(MarketError::InvalidMarket(format!(
+                "Expected floor < cap <= {max_value}, got floor {floor} and cap {cap}"
+            )));
+        }
+        let max_precision = (cap - floor).min(MAX_SCALAR_PRECISION);
+        if precision == 0 || precision > max_precision {
+            return Err(MarketError::InvalidMarket(format!(
+                "Precision must be between 1 and {max_precision}, got {precision}"
+            )));
+        }
+        let oracle_pubkey = crate::nip19::parse_public_key(&oracle_pubkey)?;
+
+        let market_id =
+            scalar_market_event(&question, &oracle_pubkey, settlement_timestamp, &curve).id;
+
+        let market = Self {
+            market_id,
+            question,
+            oracle_pubkey,
+            settlement_timestamp,
+            network: Network::Signet,
+            curve,
+            total_amount: 0,
+            long_bets: Vec::new(),
+            short_bets: Vec::new(),
+            withdraw_timeout: 60 * 60 * 24, // 1 day
+            escape_timelock: None,
+            fees: MarketFees::default(),
+        };
+        // Fails if the curve needs too many leaves
+        market.leaves()?;
+        Ok(market)
+    }
+
+    /// Largest value the oracle can attest.
+    pub fn max_value(&self) -> u64 {
+        (self.curve.base as u64).pow(self.curve.num_digits) - 1
+    }
+
+    /// Place a bet on a side of the market.
+    ///
+    /// The amount must be above the dust threshold and the payout address valid
+    /// for the market network.
+    pub fn place_bet(
+        &mut self,
+        side: ScalarSide,
+        amount: u64,
+        payout_address: String,
+        txid: String,
+        vout: u32,
+    ) -> Result<()> {
+        if amount <= DUST_THRESHOLD {
+            return Err(MarketError::InvalidBet(format!(
+                "Bet amount {amount} is not above the dust threshold of {DUST_THRESHOLD} sats"
+            )));
+        }
+        if !crate::utils::validate_address(&payout_address, self.network) {
+            return Err(MarketError::InvalidAddress(format!(
+                "Payout address {payout_address} is not valid for the market network"
+            )));
+        }
+        let total_amount = self
+            .total_amount
+            .checked_add(amount)
+            .ok_or_else(|| MarketError::InvalidBet("Total amount overflows".to_string()))?;
+
+        let bet = Bet {
+            payout_address,
+            amount,
+            txid,
+            vout,
+        };
+        match side {
+            ScalarSide::Long => self.long_bets.push(bet),
+            ScalarSide::Short => self.short_bets.push(bet),
+        }
+        self.total_amount = total_amount;
+        Ok(())
+    }
+
+    /// Payout step of the long side for an attested value, out of `precision`.
+    pub fn payout_step(&self, value: u64) -> u64 {
+        if value <= self.curve.floor {
+            0
+        } else if value >= self.curve.cap {
+            self.curve.precision
+        } else {
+            let step = (value - self.curve.floor) as u128 * self.curve.precision as u128
+                / (self.curve.cap - self.curve.floor) as u128;
+            step as u64
+        }
+    }
+
+    /// Decompose a value into its `num_digits` digits, most significant first.
+    pub fn digits(&self, value: u64) -> Vec<u32> {
+        let base = self.curve.base as u64;
+        let mut digits = vec![0; self.curve.num_digits as usize];
+        let mut rest = value;
+        for digit in digits.iter_mut().rev() {
+            *digit = (rest % base) as u32;
+            rest /= base;
+        }
+        digits
+    }
+
+    /// Announcement event of the market, whose id is the market id.
+    pub fn announcement_event(&self) -> NostrEvent {
+        scalar_market_event(
+            &self.question,
+            &self.oracle_pubkey,
+            self.settlement_timestamp,
+            &self.curve,
+        )
+    }
+
+    /// Nostr id of the message the oracle signs for a digit prefix.
+    ///
+    /// The CSFS scripts commit to this id like categorical markets commit to
+    /// the outcome ids, see [`build_script_for_outcome`].
+    pub fn prefix_outcome_id(&self, prefix: &[u32]) -> String {
+        self.prefix_outcome_event(prefix).id
+    }
+
+    /// Template event the oracle signs for a digit prefix.
+    pub fn prefix_outcome_event(&self, prefix: &[u32]) -> NostrEvent {
+        let digits = prefix
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join("-");
+        NostrEvent::new(
+            &self.oracle_pubkey,
+            self.settlement_timestamp,
+            OUTCOME_EVENT_KIND,
+            vec![
+                vec!["d".to_string(), format!("{}:{digits}", self.market_id)],
+                vec!["market".to_string(), self.market_id.clone()],
+                vec!["digits".to_string(), digits.clone()],
+            ],
+            &digits,
+        )
+    }
+
+    /// Ids of all messages the oracle signs to attest `value`, one per digit prefix.
+    pub fn attestation_ids(&self, value: u64) -> Result<Vec<String>> {
+        if value > self.max_value() {
+            return Err(MarketError::Oracle(format!(
+                "Value {value} exceeds the attestable maximum {}",
+                self.max_value()
+            )));
+        }
+        let digits = self.digits(value);
+        Ok((1..=digits.len())
+            .map(|len| self.prefix_outcome_id(&digits[..len]))
+            .collect())
+    }
+
+    /// Compile the payout curve into digit-prefix leaves.
+    ///
+    /// Consecutive values paying out the same step form a range, and each range
+    /// is covered by the smallest set of aligned digit prefixes. The leaves
+    /// partition `0..=max_value`.
+    ///
+    /// Fails if the curve needs more than [`MAX_SCALAR_LEAVES`] leaves.
+    pub fn leaves(&self) -> Result<Vec<ScalarLeaf>> {
+        let range = (self.curve.cap - self.curve.floor) as u128;
+        let precision = self.curve.precision as u128;
+        // First value paying out `step`, for steps 1..precision
+        let step_start = |step: u128| self.curve.floor + (step * range).div_ceil(precision) as u64;
+
+        let mut leaves = Vec::new();
+        let mut start = 0;
+        for step in 0..=self.curve.precision {
+            let end = if step == self.curve.precision {
+                self.max_value()
+            } else {
+                step_start(step as u128 + 1) - 1
+            };
+            if start > end {
+                continue;
+            }
+            self.decompose_range(start, end, step, &mut leaves)?;
+            start = end + 1;
+        }
+        Ok(leaves)
+    }
+
+    /// Find the leaf covering an attested value.
+    pub fn leaf_for_value(&self, value: u64) -> Result<ScalarLeaf> {
+        let digits = self.digits(value);
+        self.leaves()?
+            .into_iter()
+            .find(|leaf| digits.starts_with(&leaf.prefix))
+            .ok_or_else(|| {
+                MarketError::Oracle(format!("No pool leaf covers the attested value {value}"))
+            })
+    }
+
+    /// Cover the inclusive range `start..=end` with aligned digit prefixes,
+    /// appended to `leaves` as leaves paying out `step`.
+    fn decompose_range(
+        &self,
+        mut start: u64,
+        end: u64,
+        step: u64,
+        leaves: &mut Vec<ScalarLeaf>,
+    ) -> Result<()> {
+        let base = self.curve.base as u128;
+        while start <= end {
+            if leaves.len() == MAX_SCALAR_LEAVES {
+                return Err(MarketError::InvalidMarket(format!(
+                    "The payout curve needs more than {MAX_SCALAR_LEAVES} pool leaves, \
+                     lower the precision or the base"
+                )));
+            }
+            // Grow the block while it stays aligned on `start` and within the range
+            let mut free_digits = 0;
+            let mut block = 1u128;
+            while free_digits < self.curve.num_digits
+                && (start as u128).is_multiple_of(block * base)
+                && start as u128 + block * base - 1 <= end as u128
+            {
+                block *= base;
+                free_digits += 1;
+            }
+            let digits = self.digits(start);
+            leaves.push(ScalarLeaf {
+                prefix: digits[..(self.curve.num_digits - free_digits) as usize].to_vec(),
+                step,
+            });
+            match start.checked_add(block as u64) {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the payout transaction for a long-side step.
+    ///
+    /// The pool (after fees) is split `step / precision` to the long side and
+    /// the rest to the short side, each side proportionally to its bets.
+    pub fn build_payout_transaction(
+        &self,
+        step: u64,
+        pool_utxo: OutPoint,
+    ) -> anyhow::Result<Transaction> {
+        if self.long_bets.is_empty() || self.short_bets.is_empty() {
+            return Err(anyhow::anyhow!("Scalar market needs bets on both sides"));
+        }
+        if step > self.curve.precision {
+            return Err(anyhow::anyhow!(
+                "Payout step {step} exceeds precision {}",
+                self.curve.precision
+            ));
+        }
+
+        let num_outputs = match step {
+            0 => self.short_bets.len(),
+            s if s == self.curve.precision => self.long_bets.len(),
+            _ => self.long_bets.len() + self.short_bets.len(),
+        };
+        let pool_after_fees = self.fees.pool_after_fees(self.total_amount, num_outputs);
+        let long_pool =
+            (pool_after_fees as u128 * step as u128 / self.curve.precision as u128) as u64;
+        let short_pool = pool_after_fees - long_pool;
+
+        let mut outputs = self.side_outputs(&self.long_bets, long_pool)?;
+        outputs.extend(self.side_outputs(&self.short_bets, short_pool)?);
+
+        // Add administrator fee output if configured
+        if let Some(admin_address) = &self.fees.administrator_address {
+            if self.fees.administrator_fee > 0 {
+                let address = Address::from_str(admin_address)
+                    .with_context(|| {
+                        format!("Failed to parse administrator address: {}", admin_address)
+                    })?
+                    .require_network(self.network)?;
+                outputs.push(TxOut {
+                    value: Amount::from_sat(self.fees.administrator_fee),
+                    script_pubkey: address.script_pubkey(),
+                });
+            }
+        }
+
+        build_pool_spend_transaction(
+            self.network,
+            &self.escape_timelock()?,
+            &WithdrawType::Payout,
+            pool_utxo,
+            outputs,
+        )
+    }
+
+    /// Timelock enforced by the escape leaf.
+    pub fn escape_timelock(&self) -> anyhow::Result<EscapeTimelock> {
+        match self.escape_timelock {
+            Some(timelock) => Ok(timelock),
+            None => {
+                EscapeTimelock::after_settlement(self.settlement_timestamp, self.withdraw_timeout)
+            }
+        }
+    }
+
+    /// Build the escape transaction returning every bet after the timeout.
+    pub fn build_escape_transaction(&self, pool_utxo: OutPoint) -> anyhow::Result<Transaction> {
+        let all_bets: Vec<Bet> = self
+            .long_bets
+            .iter()
+            .chain(self.short_bets.iter())
+            .cloned()
+            .collect();
+        build_pool_spend_transaction(
+            self.network,
+            &self.escape_timelock()?,
+            &WithdrawType::Escape,
+            pool_utxo,
+            generate_escape_outputs(&all_bets, self.network, &self.fees)?,
+        )
+    }
+
+    /// Split `side_pool` between the bets of one side, dropping dust outputs.
+    fn side_outputs(&self, bets: &[Bet], side_pool: u64) -> anyhow::Result<Vec<TxOut>> {
+        let side_total = bets.iter().map(|bet| bet.amount).sum::<u64>();
+        let mut outputs = Vec::with_capacity(bets.len());
+        if side_total == 0 {
+            return Ok(outputs);
+        }
+        for bet in bets {
+            let address = Address::from_str(&bet.payout_address)
+                .with_context(|| format!("Failed to parse payout address: {}", bet.payout_address))?
+                .require_network(self.network)?;
+            let amount = (bet.amount as u128 * side_pool as u128 / side_total as u128) as u64;
+            if amount > DUST_THRESHOLD {
+                outputs.push(TxOut {
+                    value: Amount::from_sat(amount),
+                    script_pubkey: address.script_pubkey(),
+                });
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Build the scripts of every pool leaf: one CSFS + CTV leaf per digit prefix, then escape.
+    pub fn pool_scripts(&self) -> anyhow::Result<Vec<ScriptBuf>> {
+        // Every prefix of a step commits to the same payout transaction
+        let ctv_hashes = (0..=self.curve.precision)
+            .map(|step| {
+                let tx = self.build_payout_transaction(step, OutPoint::null())?;
+                Ok(calculate_ctv_hash_from_transaction(&tx))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut scripts = self
+            .leaves()?
+            .iter()
+            .map(|leaf| {
+                build_script_for_outcome(
+                    ctv_hashes[leaf.step as usize],
+                    &self.oracle_pubkey,
+                    &self.prefix_outcome_id(&leaf.prefix),
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let escape_tx = self.build_escape_transaction(OutPoint::null())?;
+        scripts.push(build_script_for_escape(
+            calculate_ctv_hash_from_transaction(&escape_tx),
+            &self.escape_timelock()?,
+        ));
+        Ok(scripts)
+    }
+
+    /// Taproot spend info of the pool, its payout scripts indexed like [`Self::leaves`].
+    pub fn pool_spend_info(&self) -> anyhow::Result<PoolSpendInfo> {
+        let mut payout_scripts = self.pool_scripts()?;
+        let escape_script = payout_scripts
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Scalar pool has no escape leaf"))?;
+        let mut scripts = payout_scripts.clone();
+        scripts.push(escape_script.clone());
+        Ok(PoolSpendInfo {
+            spend_info: build_balanced_spend_info(scripts)?,
+            payout_scripts,
+            escape_script,
+            network: self.network,
+        })
+    }
+
+    /// Generate the pool address of the scalar market.
+    pub fn pool_address(&self) -> anyhow::Result<Address> {
+        Ok(self.pool_spend_info()?.address())
+    }
+
+    /// Attach the witness of the payout leaf covering the attested `value`.
+    ///
+    /// `oracle_signatures` are the oracle's CSFS signatures of every digit prefix
+    /// of `value`, ordered like [`Self::attestation_ids`]; the one of the leaf
+    /// prefix goes in the witness. The transaction must match the payout
+    /// template committed in that leaf.
+    pub fn sign_payout_transaction(
+        &self,
+        mut tx: Transaction,
+        value: u64,
+        oracle_signatures: &[Vec<u8>],
+    ) -> anyhow::Result<Transaction> {
+        let leaves = self.leaves()?;
+        let digits = self.digits(value);
+        let index = leaves
+            .iter()
+            .position(|leaf| digits.starts_with(&leaf.prefix))
+            .ok_or_else(|| anyhow::anyhow!("No pool leaf covers the attested value {value}"))?;
+        let prefix = &leaves[index].prefix;
+        let oracle_signature = oracle_signatures.get(prefix.len() - 1).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Expected the oracle signatures of the {} digit prefixes of {value}",
+                digits.len()
+            )
+        })?;
+
+        let (script, control_block) = self.pool_spend_info()?.payout_leaf(index)?;
+        let expected = build_script_for_outcome(
+            calculate_ctv_hash_from_transaction(&tx),
+            &self.oracle_pubkey,
+            &self.prefix_outcome_id(prefix),
+        )?;
+        if script != expected {
+            return Err(anyhow::anyhow!(
+                "Transaction does not match the payout template committed in the pool"
+            ));
+        }
+
+        let input = tx
+            .input
+            .first_mut()
+            .ok_or_else(|| anyhow::anyhow!("Payout transaction has no input"))?;
+        input.witness = create_outcome_witness(oracle_signature, control_block, script)?;
+        Ok(tx)
+    }
+
+    /// Attach the witness of the escape leaf to the escape transaction.
+    pub fn sign_escape_transaction(&self, mut tx: Transaction) -> anyhow::Result<Transaction> {
+        let (script, control_block) = self.pool_spend_info()?.escape_leaf()?;
+        let expected = build_script_for_escape(
+            calculate_ctv_hash_from_transaction(&tx),
+            &self.escape_timelock()?,
+        );
+        if script != expected {
+            return Err(anyhow::anyhow!(
+                "Transaction does not match the escape template committed in the pool"
+            ));
+        }
+
+        let input = tx
+            .input
+            .first_mut()
+            .ok_or_else(|| anyhow::anyhow!("Escape transaction has no input"))?;
+        input.witness = create_escape_witness(control_block, script)?;
+        Ok(tx)
+    }
+}
+
+/// Announcement event of a scalar market, tagged with its payout curve.
+fn scalar_market_event(
+    question: &str,
+    oracle_pubkey: &str,
+    settlement_timestamp: u64,
+    curve: &PayoutCurve,
+) -> NostrEvent {
+    let scalar = [
+        curve.base.to_string(),
+        curve.num_digits.to_string(),
+        curve.floor.to_string(),
+        curve.cap.to_string(),
+        curve.precision.to_string(),
+    ];
+    let identifier = format!("{settlement_timestamp}\n{question}\n{}", scalar.join("\n"));
+    let mut scalar_tag = vec!["scalar".to_string()];
+    scalar_tag.extend(scalar);
+    NostrEvent::new(
+        oracle_pubkey,
+        settlement_timestamp,
+        MARKET_EVENT_KIND,
+        vec![
+            vec!["d".to_string(), crate::sha256_hash(&identifier)],
+            scalar_tag,
+        ],
+        question,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{constants::*, create_valid_regtest_address};
+
+    fn create_scalar_market(
+        base: u32,
+        num_digits: u32,
+        floor: u64,
+        cap: u64,
+        precision: u64,
+    ) -> ScalarMarket {
+        let mut market = ScalarMarket::new(
+            "BTC/USD price in thousands?".to_string(),
+            TEST_ORACLE_PUBKEY.to_string(),
+            TEST_SETTLEMENT_TIMESTAMP,
+            PayoutCurve {
+                base,
+                num_digits,
+                floor,
+                cap,
+                precision,
+            },
+        )
+        .unwrap();
+        market.network = Network::Regtest;
+        market
+    }
+
+    fn with_bets(mut market: ScalarMarket) -> ScalarMarket {
+        market
+            .place_bet(
+                ScalarSide::Long,
+                100000,
+                create_valid_regtest_address(1),
+                TEST_TXID.to_string(),
+                0,
+            )
+            .unwrap();
+        market
+            .place_bet(
+                ScalarSide::Long,
+                50000,
+                create_valid_regtest_address(2),
+                TEST_TXID.to_string(),
+                1,
+            )
+            .unwrap();
+        market
+            .place_bet(
+                ScalarSide::Short,
+                150000,
+                create_valid_regtest_address(3),
+                TEST_TXID.to_string(),
+                2,
+            )
+            .unwrap();
+        market
+    }
+
+    #[test]
+    fn test_new_scalar_market_validation() {
+        let new = |base, digits, floor, cap, precision| {
+            ScalarMarket::new(
+                "Question?".to_string(),
+                TEST_ORACLE_PUBKEY.to_string(),
+                TEST_SETTLEMENT_TIMESTAMP,
+                PayoutCurve {
+                    base,
+                    num_digits: digits,
+                    floor,
+                    cap,
+                    precision,
+                },
+            )
+        };
+        assert!(new(10, 3, 100, 200, 10).is_ok());
+        assert!(new(1, 3, 100, 200, 10).is_err());
+        assert!(new(10, 3, 200, 100, 10).is_err());
+        assert!(new(10, 3, 100, 1000, 10).is_err());
+        assert!(new(10, 3, 100, 200, 0).is_err());
+        assert!(new(10, 3, 100, 200, 101).is_err());
+        assert!(new(10, 30, 100, 200, 10).is_err());
+
+        // Precision and leaf count are capped
+        assert!(new(10, 6, 0, 100_000, MAX_SCALAR_PRECISION).is_ok());
+        assert!(new(10, 6, 0, 100_000, MAX_SCALAR_PRECISION + 1).is_err());
+        let error = new(100_000, 2, 1, 99_999, 1).unwrap_err();
+        assert!(error.to_string().contains("pool leaves"), "{error}");
+    }
+
+    #[test]
+    fn test_place_bet_validation() {
+        let mut market = create_scalar_market(10, 3, 100, 200, 10);
+        let bet = |market: &mut ScalarMarket, amount, address: String| {
+            market.place_bet(ScalarSide::Long, amount, address, TEST_TXID.to_string(), 0)
+        };
+        assert!(bet(&mut market, DUST_THRESHOLD, create_valid_regtest_address(1)).is_err());
+        assert!(bet(&mut market, 10000, "not an address".to_string()).is_err());
+        assert!(bet(
+            &mut market,
+            10000,
+            crate::test_utils::create_valid_address_for_network(1, Network::Bitcoin)
+        )
+        .is_err());
+        bet(&mut market, u64::MAX - 1, create_valid_regtest_address(1)).unwrap();
+        assert!(bet(&mut market, 10000, create_valid_regtest_address(2)).is_err());
+        assert_eq!(market.long_bets.len(), 1);
+        assert_eq!(market.total_amount, u64::MAX - 1);
+    }
+
+    #[test]
+    fn test_payout_step() {
+        let market = create_scalar_market(10, 3, 100, 200, 10);
+        assert_eq!(market.payout_step(0), 0);
+        assert_eq!(market.payout_step(100), 0);
+        assert_eq!(market.payout_step(109), 0);
+        assert_eq!(market.payout_step(110), 1);
+        assert_eq!(market.payout_step(155), 5);
+        assert_eq!(market.payout_step(199), 9);
+        assert_eq!(market.payout_step(200), 10);
+        assert_eq!(market.payout_step(999), 10);
+    }
+
+    #[test]
+    fn test_leaves_partition_the_value_range() {
+        for (base, digits, floor, cap, precision) in [
+            (10, 3, 100, 200, 10),
+            (2, 10, 37, 901, 7),
+            (16, 3, 0, 4095, 3),
+            (10, 2, 12, 13, 1),
+        ] {
+            let market = create_scalar_market(base, digits, floor, cap, precision);
+            let leaves = market.leaves().unwrap();
+            for value in 0..=market.max_value() {
+                let digits = market.digits(value);
+                let matching: Vec<_> = leaves
+                    .iter()
+                    .filter(|leaf| digits.starts_with(&leaf.prefix))
+                    .collect();
+                assert_eq!(
+                    matching.len(),
+                    1,
+                    "Value {value} must match exactly one leaf"
+                );
+                assert_eq!(matching[0].step, market.payout_step(value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_leaves_use_prefixes() {
+        // 100..=109 pays step 0 together with 0..=99, so 0xx and 10x are single leaves
+        let market = create_scalar_market(10, 3, 100, 200, 10);
+        let leaves = market.leaves().unwrap();
+        assert!(leaves.contains(&ScalarLeaf {
+            prefix: vec![0],
+            step: 0
+        }));
+        assert!(leaves.contains(&ScalarLeaf {
+            prefix: vec![1, 0],
+            step: 0
+        }));
+        assert!(leaves.contains(&ScalarLeaf {
+            prefix: vec![1, 5],
+            step: 5
+        }));
+        assert!(leaves.contains(&ScalarLeaf {
+            prefix: vec![9],
+            step: 10
+        }));
+        assert_eq!(leaves.len(), 19);
+    }
+
+    #[test]
+    fn test_attestation_ids_match_leaf() {
+        let market = create_scalar_market(10, 3, 100, 200, 10);
+        let ids = market.attestation_ids(157).unwrap();
+        assert_eq!(ids.len(), 3);
+        let leaf = market.leaf_for_value(157).unwrap();
+        assert_eq!(leaf.prefix, vec![1, 5]);
+        assert_eq!(ids[1], market.prefix_outcome_id(&leaf.prefix));
+        assert!(market.attestation_ids(1000).is_err());
+    }
+
+    #[test]
+    fn test_scalar_events() {
+        let market = create_scalar_market(10, 3, 100, 200, 10);
+        let announcement = market.announcement_event();
+        assert_eq!(announcement.kind, MARKET_EVENT_KIND);
+        assert_eq!(announcement.id, market.market_id);
+        assert_eq!(announcement.tag("scalar").unwrap().len(), 5);
+
+        let event = market.prefix_outcome_event(&[1, 5]);
+        assert_eq!(event.kind, OUTCOME_EVENT_KIND);
+        assert!(event.has_valid_id());
+        assert_eq!(event.content, "1-5");
+        assert_eq!(event.tag("market"), Some(&[market.market_id.clone()][..]));
+        assert_ne!(
+            event.identifier(),
+            market.prefix_outcome_event(&[1]).identifier()
+        );
+    }
+
+    #[test]
+    fn test_scalar_payout_split() {
+        let market = with_bets(create_scalar_market(10, 3, 100, 200, 10));
+        let tx = market
+            .build_payout_transaction(5, OutPoint::null())
+            .unwrap();
+        let pool_after_fees = market.fees.pool_after_fees(300000, 3);
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(tx.output[0].value.to_sat(), pool_after_fees / 2 * 2 / 3);
+        assert_eq!(tx.output[1].value.to_sat(), pool_after_fees / 2 / 3);
+        assert_eq!(
+            tx.output[2].value.to_sat(),
+            pool_after_fees - pool_after_fees / 2
+        );
+
+        // At the cap the short side gets nothing
+        let tx = market
+            .build_payout_transaction(10, OutPoint::null())
+            .unwrap();
+        assert_eq!(tx.output.len(), 2);
+        assert!(market
+            .build_payout_transaction(11, OutPoint::null())
+            .is_err());
+    }
+
+    #[test]
+    fn test_scalar_pool_address() {
+        let market = with_bets(create_scalar_market(10, 3, 100, 200, 10));
+        let scripts = market.pool_scripts().unwrap();
+        assert_eq!(scripts.len(), market.leaves().unwrap().len() + 1);
+        let address = market.pool_address().unwrap();
+        assert!(address.to_string().starts_with("bcrt1p"));
+
+        // Without bets on both sides the payout templates cannot be built
+        let empty = create_scalar_market(10, 3, 100, 200, 10);
+        assert!(empty.pool_address().is_err());
+    }
+}
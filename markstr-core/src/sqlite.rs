@@ -0,0 +1,327 @@
+//! # SQLite Market Store
+//!
+//! [`MarketStore`] backed by a SQLite database (feature `sqlite`).
+//!
+//! Bets are kept in their own table, one row per bet, so they can be queried
+//! without decoding the markets. The rest of a market, its deposit session and
+//! its attestation event are stored as JSON. The schema version is the database
+//! `user_version`; [`SqliteStore::open`] applies the missing migrations, each in
+//! its own transaction.
+
+use crate::{
+    attestation::Attestation,
+    coordinator::DepositCoordinator,
+    market::{Bet, PredictionMarket},
+    store::{check_market_id, MarketStore, StoreError, STORE_VERSION},
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+/// Schema migrations, the `i`-th one upgrades version `i` to `i + 1`
+const MIGRATIONS: &[&str] = &[
+    // 1: markets, bets, deposit sessions and attestations
+    "CREATE TABLE markets (
+        id TEXT PRIMARY KEY,
+        question TEXT NOT NULL,
+        state TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE bets (
+        market_id TEXT NOT NULL REFERENCES markets(id) ON DELETE CASCADE,
+        outcome INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        payout_address TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        txid TEXT NOT NULL,
+        vout INTEGER NOT NULL,
+        PRIMARY KEY (market_id, outcome, position)
+    );
+    CREATE TABLE deposit_sessions (
+        market_id TEXT PRIMARY KEY REFERENCES markets(id) ON DELETE CASCADE,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE attestations (
+        market_id TEXT PRIMARY KEY REFERENCES markets(id) ON DELETE CASCADE,
+        outcome_index INTEGER NOT NULL,
+        outcome_id TEXT NOT NULL,
+        csfs_signature BLOB NOT NULL,
+        event TEXT NOT NULL
+    );",
+];
+
+/// Market store in a SQLite database
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Connection,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating or migrating it as needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens a store in memory, lost when dropped
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, StoreError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let mut store = Self { connection };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Schema version of the database, 0 for a new database
+    pub fn version(&self) -> Result<u32, StoreError> {
+        Ok(self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    fn migrate(&mut self) -> Result<(), StoreError> {
+        let version = self.version()?;
+        if version > STORE_VERSION {
+            return Err(StoreError::UnsupportedVersion {
+                found: version,
+                supported: STORE_VERSION,
+            });
+        }
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = self.connection.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", from + 1)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    fn ensure_market(&self, market_id: &str) -> Result<(), StoreError> {
+        let exists = self
+            .connection
+            .query_row("SELECT 1 FROM markets WHERE id = ?1", [market_id], |_| {
+                Ok(())
+            })
+            .optional()?;
+        exists.ok_or_else(|| StoreError::NotFound(market_id.to_string()))
+    }
+
+    fn load_bets(&self, market: &mut PredictionMarket) -> Result<(), StoreError> {
+        let mut statement = self.connection.prepare(
+            "SELECT outcome, payout_address, amount, txid, vout FROM bets
+             WHERE market_id = ?1 ORDER BY outcome, position",
+        )?;
+        let rows = statement.query_map([&market.market_id], |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                Bet {
+                    payout_address: row.get(1)?,
+                    amount: row.get(2)?,
+                    txid: row.get(3)?,
+                    vout: row.get(4)?,
+                },
+            ))
+        })?;
+        market.bets = vec![Vec::new(); market.outcomes.len()];
+        for row in rows {
+            let (outcome, bet) = row?;
+            market
+                .bets
+                .get_mut(outcome)
+                .ok_or_else(|| {
+                    StoreError::Corrupt(format!(
+                        "{}: bet on unknown outcome {outcome}",
+                        market.market_id
+                    ))
+                })?
+                .push(bet);
+        }
+        Ok(())
+    }
+}
+
+impl MarketStore for SqliteStore {
+    fn save_market(&mut self, market: &PredictionMarket) -> Result<(), StoreError> {
+        check_market_id(&market.market_id)?;
+        let mut data = market.clone();
+        data.bets = Vec::new();
+        let data = serde_json::to_string(&data)?;
+
+        let tx = self.connection.transaction()?;
+        tx.execute(
+            "INSERT INTO markets (id, question, state, data) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (id) DO UPDATE SET
+                question = excluded.question, state = excluded.state, data = excluded.data",
+            params![
+                market.market_id,
+                market.question,
+                market.state.to_string(),
+                data
+            ],
+        )?;
+        tx.execute("DELETE FROM bets WHERE market_id = ?1", [&market.market_id])?;
+        for (outcome, bets) in market.bets.iter().enumerate() {
+            for (position, bet) in bets.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO bets (market_id, outcome, position, payout_address, amount, txid, vout)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        market.market_id,
+                        outcome,
+                        position,
+                        bet.payout_address,
+                        bet.amount,
+                        bet.txid,
+                        bet.vout
+                    ],
+                )?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_market(&self, market_id: &str) -> Result<PredictionMarket, StoreError> {
+        let data: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT data FROM markets WHERE id = ?1",
+                [market_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let data = data.ok_or_else(|| StoreError::NotFound(market_id.to_string()))?;
+        let mut market: PredictionMarket = serde_json::from_str(&data)?;
+        self.load_bets(&mut market)?;
+        Ok(market)
+    }
+
+    fn list_markets(&self) -> Result<Vec<PredictionMarket>, StoreError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id FROM markets ORDER BY id")?;
+        let ids = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ids.iter().map(|id| self.load_market(id)).collect()
+    }
+
+    fn delete_market(&mut self, market_id: &str) -> Result<(), StoreError> {
+        let deleted = self
+            .connection
+            .execute("DELETE FROM markets WHERE id = ?1", [market_id])?;
+        if deleted == 0 {
+            return Err(StoreError::NotFound(market_id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn save_session(&mut self, session: &DepositCoordinator) -> Result<(), StoreError> {
+        let market_id = &session.market().market_id;
+        self.ensure_market(market_id)?;
+        self.connection.execute(
+            "INSERT OR REPLACE INTO deposit_sessions (market_id, data) VALUES (?1, ?2)",
+            params![market_id, serde_json::to_string(session)?],
+        )?;
+        Ok(())
+    }
+
+    fn load_session(&self, market_id: &str) -> Result<Option<DepositCoordinator>, StoreError> {
+        let data: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT data FROM deposit_sessions WHERE market_id = ?1",
+                [market_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+    }
+
+    fn save_attestation(
+        &mut self,
+        market_id: &str,
+        attestation: &Attestation,
+    ) -> Result<(), StoreError> {
+        self.ensure_market(market_id)?;
+        self.connection.execute(
+            "INSERT OR REPLACE INTO attestations
+                (market_id, outcome_index, outcome_id, csfs_signature, event)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                market_id,
+                attestation.outcome_index,
+                attestation.outcome_id,
+                attestation.csfs_signature,
+                serde_json::to_string(&attestation.event)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_attestation(&self, market_id: &str) -> Result<Option<Attestation>, StoreError> {
+        let row = self
+            .connection
+            .query_row(
+                "SELECT outcome_index, outcome_id, csfs_signature, event FROM attestations
+                 WHERE market_id = ?1",
+                [market_id],
+                |row| {
+                    Ok((
+                        row.get::<_, usize>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Vec<u8>>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()?;
+        row.map(|(outcome_index, outcome_id, csfs_signature, event)| {
+            Ok(Attestation {
+                outcome_index,
+                outcome_id,
+                csfs_signature,
+                event: serde_json::from_str(&event)?,
+            })
+        })
+        .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_sqlite_store() {
+        let mut store = SqliteStore::open_in_memory().unwrap();
+        check_market_store(&mut store);
+    }
+
+    #[test]
+    fn test_sqlite_migrations() {
+        let path = std::env::temp_dir().join(format!("markstr-{}.db", uuid::Uuid::new_v4()));
+        let mut store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.version().unwrap(), STORE_VERSION);
+        let market = create_test_market();
+        store.save_market(&market).unwrap();
+        drop(store);
+
+        // Reopening runs no migration and keeps the saved markets
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.load_market(&market.market_id).unwrap(), market);
+
+        store
+            .connection
+            .pragma_update(None, "user_version", STORE_VERSION + 1)
+            .unwrap();
+        drop(store);
+        assert!(matches!(
+            SqliteStore::open(&path),
+            Err(StoreError::UnsupportedVersion { found: 2, .. })
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
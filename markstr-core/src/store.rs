@@ -0,0 +1,390 @@
+//! # Market Store
+//!
+//! Persistence of markets and of the state built around them, so that every tool
+//! works on the same saved markets:
+//!
+//! - markets, with their registered bets
+//! - deposit sessions, the [`DepositCoordinator`] of a market's deposit round
+//! - settlement attestations, the oracle's [`Attestation`] of the winning outcome
+//!
+//! [`MarketStore`] is implemented by [`JsonStore`], a directory of JSON files, and
+//! by `SqliteStore` (feature `sqlite`). Both record the version of their layout
+//! and migrate older stores when opened; a store written by a newer release is
+//! rejected rather than misread.
+//!
+//! Sessions and attestations belong to a saved market and are deleted with it.
+
+use crate::{
+    attestation::Attestation,
+    coordinator::DepositCoordinator,
+    error::MarketError,
+    market::{Bet, PredictionMarket},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Version of the store layout produced by this library
+pub const STORE_VERSION: u32 = 1;
+
+/// Errors of the market stores
+#[derive(Error, Debug)]
+pub enum StoreError {
+    /// No market is saved under this id
+    #[error("Market {0} not found")]
+    NotFound(String),
+
+    /// The market id cannot be used as a key
+    #[error("Invalid market id: {0}")]
+    InvalidId(String),
+
+    /// The stored data is inconsistent
+    #[error("Corrupt store: {0}")]
+    Corrupt(String),
+
+    /// The store was written by a newer release
+    #[error("Store version {found} is newer than the supported version {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    /// Filesystem errors
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// JSON encoding or decoding failed
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// SQLite errors
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// The stored market rejected an update
+    #[error(transparent)]
+    Market(#[from] MarketError),
+}
+
+impl StoreError {
+    /// Stable machine-readable code of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "market_not_found",
+            Self::InvalidId(_) => "invalid_market_id",
+            Self::Corrupt(_) => "corrupt_store",
+            Self::UnsupportedVersion { .. } => "unsupported_store_version",
+            Self::Io(_) => "io",
+            Self::Json(_) => "json",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(_) => "sqlite",
+            Self::Market(error) => error.code(),
+        }
+    }
+}
+
+/// Storage of markets, deposit sessions and settlement attestations
+pub trait MarketStore {
+    /// Saves a market, replacing the saved market with the same id
+    fn save_market(&mut self, market: &PredictionMarket) -> Result<(), StoreError>;
+
+    /// Loads the market saved under `market_id`
+    fn load_market(&self, market_id: &str) -> Result<PredictionMarket, StoreError>;
+
+    /// Lists the saved markets, ordered by id
+    fn list_markets(&self) -> Result<Vec<PredictionMarket>, StoreError>;
+
+    /// Deletes a market with its deposit session and attestation
+    fn delete_market(&mut self, market_id: &str) -> Result<(), StoreError>;
+
+    /// Saves the deposit session of a saved market
+    fn save_session(&mut self, session: &DepositCoordinator) -> Result<(), StoreError>;
+
+    /// Loads the deposit session of a market, if any
+    fn load_session(&self, market_id: &str) -> Result<Option<DepositCoordinator>, StoreError>;
+
+    /// Saves the settlement attestation of a saved market
+    fn save_attestation(
+        &mut self,
+        market_id: &str,
+        attestation: &Attestation,
+    ) -> Result<(), StoreError>;
+
+    /// Loads the settlement attestation of a market, if any
+    fn load_attestation(&self, market_id: &str) -> Result<Option<Attestation>, StoreError>;
+
+    /// Applies `update` to a saved market and saves the result.
+    ///
+    /// Nothing is saved if `update` fails.
+    fn update_market(
+        &mut self,
+        market_id: &str,
+        update: &mut dyn FnMut(&mut PredictionMarket) -> crate::Result<()>,
+    ) -> Result<PredictionMarket, StoreError> {
+        let mut market = self.load_market(market_id)?;
+        update(&mut market)?;
+        self.save_market(&market)?;
+        Ok(market)
+    }
+
+    /// Registers a bet on a saved market (see [`PredictionMarket::place_bet`])
+    fn add_bet(
+        &mut self,
+        market_id: &str,
+        outcome: usize,
+        bet: &Bet,
+    ) -> Result<PredictionMarket, StoreError> {
+        self.update_market(market_id, &mut |market| {
+            market.place_bet(
+                outcome,
+                bet.amount,
+                bet.payout_address.clone(),
+                bet.txid.clone(),
+                bet.vout,
+            )
+        })
+    }
+
+    /// Lists the bets of a saved market with their outcome, in registration order
+    fn list_bets(&self, market_id: &str) -> Result<Vec<(usize, Bet)>, StoreError> {
+        let market = self.load_market(market_id)?;
+        Ok(market
+            .bets
+            .into_iter()
+            .enumerate()
+            .flat_map(|(outcome, bets)| bets.into_iter().map(move |bet| (outcome, bet)))
+            .collect())
+    }
+}
+
+/// Checks that a market id can be used as a file name or key
+pub(crate) fn check_market_id(market_id: &str) -> Result<(), StoreError> {
+    let valid = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'-';
+    if market_id.is_empty() || !market_id.bytes().all(valid) {
+        return Err(StoreError::InvalidId(market_id.to_string()));
+    }
+    Ok(())
+}
+
+const MARKETS_DIR: &str = "markets";
+const SESSIONS_DIR: &str = "sessions";
+const ATTESTATIONS_DIR: &str = "attestations";
+const VERSION_FILE: &str = "VERSION";
+
+/// Migrations of the directory layout, the `i`-th one upgrades version `i` to `i + 1`
+const JSON_MIGRATIONS: &[fn(&Path) -> std::io::Result<()>] = &[
+    // 1: one directory per record type
+    |root| {
+        for dir in [MARKETS_DIR, SESSIONS_DIR, ATTESTATIONS_DIR] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        Ok(())
+    },
+];
+
+/// Market store in a directory of JSON files
+///
+/// ```text
+/// <root>/VERSION
+/// <root>/markets/<market id>.json
+/// <root>/sessions/<market id>.json
+/// <root>/attestations/<market id>.json
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsonStore {
+    root: PathBuf,
+}
+
+impl JsonStore {
+    /// Opens the store in `root`, creating or migrating it as needed
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        let store = Self { root };
+
+        let version = store.version()?;
+        if version > STORE_VERSION {
+            return Err(StoreError::UnsupportedVersion {
+                found: version,
+                supported: STORE_VERSION,
+            });
+        }
+        for (from, migrate) in JSON_MIGRATIONS.iter().enumerate().skip(version as usize) {
+            migrate(&store.root)?;
+            write_atomic(
+                &store.root.join(VERSION_FILE),
+                (from + 1).to_string().as_bytes(),
+            )?;
+        }
+        Ok(store)
+    }
+
+    /// Directory of the store
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Layout version of the store, 0 for a new store
+    pub fn version(&self) -> Result<u32, StoreError> {
+        match fs::read_to_string(self.root.join(VERSION_FILE)) {
+            Ok(version) => version
+                .trim()
+                .parse()
+                .map_err(|_| StoreError::Corrupt(format!("invalid version {:?}", version.trim()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn path(&self, dir: &str, market_id: &str) -> Result<PathBuf, StoreError> {
+        check_market_id(market_id)?;
+        Ok(self.root.join(dir).join(format!("{market_id}.json")))
+    }
+
+    fn write<T: Serialize>(&self, dir: &str, market_id: &str, value: &T) -> Result<(), StoreError> {
+        let json = serde_json::to_vec_pretty(value)?;
+        Ok(write_atomic(&self.path(dir, market_id)?, &json)?)
+    }
+
+    fn read<T: DeserializeOwned>(
+        &self,
+        dir: &str,
+        market_id: &str,
+    ) -> Result<Option<T>, StoreError> {
+        match fs::read(self.path(dir, market_id)?) {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn remove(&self, dir: &str, market_id: &str) -> Result<(), StoreError> {
+        match fs::remove_file(self.path(dir, market_id)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn ensure_market(&self, market_id: &str) -> Result<(), StoreError> {
+        if !self.path(MARKETS_DIR, market_id)?.exists() {
+            return Err(StoreError::NotFound(market_id.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Writes a file through a temporary file, so readers never see a partial write
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
+
+impl MarketStore for JsonStore {
+    fn save_market(&mut self, market: &PredictionMarket) -> Result<(), StoreError> {
+        self.write(MARKETS_DIR, &market.market_id, market)
+    }
+
+    fn load_market(&self, market_id: &str) -> Result<PredictionMarket, StoreError> {
+        self.read(MARKETS_DIR, market_id)?
+            .ok_or_else(|| StoreError::NotFound(market_id.to_string()))
+    }
+
+    fn list_markets(&self) -> Result<Vec<PredictionMarket>, StoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(self.root.join(MARKETS_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        ids.iter().map(|id| self.load_market(id)).collect()
+    }
+
+    fn delete_market(&mut self, market_id: &str) -> Result<(), StoreError> {
+        self.ensure_market(market_id)?;
+        self.remove(SESSIONS_DIR, market_id)?;
+        self.remove(ATTESTATIONS_DIR, market_id)?;
+        self.remove(MARKETS_DIR, market_id)
+    }
+
+    fn save_session(&mut self, session: &DepositCoordinator) -> Result<(), StoreError> {
+        let market_id = &session.market().market_id;
+        self.ensure_market(market_id)?;
+        self.write(SESSIONS_DIR, market_id, session)
+    }
+
+    fn load_session(&self, market_id: &str) -> Result<Option<DepositCoordinator>, StoreError> {
+        self.read(SESSIONS_DIR, market_id)
+    }
+
+    fn save_attestation(
+        &mut self,
+        market_id: &str,
+        attestation: &Attestation,
+    ) -> Result<(), StoreError> {
+        self.ensure_market(market_id)?;
+        self.write(ATTESTATIONS_DIR, market_id, attestation)
+    }
+
+    fn load_attestation(&self, market_id: &str) -> Result<Option<Attestation>, StoreError> {
+        self.read(ATTESTATIONS_DIR, market_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn temp_store() -> JsonStore {
+        let root = std::env::temp_dir().join(format!("markstr-store-{}", uuid::Uuid::new_v4()));
+        JsonStore::open(root).unwrap()
+    }
+
+    #[test]
+    fn test_json_store() {
+        let mut store = temp_store();
+        check_market_store(&mut store);
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn test_json_store_versions() {
+        let store = temp_store();
+        assert_eq!(store.version().unwrap(), STORE_VERSION);
+        assert!(store.root().join(MARKETS_DIR).is_dir());
+
+        // Reopening keeps the saved markets
+        let mut store = JsonStore::open(store.root()).unwrap();
+        let market = create_test_market();
+        store.save_market(&market).unwrap();
+        let store = JsonStore::open(store.root()).unwrap();
+        assert_eq!(store.load_market(&market.market_id).unwrap(), market);
+
+        fs::write(store.root().join(VERSION_FILE), "2").unwrap();
+        assert!(matches!(
+            JsonStore::open(store.root()),
+            Err(StoreError::UnsupportedVersion {
+                found: 2,
+                supported: STORE_VERSION
+            })
+        ));
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_path_ids() {
+        let store = temp_store();
+        let error = store.load_market("../VERSION").unwrap_err();
+        assert!(matches!(error, StoreError::InvalidId(_)));
+        assert_eq!(error.code(), "invalid_market_id");
+        let error = StoreError::Market(MarketError::InvalidBet("closed".to_string()));
+        assert_eq!(error.code(), "invalid_bet");
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+}
@@ -3,9 +3,20 @@
 //! This module provides shared functionality for testing across all modules,
 //! including market creation, address generation, and other common test setup.
 
-use crate::market::{Bet, MarketFees, PredictionMarket, PredictionOutcome};
+use crate::attestation::Attestation;
+use crate::coordinator::DepositCoordinator;
+use crate::deposit::{
+    create_partial_pool_tx, keypair_from_private_key, DepositInput, PartialDepositTx,
+};
+use crate::lifecycle::MarketState;
+use crate::market::{outcome_label, Bet, MarketFees, PredictionMarket, PredictionOutcome};
+use crate::store::{MarketStore, StoreError};
+use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::{Secp256k1, SecretKey};
-use bitcoin::{Address, CompressedPublicKey, Network, PrivateKey};
+use bitcoin::{
+    Address, Amount, CompressedPublicKey, Network, OutPoint, PrivateKey, ScriptBuf, TxOut, Txid,
+};
+use std::collections::HashMap;
 
 /// Generate a valid regtest address for testing purposes.
 /// Uses deterministic key generation based on the index for reproducible tests.
@@ -36,6 +47,26 @@ pub fn create_valid_address_for_network(index: u8, network: Network) -> String {
     address.to_string()
 }
 
+/// Create the standard "Team A wins" / "Team B wins" outcomes.
+pub fn create_test_outcomes() -> Vec<PredictionOutcome> {
+    create_test_outcomes_with_count(2)
+}
+
+/// Create `count` test outcomes labelled "Team A wins", "Team B wins", ...
+pub fn create_test_outcomes_with_count(count: usize) -> Vec<PredictionOutcome> {
+    (0..count)
+        .map(|i| {
+            PredictionOutcome::new(
+                format!("Team {} wins", outcome_label(i)),
+                "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
+                1735689600,
+                outcome_label(i),
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
 /// Create a standard test prediction market with predefined bets.
 /// Uses regtest network and creates a market with bets on both sides.
 pub fn create_test_market() -> PredictionMarket {
@@ -44,109 +75,81 @@ pub fn create_test_market() -> PredictionMarket {
 
 /// Create a test prediction market for a specific network.
 pub fn create_test_market_with_network(network: Network) -> PredictionMarket {
-    let outcome_a = PredictionOutcome::new(
-        "Team A wins".to_string(),
-        "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
-        1735689600,
-        'A',
-    )
-    .unwrap();
-
-    let outcome_b = PredictionOutcome::new(
-        "Team B wins".to_string(),
-        "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
-        1735689600,
-        'B',
-    )
-    .unwrap();
-
     PredictionMarket {
         market_id: "test_market_id".to_string(),
         question: "Who will win the match?".to_string(),
-        outcome_a,
-        outcome_b,
+        outcomes: create_test_outcomes(),
         oracle_pubkey: "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a"
             .to_string(),
         settlement_timestamp: 1735689600,
         network,
+        signet_challenge: None,
         market_utxo: None,
         total_amount: 300000, // 3 BTC worth in sats
-        bets_a: vec![
-            Bet {
-                payout_address: if network == Network::Regtest {
-                    create_valid_regtest_address(1)
-                } else {
-                    create_valid_address_for_network(1, network)
+        bets: vec![
+            vec![
+                Bet {
+                    payout_address: if network == Network::Regtest {
+                        create_valid_regtest_address(1)
+                    } else {
+                        create_valid_address_for_network(1, network)
+                    },
+                    amount: 100000,
+                    txid: "abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcd"
+                        .to_string(),
+                    vout: 0,
                 },
-                amount: 100000,
-                txid: "abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcd"
-                    .to_string(),
-                vout: 0,
-            },
-            Bet {
+                Bet {
+                    payout_address: if network == Network::Regtest {
+                        create_valid_regtest_address(2)
+                    } else {
+                        create_valid_address_for_network(2, network)
+                    },
+                    amount: 50000,
+                    txid: "abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabce"
+                        .to_string(),
+                    vout: 1,
+                },
+            ],
+            vec![Bet {
                 payout_address: if network == Network::Regtest {
-                    create_valid_regtest_address(2)
+                    create_valid_regtest_address(3)
                 } else {
-                    create_valid_address_for_network(2, network)
+                    create_valid_address_for_network(3, network)
                 },
-                amount: 50000,
-                txid: "abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabce"
+                amount: 150000,
+                txid: "fedcbafedcbafedcbafedcbafedcbafedcbafedcbafedcbafedcbafedcbafedc"
                     .to_string(),
-                vout: 1,
-            },
+                vout: 0,
+            }],
         ],
-        bets_b: vec![Bet {
-            payout_address: if network == Network::Regtest {
-                create_valid_regtest_address(3)
-            } else {
-                create_valid_address_for_network(3, network)
-            },
-            amount: 150000,
-            txid: "fedcbafedcbafedcbafedcbafedcbafedcbafedcbafedcbafedcbafedcbafed".to_string(),
-            vout: 0,
-        }],
-        settled: false,
-        winning_outcome: None,
+        state: MarketState::Open,
         withdraw_timeout: 86400, // 1 day
+        escape_timelock: None,
         fees: MarketFees::default(),
+        pool_cache: Default::default(),
     }
 }
 
 /// Create a minimal test market with no bets.
 pub fn create_empty_test_market() -> PredictionMarket {
-    let outcome_a = PredictionOutcome::new(
-        "Team A wins".to_string(),
-        "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
-        1735689600,
-        'A',
-    )
-    .unwrap();
-
-    let outcome_b = PredictionOutcome::new(
-        "Team B wins".to_string(),
-        "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
-        1735689600,
-        'B',
-    )
-    .unwrap();
-
     PredictionMarket {
         market_id: "empty_market_id".to_string(),
         question: "Empty market for testing?".to_string(),
-        outcome_a,
-        outcome_b,
+        outcomes: create_test_outcomes(),
         oracle_pubkey: "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a"
             .to_string(),
         settlement_timestamp: 1735689600,
         network: Network::Regtest,
+        signet_challenge: None,
         market_utxo: None,
         total_amount: 0,
-        bets_a: vec![],
-        bets_b: vec![],
-        settled: false,
-        winning_outcome: None,
+        bets: vec![vec![], vec![]],
+        state: MarketState::Open,
         withdraw_timeout: 86400,
+        escape_timelock: None,
         fees: MarketFees::default(),
+        pool_cache: Default::default(),
     }
 }
 
@@ -155,22 +158,6 @@ pub fn create_test_market_with_amounts(
     bets_a_amounts: Vec<u64>,
     bets_b_amounts: Vec<u64>,
 ) -> PredictionMarket {
-    let outcome_a = PredictionOutcome::new(
-        "Team A wins".to_string(),
-        "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
-        1735689600,
-        'A',
-    )
-    .unwrap();
-
-    let outcome_b = PredictionOutcome::new(
-        "Team B wins".to_string(),
-        "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a".to_string(),
-        1735689600,
-        'B',
-    )
-    .unwrap();
-
     let mut bets_a = Vec::new();
     for (i, amount) in bets_a_amounts.iter().enumerate() {
         bets_a.push(Bet {
@@ -202,23 +189,258 @@ pub fn create_test_market_with_amounts(
     PredictionMarket {
         market_id: "custom_market_id".to_string(),
         question: "Custom amounts market?".to_string(),
-        outcome_a,
-        outcome_b,
+        outcomes: create_test_outcomes(),
         oracle_pubkey: "ee96d4b9c5e16f3b11e33bb27fe39ae7a57daa6b24210de5b39237993742cc0a"
             .to_string(),
         settlement_timestamp: 1735689600,
         network: Network::Regtest,
+        signet_challenge: None,
         market_utxo: None,
         total_amount,
-        bets_a,
-        bets_b,
-        settled: false,
-        winning_outcome: None,
+        bets: vec![bets_a, bets_b],
+        state: MarketState::Open,
         withdraw_timeout: 86400,
+        escape_timelock: None,
         fees: MarketFees::default(),
+        pool_cache: Default::default(),
+    }
+}
+
+/// Create a test market with one outcome per entry of `bet_amounts`, holding
+/// bets of the given amounts.
+pub fn create_test_market_with_outcomes(bet_amounts: &[Vec<u64>]) -> PredictionMarket {
+    let mut market = create_empty_test_market();
+    market.market_id = "categorical_market_id".to_string();
+    market.outcomes = create_test_outcomes_with_count(bet_amounts.len());
+    market.bets = bet_amounts
+        .iter()
+        .enumerate()
+        .map(|(outcome, amounts)| {
+            amounts
+                .iter()
+                .enumerate()
+                .map(|(i, amount)| Bet {
+                    payout_address: create_valid_regtest_address((outcome * 8 + i + 1) as u8),
+                    amount: *amount,
+                    txid: format!(
+                        "abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdef{:02x}{:02x}",
+                        outcome, i
+                    ),
+                    vout: i as u32,
+                })
+                .collect()
+        })
+        .collect();
+    market.total_amount = bet_amounts.iter().flatten().sum();
+    market
+}
+
+/// Deterministic key of the bettor funding a deposit.
+pub fn bettor_key(index: u8) -> PrivateKey {
+    let secret_key = SecretKey::from_slice(&[index; 32]).unwrap();
+    PrivateKey::new(secret_key, Network::Regtest)
+}
+
+/// Deterministic outpoint of a bettor's UTXO.
+pub fn test_outpoint(index: u8) -> OutPoint {
+    OutPoint {
+        txid: Txid::from_byte_array([index; 32]),
+        vout: index as u32,
     }
 }
 
+/// A BIP-86 Taproot UTXO controlled by `key`.
+pub fn p2tr_input(key: &PrivateKey, index: u8, amount: u64) -> DepositInput {
+    let secp = Secp256k1::new();
+    let keypair = keypair_from_private_key(key).unwrap();
+    let script_pubkey = ScriptBuf::new_p2tr(&secp, keypair.x_only_public_key().0, None);
+    DepositInput::new(
+        test_outpoint(index),
+        TxOut {
+            value: Amount::from_sat(amount),
+            script_pubkey,
+        },
+    )
+}
+
+/// The P2WPKH script of `key`.
+pub fn p2wpkh_script(key: &PrivateKey) -> ScriptBuf {
+    let secp = Secp256k1::new();
+    let public_key = CompressedPublicKey::from_private_key(&secp, key).unwrap();
+    ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash())
+}
+
+/// A P2WPKH UTXO controlled by `key`.
+pub fn p2wpkh_input(key: &PrivateKey, index: u8, amount: u64) -> DepositInput {
+    DepositInput::new(
+        test_outpoint(index),
+        TxOut {
+            value: Amount::from_sat(amount),
+            script_pubkey: p2wpkh_script(key),
+        },
+    )
+}
+
+/// A P2SH-P2WPKH UTXO controlled by `key`.
+pub fn p2sh_p2wpkh_input(key: &PrivateKey, index: u8, amount: u64) -> DepositInput {
+    let redeem_script = p2wpkh_script(key);
+    DepositInput::with_redeem_script(
+        test_outpoint(index),
+        TxOut {
+            value: Amount::from_sat(amount),
+            script_pubkey: ScriptBuf::new_p2sh(&redeem_script.script_hash()),
+        },
+        redeem_script,
+    )
+}
+
+/// A regtest change address.
+pub fn change_address(index: u8) -> Address {
+    create_valid_regtest_address(index)
+        .parse::<Address<_>>()
+        .unwrap()
+        .assume_checked()
+}
+
+/// Contributions for the three bets of [`create_test_market`]: two P2TR inputs with change,
+/// a P2WPKH input with change and an exact P2SH-P2WPKH input
+pub fn deposit_contributions(market: &PredictionMarket) -> Vec<(PartialDepositTx, PrivateKey)> {
+    let bets: Vec<_> = market.all_bets().collect();
+    let fee = market.fees.fee_per_deposit_input;
+    let funding = [
+        vec![
+            p2tr_input(&bettor_key(1), 1, 60000),
+            p2tr_input(&bettor_key(1), 2, 60000),
+        ],
+        vec![p2wpkh_input(&bettor_key(2), 3, 80000)],
+        vec![p2sh_p2wpkh_input(&bettor_key(3), 4, 150000 + fee)],
+    ];
+    funding
+        .into_iter()
+        .enumerate()
+        .map(|(index, inputs)| {
+            let key = bettor_key(index as u8 + 1);
+            let partial = create_partial_pool_tx(
+                market,
+                bets[index],
+                index,
+                inputs,
+                &change_address(index as u8 + 1),
+            )
+            .unwrap();
+            (partial, key)
+        })
+        .collect()
+}
+
+/// The unspent outputs funding the contributions, as seen on chain
+pub fn utxo_set(partials: &[PartialDepositTx]) -> HashMap<OutPoint, TxOut> {
+    partials
+        .iter()
+        .flat_map(|partial| {
+            partial
+                .psbt
+                .unsigned_tx
+                .input
+                .iter()
+                .zip(&partial.psbt.inputs)
+                .map(|(tx_input, input)| {
+                    (
+                        tx_input.previous_output,
+                        input.witness_utxo.clone().unwrap(),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Exercise a market store: markets, bets, deposit sessions and attestations.
+pub fn check_market_store(store: &mut dyn MarketStore) {
+    let market = create_test_market();
+    let empty = create_empty_test_market();
+    assert!(store.list_markets().unwrap().is_empty());
+    assert!(matches!(
+        store.load_market(&market.market_id),
+        Err(StoreError::NotFound(_))
+    ));
+
+    store.save_market(&market).unwrap();
+    store.save_market(&empty).unwrap();
+    assert_eq!(store.load_market(&market.market_id).unwrap(), market);
+    assert_eq!(
+        store.list_markets().unwrap(),
+        vec![empty.clone(), market.clone()]
+    );
+
+    // Bets
+    let bet = Bet {
+        payout_address: create_valid_regtest_address(4),
+        amount: 25_000,
+        txid: constants::TEST_TXID.to_string(),
+        vout: 2,
+    };
+    let updated = store.add_bet(&empty.market_id, 1, &bet).unwrap();
+    assert_eq!(updated.total_amount, 25_000);
+    assert_eq!(store.load_market(&empty.market_id).unwrap(), updated);
+    assert_eq!(
+        store.list_bets(&empty.market_id).unwrap(),
+        vec![(1, bet.clone())]
+    );
+    assert_eq!(store.list_bets(&market.market_id).unwrap().len(), 3);
+
+    // A rejected update leaves the saved market unchanged
+    assert!(matches!(
+        store.add_bet(&empty.market_id, 2, &bet),
+        Err(StoreError::Market(_))
+    ));
+    assert_eq!(store.load_market(&empty.market_id).unwrap(), updated);
+
+    // Deposit sessions
+    assert!(store.load_session(&market.market_id).unwrap().is_none());
+    let mut session = DepositCoordinator::new(market.clone()).unwrap();
+    session.freeze().unwrap();
+    store.save_session(&session).unwrap();
+    let loaded = store.load_session(&market.market_id).unwrap().unwrap();
+    assert_eq!(loaded.phase(), session.phase());
+    assert_eq!(loaded.market(), session.market());
+    let mut unsaved = create_test_market();
+    unsaved.market_id = "unsaved".to_string();
+    assert!(matches!(
+        store.save_session(&DepositCoordinator::new(unsaved).unwrap()),
+        Err(StoreError::NotFound(_))
+    ));
+
+    // Settlement attestations
+    assert!(store.load_attestation(&market.market_id).unwrap().is_none());
+    let attestation = Attestation {
+        outcome_index: 1,
+        outcome_id: market.outcomes[1].nostr_id(),
+        csfs_signature: vec![7; 64],
+        event: market.outcomes[1].nostr_event(),
+    };
+    store
+        .save_attestation(&market.market_id, &attestation)
+        .unwrap();
+    assert_eq!(
+        store.load_attestation(&market.market_id).unwrap(),
+        Some(attestation.clone())
+    );
+    assert!(matches!(
+        store.save_attestation("unsaved", &attestation),
+        Err(StoreError::NotFound(_))
+    ));
+
+    // Deleting a market deletes its session and attestation
+    store.delete_market(&market.market_id).unwrap();
+    assert_eq!(store.list_markets().unwrap(), vec![updated]);
+    assert!(store.load_session(&market.market_id).unwrap().is_none());
+    assert!(store.load_attestation(&market.market_id).unwrap().is_none());
+    assert!(matches!(
+        store.delete_market(&market.market_id),
+        Err(StoreError::NotFound(_))
+    ));
+}
+
 /// Common test constants
 pub mod constants {
     /// Standard oracle public key used in tests
@@ -0,0 +1,368 @@
+//! # Nostr Transport
+//!
+//! Carries the deposit round's [`ProtocolMessage`]s over Nostr relays.
+//!
+//! Every message is the versioned JSON ([`crate::wire`]) content of an unsigned rumor of kind
+//! [`DEPOSIT_MESSAGE_KIND`], tagged with the market id and the recipient. As for
+//! NIP-17 direct messages, the rumor is encrypted (NIP-44) into a seal signed by the
+//! sender, which is encrypted again into a gift wrap (NIP-59) signed by a throwaway
+//! key. Relays only see the recipient of a gift wrap; the sender, the market and the
+//! message stay private.
+//!
+//! Participants address their messages to the coordinator, who answers each of them
+//! the same way. Incoming gift wraps are checked end to end before the message is
+//! handed to [`crate::coordinator::DepositCoordinator::handle`]: both signatures, the
+//! rumor author, the recipient, the market and the direction of the message.
+
+use crate::{deposit::ProtocolMessage, wire::WireError, WireMessage};
+use nostr::{
+    nips::{nip44, nip59::RANGE_RANDOM_TIMESTAMP_TWEAK},
+    Event, EventBuilder, JsonUtil, Keys, Kind, PublicKey, Tag, TagKind, Timestamp, UnsignedEvent,
+};
+use thiserror::Error;
+
+/// Kind of the rumors carrying deposit round messages
+pub const DEPOSIT_MESSAGE_KIND: Kind = Kind::Custom(8_210);
+
+/// Tag naming the market of a deposit round message
+pub const MARKET_TAG: &str = "market";
+
+/// Errors of the Nostr transport
+#[derive(Error, Debug)]
+pub enum TransportError {
+    /// The event could not be built, signed or verified
+    #[error("Invalid event: {0}")]
+    InvalidEvent(String),
+
+    /// NIP-44 encryption or decryption failed
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    /// The message content is not a protocol message
+    #[error("Invalid message content: {0}")]
+    Content(#[from] WireError),
+
+    /// The rumor is not addressed to us
+    #[error("Message is addressed to another recipient")]
+    WrongRecipient,
+
+    /// The rumor belongs to another market
+    #[error("Message for market {found}, expected {expected}")]
+    WrongMarket { expected: String, found: String },
+
+    /// The rumor was not written by the sender of the seal, or not by the coordinator
+    #[error("Unexpected sender {0}")]
+    UnexpectedSender(PublicKey),
+
+    /// The message travels in the wrong direction
+    #[error("Unexpected {0} message")]
+    UnexpectedMessage(&'static str),
+}
+
+/// A deposit round message received over Nostr
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReceivedMessage {
+    /// Author of the message, from the verified seal
+    pub sender: PublicKey,
+    /// Market of the deposit round
+    pub market_id: String,
+    /// When the sender wrote the message
+    pub created_at: Timestamp,
+    /// The message
+    pub message: ProtocolMessage,
+}
+
+/// Encrypts a deposit round message into a gift wrap for `recipient`.
+///
+/// # Arguments
+/// * `keys` - Sender keys, signing the seal
+/// * `recipient` - Coordinator, or participant for coordinator messages
+/// * `market_id` - Market of the deposit round
+/// * `message` - Message to send
+pub fn wrap_message(
+    keys: &Keys,
+    recipient: &PublicKey,
+    market_id: &str,
+    message: &ProtocolMessage,
+) -> Result<Event, TransportError> {
+    let content = message.to_json()?;
+    let mut rumor = EventBuilder::new(DEPOSIT_MESSAGE_KIND, content)
+        .tags([
+            Tag::public_key(*recipient),
+            Tag::custom(TagKind::custom(MARKET_TAG), [market_id]),
+        ])
+        .build(keys.public_key());
+    rumor.ensure_id();
+
+    let sealed = nip44::encrypt(
+        keys.secret_key(),
+        recipient,
+        rumor.as_json(),
+        nip44::Version::default(),
+    )
+    .map_err(|e| TransportError::Encryption(e.to_string()))?;
+    let seal = EventBuilder::new(Kind::Seal, sealed)
+        .custom_created_at(Timestamp::tweaked(RANGE_RANDOM_TIMESTAMP_TWEAK))
+        .sign_with_keys(keys)
+        .map_err(|e| TransportError::InvalidEvent(e.to_string()))?;
+    EventBuilder::gift_wrap_from_seal(recipient, &seal, [])
+        .map_err(|e| TransportError::InvalidEvent(e.to_string()))
+}
+
+/// Opens a participant's gift wrap addressed to the coordinator.
+///
+/// Rejects coordinator messages, so a participant cannot impersonate the coordinator.
+pub fn unwrap_participant_message(
+    keys: &Keys,
+    gift_wrap: &Event,
+    market_id: &str,
+) -> Result<ReceivedMessage, TransportError> {
+    let received = unwrap_message(keys, gift_wrap, market_id)?;
+    if received.message.is_from_coordinator() {
+        return Err(TransportError::UnexpectedMessage(received.message.name()));
+    }
+    Ok(received)
+}
+
+/// Opens the coordinator's gift wrap addressed to a participant.
+///
+/// Only messages written by `coordinator` are accepted.
+pub fn unwrap_coordinator_message(
+    keys: &Keys,
+    gift_wrap: &Event,
+    market_id: &str,
+    coordinator: &PublicKey,
+) -> Result<ReceivedMessage, TransportError> {
+    let received = unwrap_message(keys, gift_wrap, market_id)?;
+    if received.sender != *coordinator {
+        return Err(TransportError::UnexpectedSender(received.sender));
+    }
+    if !received.message.is_from_coordinator() {
+        return Err(TransportError::UnexpectedMessage(received.message.name()));
+    }
+    Ok(received)
+}
+
+fn unwrap_message(
+    keys: &Keys,
+    gift_wrap: &Event,
+    market_id: &str,
+) -> Result<ReceivedMessage, TransportError> {
+    if gift_wrap.kind != Kind::GiftWrap {
+        return Err(TransportError::InvalidEvent(format!(
+            "expected a gift wrap, got kind {}",
+            gift_wrap.kind
+        )));
+    }
+    gift_wrap
+        .verify()
+        .map_err(|e| TransportError::InvalidEvent(e.to_string()))?;
+
+    let seal = nip44::decrypt(keys.secret_key(), &gift_wrap.pubkey, &gift_wrap.content)
+        .map_err(|e| TransportError::Encryption(e.to_string()))?;
+    let seal = Event::from_json(seal).map_err(|e| TransportError::InvalidEvent(e.to_string()))?;
+    if seal.kind != Kind::Seal {
+        return Err(TransportError::InvalidEvent(format!(
+            "expected a seal, got kind {}",
+            seal.kind
+        )));
+    }
+    seal.verify()
+        .map_err(|e| TransportError::InvalidEvent(e.to_string()))?;
+
+    let rumor = nip44::decrypt(keys.secret_key(), &seal.pubkey, &seal.content)
+        .map_err(|e| TransportError::Encryption(e.to_string()))?;
+    let rumor =
+        UnsignedEvent::from_json(rumor).map_err(|e| TransportError::InvalidEvent(e.to_string()))?;
+    rumor
+        .verify_id()
+        .map_err(|e| TransportError::InvalidEvent(e.to_string()))?;
+    if rumor.pubkey != seal.pubkey {
+        return Err(TransportError::UnexpectedSender(rumor.pubkey));
+    }
+    if rumor.kind != DEPOSIT_MESSAGE_KIND {
+        return Err(TransportError::InvalidEvent(format!(
+            "expected a deposit message, got kind {}",
+            rumor.kind
+        )));
+    }
+    if !rumor
+        .tags
+        .public_keys()
+        .any(|recipient| *recipient == keys.public_key())
+    {
+        return Err(TransportError::WrongRecipient);
+    }
+    let found = rumor
+        .tags
+        .find(TagKind::custom(MARKET_TAG))
+        .and_then(|tag| tag.content())
+        .unwrap_or_default();
+    if found != market_id {
+        return Err(TransportError::WrongMarket {
+            expected: market_id.to_string(),
+            found: found.to_string(),
+        });
+    }
+
+    Ok(ReceivedMessage {
+        sender: seal.pubkey,
+        market_id: market_id.to_string(),
+        created_at: rumor.created_at,
+        message: ProtocolMessage::from_json(&rumor.content)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn keys(byte: u8) -> Keys {
+        Keys::parse(&hex::encode([byte; 32])).unwrap()
+    }
+
+    fn frozen_market() -> crate::PredictionMarket {
+        let mut market = create_test_market();
+        market.freeze_bets().unwrap();
+        market
+    }
+
+    #[test]
+    fn test_contribution_round_trip() {
+        let (coordinator, bettor) = (keys(1), keys(2));
+        let market = frozen_market();
+        let partial = deposit_contributions(&market).remove(1).0;
+        let message = ProtocolMessage::PartialDepositTx(partial);
+
+        let event = wrap_message(
+            &bettor,
+            &coordinator.public_key(),
+            &market.market_id,
+            &message,
+        )
+        .unwrap();
+        assert_eq!(event.kind, Kind::GiftWrap);
+        assert_ne!(event.pubkey, bettor.public_key());
+        assert!(!event.content.contains(&market.market_id));
+
+        let received = unwrap_participant_message(&coordinator, &event, &market.market_id).unwrap();
+        assert_eq!(received.sender, bettor.public_key());
+        assert_eq!(received.market_id, market.market_id);
+        assert_eq!(received.message, message);
+    }
+
+    #[test]
+    fn test_bet_round_trip() {
+        let (coordinator, bettor) = (keys(1), keys(2));
+        let market = create_test_market();
+        let message = ProtocolMessage::Bet {
+            outcome: 1,
+            bet: market.bets[1][0].clone(),
+        };
+        let event = wrap_message(
+            &bettor,
+            &coordinator.public_key(),
+            &market.market_id,
+            &message,
+        )
+        .unwrap();
+        let received = unwrap_participant_message(&coordinator, &event, &market.market_id).unwrap();
+        assert_eq!(received.message, message);
+    }
+
+    #[test]
+    fn test_coordinator_messages() {
+        let (coordinator, bettor) = (keys(1), keys(2));
+        let market = frozen_market();
+        let message = ProtocolMessage::BetsFrozen(Box::new(market.clone()));
+        let event = wrap_message(
+            &coordinator,
+            &bettor.public_key(),
+            &market.market_id,
+            &message,
+        )
+        .unwrap();
+        let received = unwrap_coordinator_message(
+            &bettor,
+            &event,
+            &market.market_id,
+            &coordinator.public_key(),
+        )
+        .unwrap();
+        assert_eq!(received.message, message);
+
+        // Only the coordinator may freeze the bets
+        let impostor = keys(3);
+        let event =
+            wrap_message(&impostor, &bettor.public_key(), &market.market_id, &message).unwrap();
+        assert!(matches!(
+            unwrap_coordinator_message(
+                &bettor,
+                &event,
+                &market.market_id,
+                &coordinator.public_key()
+            ),
+            Err(TransportError::UnexpectedSender(sender)) if sender == impostor.public_key()
+        ));
+
+        // Nor send it to the coordinator
+        let event = wrap_message(
+            &bettor,
+            &coordinator.public_key(),
+            &market.market_id,
+            &message,
+        )
+        .unwrap();
+        assert!(matches!(
+            unwrap_participant_message(&coordinator, &event, &market.market_id),
+            Err(TransportError::UnexpectedMessage("bets_frozen"))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_events() {
+        let (coordinator, bettor) = (keys(1), keys(2));
+        let market = create_test_market();
+        let message = ProtocolMessage::Bet {
+            outcome: 0,
+            bet: market.bets[0][0].clone(),
+        };
+        let event = wrap_message(
+            &bettor,
+            &coordinator.public_key(),
+            &market.market_id,
+            &message,
+        )
+        .unwrap();
+
+        // Another market
+        assert!(matches!(
+            unwrap_participant_message(&coordinator, &event, "other"),
+            Err(TransportError::WrongMarket { .. })
+        ));
+
+        // Not addressed to us
+        assert!(matches!(
+            unwrap_participant_message(&keys(3), &event, &market.market_id),
+            Err(TransportError::Encryption(_))
+        ));
+
+        // Tampered gift wrap
+        let mut tampered = event.clone();
+        tampered.content.insert(0, 'A');
+        assert!(matches!(
+            unwrap_participant_message(&coordinator, &tampered, &market.market_id),
+            Err(TransportError::InvalidEvent(_))
+        ));
+
+        // A plain event
+        let note = EventBuilder::text_note("hello")
+            .sign_with_keys(&bettor)
+            .unwrap();
+        assert!(matches!(
+            unwrap_participant_message(&coordinator, &note, &market.market_id),
+            Err(TransportError::InvalidEvent(_))
+        ));
+    }
+}
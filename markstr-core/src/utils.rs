@@ -2,7 +2,7 @@
 //!
 //! Common utility functions for Bitcoin operations and data conversion.
 
-use crate::{error::Result, MarketError};
+use crate::{attestation::AttestationError, error::Result, MarketError};
 use bitcoin::{Address, Network};
 use sha2::{Digest, Sha256};
 use std::str::FromStr;
@@ -60,49 +60,39 @@ pub fn btc_to_satoshi(btc: f64) -> u64 {
     (btc * 100_000_000.0) as u64
 }
 
-/// Verify a signature (placeholder implementation)
+/// Verify a BIP-340 signature of a 32-byte message, hex-encoded except for the
+/// public key which may also be an `npub`.
+///
+/// Returns `Ok(false)` for a well-formed signature that does not verify and an
+/// error for malformed inputs. [`crate::attestation`] verifies whole Nostr events
+/// with typed errors.
+///
+/// # Arguments
+/// * `message` - The signed message, e.g. a Nostr event id
+/// * `signature` - 64-byte Schnorr signature
+/// * `pubkey` - 32-byte x-only public key, hex or `npub`
 pub fn verify_signature(message: &str, signature: &str, pubkey: &str) -> Result<bool> {
-    // Validate message is not empty
-    if message.is_empty() {
-        return Err(MarketError::InvalidSignature(
-            "Message cannot be empty".to_string(),
-        ));
-    }
-
-    // Validate signature is hex and 64 bytes (128 hex chars)
-    if signature.len() != 128 {
-        return Err(MarketError::InvalidSignature(
-            "Signature must be 64 bytes (128 hex characters)".to_string(),
-        ));
-    }
-
-    if hex::decode(signature).is_err() {
-        return Err(MarketError::InvalidSignature(
-            "Invalid signature hex encoding".to_string(),
-        ));
-    }
-
-    // Validate pubkey is hex and 32 bytes (64 hex chars)
-    if pubkey.len() != 64 {
-        return Err(MarketError::InvalidSignature(
-            "Public key must be 32 bytes (64 hex characters)".to_string(),
-        ));
-    }
-
-    if hex::decode(pubkey).is_err() {
-        return Err(MarketError::InvalidSignature(
-            "Invalid public key hex encoding".to_string(),
-        ));
+    let decode = |value: &str, name: &str| {
+        hex::decode(value)
+            .map_err(|e| MarketError::InvalidSignature(format!("Invalid {name} hex encoding: {e}")))
+    };
+    let message = decode(message, "message")?;
+    let signature = decode(signature, "signature")?;
+    let pubkey = hex::decode(crate::nip19::parse_public_key(pubkey)?)?;
+
+    match crate::attestation::verify_schnorr(&message, &signature, &pubkey) {
+        Ok(()) => Ok(true),
+        Err(AttestationError::BadSignature) => Ok(false),
+        Err(e) => Err(e.into()),
     }
+}
 
-    use secp256k1::{schnorr, Secp256k1, XOnlyPublicKey};
-    let secp = Secp256k1::verification_only();
-    let public_key = XOnlyPublicKey::from_slice(pubkey.as_bytes())?;
-    let signature = schnorr::Signature::from_slice(signature.as_bytes())?;
-    let message = secp256k1::Message::from_digest_slice(message.as_bytes())?;
-    Ok(secp
-        .verify_schnorr(&signature, &message, &public_key)
-        .is_ok())
+/// Derive the hex x-only public key of a 32-byte secret key
+pub fn public_key_from_secret(secret_key: &[u8]) -> Result<String> {
+    use bitcoin::secp256k1::{Keypair, Secp256k1};
+    let keypair = Keypair::from_seckey_slice(&Secp256k1::new(), secret_key)
+        .map_err(|e| MarketError::InvalidSignature(format!("Invalid secret key: {e}")))?;
+    Ok(keypair.x_only_public_key().0.to_string())
 }
 
 /// Network enum to u8 conversion
@@ -181,6 +171,30 @@ mod tests {
         assert!(!validate_address(invalid_addr, Network::Bitcoin));
     }
 
+    #[test]
+    fn test_verify_signature() {
+        use bitcoin::secp256k1::{Keypair, Message, Secp256k1};
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_slice(&secp, &[0x11; 32]).unwrap();
+        let pubkey = keypair.x_only_public_key().0.to_string();
+        let message = sha256_hash("attested outcome");
+        let digest: [u8; 32] = hex::decode(&message).unwrap().try_into().unwrap();
+        let signature = secp.sign_schnorr(&Message::from_digest(digest), &keypair);
+        let signature = hex::encode(signature.serialize());
+
+        assert!(verify_signature(&message, &signature, &pubkey).unwrap());
+        let other = sha256_hash("another outcome");
+        assert!(!verify_signature(&other, &signature, &pubkey).unwrap());
+        assert!(verify_signature("", &signature, &pubkey).is_err());
+        assert!(verify_signature(&message, "not hex", &pubkey).is_err());
+        assert!(verify_signature(&message, &signature, &message[..62]).is_err());
+        let npub = crate::nip19::encode_public_key(&pubkey).unwrap();
+        assert!(verify_signature(&message, &signature, &npub).unwrap());
+
+        assert_eq!(public_key_from_secret(&[0x11; 32]).unwrap(), pubkey);
+        assert!(public_key_from_secret(&[0; 32]).is_err());
+    }
+
     #[test]
     fn test_nostr_id() {
         // Tests that the Nostr ID is deterministic and unique
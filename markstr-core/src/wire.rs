@@ -0,0 +1,303 @@
+//! # Wire Format
+//!
+//! Versioned encodings of the messages that cross a process or network boundary:
+//! [`ProtocolMessage`], [`PartialDepositTx`], [`WithdrawParams`] and [`WithdrawType`].
+//!
+//! The JSON encoding wraps the serde representation of the value in an envelope:
+//!
+//! ```json
+//! {"version": 1, "type": "withdraw_type", "payload": "escape"}
+//! ```
+//!
+//! The compact binary encoding is a version byte followed by the CBOR encoding of
+//! the `type` and `payload` fields, where scripts, keys and PSBT maps are raw bytes
+//! instead of hex strings. Decoding checks the version before anything else and
+//! rejects the versions it does not know, then rejects payloads of another type.
+
+use crate::{
+    deposit::{PartialDepositTx, ProtocolMessage},
+    withdraw::{WithdrawParams, WithdrawType},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+/// Version of the wire format produced by this library
+pub const WIRE_VERSION: u8 = 1;
+
+/// Errors of the wire format
+#[derive(Error, Debug)]
+pub enum WireError {
+    /// The message was encoded with a version this library does not know
+    #[error("Unsupported wire format version {0}")]
+    UnsupportedVersion(u64),
+
+    /// The message carries another type than the one requested
+    #[error("Expected a {expected} message, got {found}")]
+    WrongType {
+        expected: &'static str,
+        found: String,
+    },
+
+    /// JSON encoding or decoding failed
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// Binary encoding or decoding failed
+    #[error("Binary encoding error: {0}")]
+    Binary(String),
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u8>,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    payload: &'a T,
+}
+
+#[derive(Deserialize)]
+struct VersionProbe {
+    version: u64,
+}
+
+#[derive(Deserialize)]
+struct RawEnvelope<P> {
+    #[serde(rename = "type")]
+    kind: String,
+    payload: P,
+}
+
+/// A value exchanged in the versioned wire format
+pub trait WireMessage: Serialize + DeserializeOwned {
+    /// Name of the type, carried next to the payload
+    const TYPE: &'static str;
+
+    /// Encodes the value as versioned JSON
+    fn to_json(&self) -> Result<String, WireError> {
+        Ok(serde_json::to_string(&Envelope {
+            version: Some(WIRE_VERSION),
+            kind: Self::TYPE,
+            payload: self,
+        })?)
+    }
+
+    /// Decodes a value from versioned JSON
+    fn from_json(json: &str) -> Result<Self, WireError> {
+        let VersionProbe { version } = serde_json::from_str(json)?;
+        check_version(version)?;
+        let envelope: RawEnvelope<serde_json::Value> = serde_json::from_str(json)?;
+        check_type::<Self>(&envelope.kind)?;
+        Ok(serde_json::from_value(envelope.payload)?)
+    }
+
+    /// Encodes the value in the compact binary format
+    fn to_bytes(&self) -> Result<Vec<u8>, WireError> {
+        let mut bytes = vec![WIRE_VERSION];
+        let envelope = Envelope {
+            version: None,
+            kind: Self::TYPE,
+            payload: self,
+        };
+        ciborium::into_writer(&envelope, &mut bytes)
+            .map_err(|e| WireError::Binary(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    /// Decodes a value from the compact binary format
+    fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let (&version, cbor) = bytes
+            .split_first()
+            .ok_or_else(|| WireError::Binary("empty message".to_string()))?;
+        check_version(version.into())?;
+        let envelope: RawEnvelope<ciborium::Value> =
+            ciborium::from_reader(cbor).map_err(|e| WireError::Binary(e.to_string()))?;
+        check_type::<Self>(&envelope.kind)?;
+        envelope
+            .payload
+            .deserialized()
+            .map_err(|e| WireError::Binary(e.to_string()))
+    }
+}
+
+fn check_version(version: u64) -> Result<(), WireError> {
+    if version != u64::from(WIRE_VERSION) {
+        return Err(WireError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+fn check_type<T: WireMessage>(kind: &str) -> Result<(), WireError> {
+    if kind != T::TYPE {
+        return Err(WireError::WrongType {
+            expected: T::TYPE,
+            found: kind.to_string(),
+        });
+    }
+    Ok(())
+}
+
+impl WireMessage for ProtocolMessage {
+    const TYPE: &'static str = "protocol_message";
+}
+
+impl WireMessage for PartialDepositTx {
+    const TYPE: &'static str = "partial_deposit_tx";
+}
+
+impl WireMessage for WithdrawParams {
+    const TYPE: &'static str = "withdraw_params";
+}
+
+impl WireMessage for WithdrawType {
+    const TYPE: &'static str = "withdraw_type";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::{Blame, CoordinatorPhase};
+    use crate::deposit::{build_deposit_psbt, sign_deposit_psbt};
+    use crate::test_utils::*;
+    use bitcoin::OutPoint;
+    use std::fmt::Debug;
+
+    fn assert_round_trip<T: WireMessage + PartialEq + Debug>(value: &T) {
+        assert_eq!(&T::from_json(&value.to_json().unwrap()).unwrap(), value);
+        assert_eq!(&T::from_bytes(&value.to_bytes().unwrap()).unwrap(), value);
+    }
+
+    /// One message of every kind, from a deposit round over `create_test_market`
+    fn protocol_messages() -> Vec<ProtocolMessage> {
+        let mut market = create_test_market();
+        market.freeze_bets().unwrap();
+        let contributions = deposit_contributions(&market);
+        let partials: Vec<_> = contributions.iter().map(|(p, _)| p.clone()).collect();
+        let template = build_deposit_psbt(&market, partials.clone()).unwrap();
+        let mut signed = template.clone();
+        sign_deposit_psbt(
+            &mut signed,
+            &market,
+            &contributions[0].0,
+            &contributions[0].1,
+        )
+        .unwrap();
+
+        vec![
+            ProtocolMessage::Bet {
+                outcome: 1,
+                bet: market.bets[1][0].clone(),
+            },
+            ProtocolMessage::BetsFrozen(Box::new(market.clone())),
+            ProtocolMessage::PartialDepositTx(partials[2].clone()),
+            ProtocolMessage::DepositTemplate(template.clone()),
+            ProtocolMessage::SignedDeposit {
+                input_index: 0,
+                psbt: signed,
+            },
+            ProtocolMessage::DepositTransaction(template.unsigned_tx),
+            ProtocolMessage::RoundRestarted {
+                blamed: vec![Blame {
+                    participant: 1,
+                    outcome: 1,
+                    bet: market.bets[1][0].clone(),
+                    phase: CoordinatorPhase::Signatures,
+                    rejection: Some("Participant 1: contribution is missing".to_string()),
+                }],
+                market: Box::new(market),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_protocol_message_round_trip() {
+        for message in protocol_messages() {
+            assert_round_trip(&message);
+        }
+    }
+
+    #[test]
+    fn test_withdraw_round_trip() {
+        let partial = deposit_contributions(&create_test_market()).remove(0).0;
+        assert_round_trip(&partial);
+        assert_round_trip(&WithdrawType::Payout);
+        assert_round_trip(&WithdrawType::Escape);
+        assert_round_trip(&WithdrawParams {
+            market: create_test_market(),
+            withdraw_type: WithdrawType::Escape,
+            pool_utxo: OutPoint::null(),
+            fee_rate: Some(5),
+        });
+    }
+
+    #[test]
+    fn test_stable_encoding() {
+        assert_eq!(
+            WithdrawType::Escape.to_json().unwrap(),
+            r#"{"version":1,"type":"withdraw_type","payload":"escape"}"#
+        );
+        let bytes = WithdrawType::Payout.to_bytes().unwrap();
+        assert_eq!(bytes[0], WIRE_VERSION);
+
+        // Binary messages carry raw bytes rather than hex strings
+        for message in protocol_messages() {
+            let json = message.to_json().unwrap();
+            assert!(message.to_bytes().unwrap().len() < json.len(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_versions() {
+        let json = r#"{"version":2,"type":"withdraw_type","payload":"escape"}"#;
+        assert!(matches!(
+            WithdrawType::from_json(json),
+            Err(WireError::UnsupportedVersion(2))
+        ));
+        // Without a version
+        let json = r#"{"type":"withdraw_type","payload":"escape"}"#;
+        assert!(matches!(
+            WithdrawType::from_json(json),
+            Err(WireError::Json(_))
+        ));
+
+        // The version byte is checked before the payload is parsed
+        let mut bytes = WithdrawType::Escape.to_bytes().unwrap();
+        bytes[0] = 2;
+        assert!(matches!(
+            WithdrawType::from_bytes(&bytes),
+            Err(WireError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            WithdrawType::from_bytes(&[0xff, 0x00]),
+            Err(WireError::UnsupportedVersion(255))
+        ));
+        assert!(matches!(
+            WithdrawType::from_bytes(&[]),
+            Err(WireError::Binary(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_other_types() {
+        let json = WithdrawType::Escape.to_json().unwrap();
+        assert!(matches!(
+            ProtocolMessage::from_json(&json),
+            Err(WireError::WrongType {
+                expected: "protocol_message",
+                ..
+            })
+        ));
+        let bytes = WithdrawType::Escape.to_bytes().unwrap();
+        assert!(matches!(
+            PartialDepositTx::from_bytes(&bytes),
+            Err(WireError::WrongType { .. })
+        ));
+
+        // Truncated binary payload
+        let bytes = protocol_messages()[3].to_bytes().unwrap();
+        assert!(matches!(
+            ProtocolMessage::from_bytes(&bytes[..bytes.len() / 2]),
+            Err(WireError::Binary(_))
+        ));
+    }
+}
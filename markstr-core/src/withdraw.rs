@@ -7,33 +7,36 @@
 
 use anyhow::{Context, Result};
 use bitcoin::{
-    absolute::LockTime,
-    hashes::{sha256, Hash},
-    taproot::ControlBlock,
-    transaction::Version,
-    Address, Amount, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+    absolute::LockTime, taproot::ControlBlock, transaction::Version, Address, Amount, Network,
+    OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
 };
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 use crate::{
     get_tx_version,
+    lifecycle::MarketState,
     market::{Bet, MarketFees, PredictionMarket},
     pool::{
         build_script_for_escape, build_script_for_outcome, calculate_ctv_hash_from_transaction,
+        EscapeTimelock,
     },
+    MarketError, DUST_THRESHOLD,
 };
 
 /// Transaction type for withdrawal
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum WithdrawType {
-    /// Payout to winning bets only (uses market.winning_outcome)
+    /// Payout to winning bets only (uses market.winning_outcome).
+    /// If nobody bet on the winning outcome, every bet is refunded.
     Payout,
     /// Escape withdrawal returning all funds
     Escape,
 }
 
 /// Parameters for building a withdrawal transaction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WithdrawParams {
     /// The market to withdraw from
     pub market: PredictionMarket,
@@ -68,7 +71,12 @@ pub fn generate_payout_outputs(
     let mut outputs = Vec::with_capacity(winning_bets.len());
     for (i, bet) in winning_bets.iter().enumerate() {
         let address = Address::from_str(&bet.payout_address)
-            .with_context(|| format!("Failed to parse payout address for bet {}: {}", i, bet.payout_address))?
+            .with_context(|| {
+                format!(
+                    "Failed to parse payout address for bet {}: {}",
+                    i, bet.payout_address
+                )
+            })?
             .require_network(network)
             .with_context(|| {
                 format!(
@@ -78,20 +86,21 @@ pub fn generate_payout_outputs(
             })?;
 
         let amount = (bet.amount * pool_after_fees) / winning_side_total;
-        if amount > 546 {
-            // dust threshold
+        if amount > DUST_THRESHOLD {
             outputs.push(TxOut {
                 value: Amount::from_sat(amount),
                 script_pubkey: address.script_pubkey(),
             });
         }
     }
-    
+
     // Add administrator fee output if configured
     if let Some(admin_address) = &fees.administrator_address {
         if fees.administrator_fee > 0 {
             let address = Address::from_str(admin_address)
-                .with_context(|| format!("Failed to parse administrator address: {}", admin_address))?
+                .with_context(|| {
+                    format!("Failed to parse administrator address: {}", admin_address)
+                })?
                 .require_network(network)
                 .with_context(|| {
                     format!(
@@ -99,7 +108,7 @@ pub fn generate_payout_outputs(
                         admin_address, network
                     )
                 })?;
-            
+
             outputs.push(TxOut {
                 value: Amount::from_sat(fees.administrator_fee),
                 script_pubkey: address.script_pubkey(),
@@ -111,7 +120,16 @@ pub fn generate_payout_outputs(
 }
 
 /// Generate transaction outputs for an escape transaction (all bets)
-pub fn generate_escape_outputs(all_bets: &[Bet], network: Network) -> Result<Vec<TxOut>> {
+///
+/// The escape transaction spends the pool alone, so its fee comes out of the
+/// refunds: like a payout output, each refund pays
+/// `fees.fee_per_withdraw_output`. Refunds left at or below [`DUST_THRESHOLD`]
+/// would make the transaction non-standard, they are dropped and go to the fee.
+pub fn generate_escape_outputs(
+    all_bets: &[Bet],
+    network: Network,
+    fees: &MarketFees,
+) -> Result<Vec<TxOut>> {
     let mut outputs = Vec::with_capacity(all_bets.len());
     for bet in all_bets {
         let address = Address::from_str(&bet.payout_address)
@@ -129,88 +147,116 @@ pub fn generate_escape_outputs(all_bets: &[Bet], network: Network) -> Result<Vec
                 )
             })?;
 
-        outputs.push(TxOut {
-            value: Amount::from_sat(bet.amount),
-            script_pubkey: address.script_pubkey(),
-        });
+        let amount = bet.amount.saturating_sub(fees.fee_per_withdraw_output);
+        if amount > DUST_THRESHOLD {
+            outputs.push(TxOut {
+                value: Amount::from_sat(amount),
+                script_pubkey: address.script_pubkey(),
+            });
+        }
     }
     Ok(outputs)
 }
 
-/// Build a withdrawal transaction
+/// Build a withdrawal transaction.
+///
+/// Refuses to build a payout before the market is settled, or an escape
+/// before the pool is funded.
 pub fn build_withdraw_transaction(params: WithdrawParams) -> Result<Transaction> {
-    let outputs = match &params.withdraw_type {
+    let state = params.market.state;
+    let allowed = match params.withdraw_type {
+        WithdrawType::Payout => matches!(state, MarketState::Settled { .. }),
+        WithdrawType::Escape => matches!(state, MarketState::Funded | MarketState::Settled { .. }),
+    };
+    if !allowed {
+        return Err(MarketError::InvalidState(format!(
+            "Cannot build a {:?} transaction for a market in state {}",
+            params.withdraw_type, state
+        ))
+        .into());
+    }
+
+    build_withdraw_template(&params.market, &params.withdraw_type, params.pool_utxo)
+}
+
+/// Build a withdrawal transaction without checking the market lifecycle.
+///
+/// Used to derive the CTV templates the pool commits to before the market is funded.
+pub fn build_withdraw_template(
+    market: &PredictionMarket,
+    withdraw_type: &WithdrawType,
+    pool_utxo: OutPoint,
+) -> Result<Transaction> {
+    let outputs = match withdraw_type {
         WithdrawType::Payout => {
-            let winning_outcome = params
-                .market
-                .winning_outcome
+            let winning_outcome = market
+                .winning_outcome()
                 .ok_or_else(|| anyhow::anyhow!("Market must be settled for payout transactions"))?;
-            let winning_bets = match winning_outcome {
-                'A' => &params.market.bets_a,
-                'B' => &params.market.bets_b,
-                _ => {
-                    return Err(anyhow::anyhow!(
-                        "Invalid winning outcome: {}",
-                        winning_outcome
-                    ))
-                }
-            };
-            generate_payout_outputs(
-                winning_bets,
-                params.market.total_amount,
-                params.market.network,
-                &params.market.fees,
-            )?
+            let winning_bets = market
+                .bets
+                .get(winning_outcome)
+                .ok_or_else(|| anyhow::anyhow!("Invalid winning outcome: {}", winning_outcome))?;
+            if winning_bets.is_empty() {
+                // Nobody bet on the winning outcome, refund everyone
+                let all_bets: Vec<Bet> = market.all_bets().cloned().collect();
+                generate_escape_outputs(&all_bets, market.network, &market.fees)?
+            } else {
+                generate_payout_outputs(
+                    winning_bets,
+                    market.total_amount,
+                    market.network,
+                    &market.fees,
+                )?
+            }
         }
         WithdrawType::Escape => {
-            let all_bets: Vec<Bet> = params
-                .market
-                .bets_a
-                .iter()
-                .chain(params.market.bets_b.iter())
-                .cloned()
-                .collect();
-            generate_escape_outputs(&all_bets, params.market.network)?
+            let all_bets: Vec<Bet> = market.all_bets().cloned().collect();
+            generate_escape_outputs(&all_bets, market.network, &market.fees)?
         }
     };
 
+    build_pool_spend_transaction(
+        market.network,
+        &market.escape_timelock()?,
+        withdraw_type,
+        pool_utxo,
+        outputs,
+    )
+}
+
+/// Build a transaction spending the pool UTXO into `outputs`.
+///
+/// Escape transactions set the `nLockTime` or `nSequence` required by
+/// `escape_timelock`, payout transactions can be broadcast as soon as the
+/// oracle has signed.
+pub fn build_pool_spend_transaction(
+    network: Network,
+    escape_timelock: &EscapeTimelock,
+    withdraw_type: &WithdrawType,
+    pool_utxo: OutPoint,
+    outputs: Vec<TxOut>,
+) -> Result<Transaction> {
     if outputs.is_empty() {
         return Err(anyhow::anyhow!("No valid outputs generated"));
     }
 
+    let (lock_time, sequence) = match withdraw_type {
+        WithdrawType::Escape => (escape_timelock.lock_time(), escape_timelock.sequence()),
+        WithdrawType::Payout => (LockTime::ZERO, Sequence::ENABLE_RBF_NO_LOCKTIME),
+    };
+
     // Create input spending the pool UTXO
     let input = TxIn {
-        previous_output: params.pool_utxo,
+        previous_output: pool_utxo,
         script_sig: ScriptBuf::new(),
-        sequence: if matches!(params.withdraw_type, WithdrawType::Escape) {
-            // Use locktime sequence for escape transactions
-            let settlement_timestamp: u32 = params
-                .market
-                .settlement_timestamp
-                .try_into()
-                .with_context(|| "Settlement timestamp too large")?;
-            let escape_time = settlement_timestamp + params.market.withdraw_timeout;
-            Sequence(escape_time)
-        } else {
-            Sequence::ENABLE_RBF_NO_LOCKTIME
-        },
+        sequence,
         witness: Witness::new(),
     };
 
     // Create transaction
     let tx = Transaction {
-        version: Version(get_tx_version(params.market.network)),
-        lock_time: if matches!(params.withdraw_type, WithdrawType::Escape) {
-            let settlement_timestamp: u32 = params
-                .market
-                .settlement_timestamp
-                .try_into()
-                .with_context(|| "Settlement timestamp too large")?;
-            let escape_time = settlement_timestamp + params.market.withdraw_timeout;
-            LockTime::from_time(escape_time).with_context(|| "Invalid escape locktime")?
-        } else {
-            LockTime::ZERO
-        },
+        version: Version(get_tx_version(network)),
+        lock_time,
         input: vec![input],
         output: outputs,
     };
@@ -219,29 +265,16 @@ pub fn build_withdraw_transaction(params: WithdrawParams) -> Result<Transaction>
 }
 
 /// Create witness data for spending the pool using the outcome path
+///
+/// The leaf pushes the outcome hash and the oracle key itself, so the only
+/// witness element is the oracle signature over the outcome.
 pub fn create_outcome_witness(
-    market: &PredictionMarket,
-    winning_outcome: char,
     oracle_signature: &[u8],
     control_block: ControlBlock,
     script: ScriptBuf,
 ) -> Result<Witness> {
-    let outcome_str = match winning_outcome {
-        'A' => market.outcome_a.nostr_id(),
-        'B' => market.outcome_b.nostr_id(),
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Invalid winning outcome: {}",
-                winning_outcome
-            ))
-        }
-    };
-
-    let outcome_hash = sha256::Hash::hash(outcome_str.as_bytes());
-
     let mut witness = Witness::new();
     witness.push(oracle_signature);
-    witness.push(outcome_hash.as_byte_array());
     witness.push(script.as_bytes());
     witness.push(control_block.serialize());
     Ok(witness)
@@ -255,82 +288,62 @@ pub fn create_escape_witness(control_block: ControlBlock, script: ScriptBuf) ->
     Ok(witness)
 }
 
-/// Sign and finalize a withdrawal transaction
+/// Sign and finalize a withdrawal transaction.
+///
+/// The leaf script and control block come from the market's pool spend info.
+/// The transaction must match the CTV template committed in that leaf.
 pub fn sign_withdraw_transaction(
     mut tx: Transaction,
     params: &WithdrawParams,
     oracle_signature: Option<&[u8]>, // Required for payout, not needed for escape
 ) -> Result<Transaction> {
-    // Create the appropriate script and control block based on withdraw type
-    let (script, control_block) = match &params.withdraw_type {
-        WithdrawType::Payout => {
-            let winning_outcome = params
-                .market
-                .winning_outcome
-                .ok_or_else(|| anyhow::anyhow!("Market must be settled for payout transactions"))?;
-
-            // Generate the outcome script
-            let ctv_hash = calculate_ctv_hash_from_transaction(&tx);
-
-            let outcome_id = match winning_outcome {
-                'A' => &params.market.outcome_a.nostr_id(),
-                'B' => &params.market.outcome_b.nostr_id(),
-                _ => return Err(anyhow::anyhow!("Invalid winning outcome")),
-            };
-
-            let script =
-                build_script_for_outcome(ctv_hash, &params.market.oracle_pubkey, outcome_id)?;
-
-            // For this example, we'll create a dummy control block
-            // In a real implementation, you'd need to derive this from the market's taproot tree
-            let control_block = create_dummy_control_block()?;
-
-            (script, control_block)
-        }
-        WithdrawType::Escape => {
-            let ctv_hash = calculate_ctv_hash_from_transaction(&tx);
+    let pool = params.market.pool_spend_info()?;
+    let ctv_hash = calculate_ctv_hash_from_transaction(&tx);
 
-            let script = build_script_for_escape(ctv_hash);
-            let control_block = create_dummy_control_block()?;
-
-            (script, control_block)
-        }
-    };
-
-    // Create witness based on withdraw type
     let witness = match &params.withdraw_type {
         WithdrawType::Payout => {
             let winning_outcome = params
                 .market
-                .winning_outcome
+                .winning_outcome()
                 .ok_or_else(|| anyhow::anyhow!("Market must be settled for payout transactions"))?;
-            let oracle_sig = oracle_signature.unwrap(); // We already checked this above
-            create_outcome_witness(
-                &params.market,
-                winning_outcome,
-                oracle_sig,
-                control_block,
-                script,
-            )?
+            let oracle_sig = oracle_signature
+                .ok_or_else(|| anyhow::anyhow!("Oracle signature required for payout"))?;
+
+            let (script, control_block) = pool.payout_leaf(winning_outcome)?;
+            let outcome_id = params.market.outcome(winning_outcome)?.nostr_id();
+            let expected =
+                build_script_for_outcome(ctv_hash, &params.market.oracle_pubkey, &outcome_id)?;
+            if script != expected {
+                return Err(anyhow::anyhow!(
+                    "Transaction does not match the payout template committed in the pool"
+                ));
+            }
+
+            create_outcome_witness(oracle_sig, control_block, script)?
+        }
+        WithdrawType::Escape => {
+            let (script, control_block) = pool.escape_leaf()?;
+            let timelock = params.market.escape_timelock()?;
+            if script != build_script_for_escape(ctv_hash, &timelock) {
+                return Err(anyhow::anyhow!(
+                    "Transaction does not match the escape template committed in the pool"
+                ));
+            }
+
+            create_escape_witness(control_block, script)?
         }
-        WithdrawType::Escape => create_escape_witness(control_block, script)?,
     };
 
     // Attach witness to the input
-    tx.input[0].witness = witness;
+    let input = tx
+        .input
+        .first_mut()
+        .ok_or_else(|| anyhow::anyhow!("Withdraw transaction has no input"))?;
+    input.witness = witness;
 
     Ok(tx)
 }
 
-/// Helper function to create a dummy control block for testing
-/// In a real implementation, this would be derived from the actual taproot tree
-fn create_dummy_control_block() -> Result<ControlBlock> {
-    // This is a placeholder - in reality you'd construct this from the market's taproot spending info
-    let dummy_bytes = vec![0xc0; 33]; // 0xc0 is a valid control block first byte, followed by 32 bytes
-    ControlBlock::decode(&dummy_bytes)
-        .map_err(|e| anyhow::anyhow!("Failed to create control block: {}", e))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -339,7 +352,12 @@ mod tests {
     #[test]
     fn test_generate_payout_outputs() {
         let market = create_test_market();
-        let result = generate_payout_outputs(&market.bets_a, market.total_amount, Network::Regtest, &market.fees);
+        let result = generate_payout_outputs(
+            &market.bets[0],
+            market.total_amount,
+            Network::Regtest,
+            &market.fees,
+        );
 
         assert!(
             result.is_ok(),
@@ -365,13 +383,8 @@ mod tests {
     #[test]
     fn test_generate_escape_outputs() {
         let market = create_test_market();
-        let all_bets: Vec<Bet> = market
-            .bets_a
-            .iter()
-            .chain(market.bets_b.iter())
-            .cloned()
-            .collect();
-        let result = generate_escape_outputs(&all_bets, Network::Regtest);
+        let all_bets: Vec<Bet> = market.all_bets().cloned().collect();
+        let result = generate_escape_outputs(&all_bets, Network::Regtest, &market.fees);
 
         assert!(
             result.is_ok(),
@@ -384,10 +397,17 @@ mod tests {
             "Should generate 3 outputs for 3 total bets"
         );
 
-        // Check amounts match original bets
-        assert_eq!(outputs[0].value.to_sat(), 100000);
-        assert_eq!(outputs[1].value.to_sat(), 50000);
-        assert_eq!(outputs[2].value.to_sat(), 150000);
+        // Check amounts match original bets minus the withdraw fee
+        let fee = market.fees.fee_per_withdraw_output;
+        assert_eq!(outputs[0].value.to_sat(), 100000 - fee);
+        assert_eq!(outputs[1].value.to_sat(), 50000 - fee);
+        assert_eq!(outputs[2].value.to_sat(), 150000 - fee);
+
+        // Refunds left as dust are dropped
+        let mut small = all_bets.clone();
+        small[1].amount = fee + DUST_THRESHOLD;
+        let outputs = generate_escape_outputs(&small, Network::Regtest, &market.fees).unwrap();
+        assert_eq!(outputs.len(), 2);
     }
 
     #[test]
@@ -402,8 +422,7 @@ mod tests {
 
         // Set the market as settled with A winning for the test
         let mut market = market;
-        market.settled = true;
-        market.winning_outcome = Some('A');
+        market.state = MarketState::Settled { winning_outcome: 0 };
 
         let params = WithdrawParams {
             market,
@@ -429,8 +448,27 @@ mod tests {
     }
 
     #[test]
-    fn test_build_escape_transaction() {
+    fn test_build_withdraw_transaction_checks_lifecycle() {
         let market = create_test_market();
+        let params = |withdraw_type| WithdrawParams {
+            market: market.clone(),
+            withdraw_type,
+            pool_utxo: OutPoint::null(),
+            fee_rate: None,
+        };
+
+        // Neither path can be spent before the pool is funded
+        assert!(build_withdraw_transaction(params(WithdrawType::Payout)).is_err());
+        assert!(build_withdraw_transaction(params(WithdrawType::Escape)).is_err());
+
+        // The templates are still available to derive the pool address
+        assert!(build_withdraw_template(&market, &WithdrawType::Escape, OutPoint::null()).is_ok());
+    }
+
+    #[test]
+    fn test_build_escape_transaction() {
+        let mut market = create_test_market();
+        market.state = MarketState::Funded;
         let pool_utxo = OutPoint::new(
             "abcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcdefabcd"
                 .parse()
@@ -454,10 +492,54 @@ mod tests {
         let tx = result.unwrap();
         assert_eq!(tx.input.len(), 1, "Should have 1 input");
         assert_eq!(tx.output.len(), 3, "Should have 3 outputs for all bets");
-        assert!(
-            tx.lock_time != LockTime::ZERO,
-            "Escape tx should have locktime"
+        assert_eq!(
+            tx.lock_time,
+            LockTime::from_time(1735689600 + 86400).unwrap(),
+            "Escape tx should be locked until settlement + timeout"
         );
+        assert!(tx.input[0].sequence.enables_absolute_lock_time());
+    }
+
+    #[test]
+    fn test_sign_withdraw_transaction_control_blocks() {
+        let mut market = create_test_market();
+        market.state = MarketState::Settled { winning_outcome: 1 };
+        let pool = market.pool_spend_info().unwrap();
+        let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+        let output_key = pool.spend_info.output_key().to_x_only_public_key();
+
+        for withdraw_type in [WithdrawType::Payout, WithdrawType::Escape] {
+            let params = WithdrawParams {
+                market: market.clone(),
+                withdraw_type,
+                pool_utxo: OutPoint::null(),
+                fee_rate: None,
+            };
+            let tx = build_withdraw_transaction(params.clone()).unwrap();
+            let signed = sign_withdraw_transaction(tx, &params, Some(&[0x01; 64])).unwrap();
+
+            let witness = &signed.input[0].witness;
+            let control_block = ControlBlock::decode(witness.last().unwrap()).unwrap();
+            let script = ScriptBuf::from_bytes(witness.second_to_last().unwrap().to_vec());
+            assert!(control_block.verify_taproot_commitment(&secp, output_key, &script));
+        }
+    }
+
+    #[test]
+    fn test_sign_withdraw_transaction_rejects_wrong_template() {
+        let mut market = create_test_market();
+        market.state = MarketState::Settled { winning_outcome: 0 };
+        let params = WithdrawParams {
+            market,
+            withdraw_type: WithdrawType::Payout,
+            pool_utxo: OutPoint::null(),
+            fee_rate: None,
+        };
+        let mut tx = build_withdraw_transaction(params.clone()).unwrap();
+        assert!(sign_withdraw_transaction(tx.clone(), &params, None).is_err());
+
+        tx.output[0].value = Amount::from_sat(tx.output[0].value.to_sat() - 1);
+        assert!(sign_withdraw_transaction(tx, &params, Some(&[0x01; 64])).is_err());
     }
 
     #[test]
@@ -467,35 +549,44 @@ mod tests {
         let result = generate_payout_outputs(&empty_bets, 300000, Network::Regtest, &fees);
         assert!(result.is_err(), "Should fail with empty bets");
     }
-    
+
     #[test]
     fn test_regtest_address_parsing() {
         use crate::test_utils::create_valid_regtest_address;
         use std::str::FromStr;
-        
+
         // Test that we can create and use valid regtest addresses
         let test_addresses = vec![
             create_valid_regtest_address(1),
             create_valid_regtest_address(2),
             create_valid_regtest_address(3),
         ];
-        
+
         for addr_str in &test_addresses {
             // These should parse correctly since they're created by our test utility
             let result = Address::from_str(addr_str);
-            assert!(result.is_ok(), "Failed to parse generated address {}: {:?}", addr_str, result.err());
-            
+            assert!(
+                result.is_ok(),
+                "Failed to parse generated address {}: {:?}",
+                addr_str,
+                result.err()
+            );
+
             let address = result.unwrap();
             let network_result = address.require_network(Network::Regtest);
-            assert!(network_result.is_ok(), "Address {} not valid for regtest: {:?}", 
-                    addr_str, network_result.err());
+            assert!(
+                network_result.is_ok(),
+                "Address {} not valid for regtest: {:?}",
+                addr_str,
+                network_result.err()
+            );
         }
     }
-    
+
     #[test]
     fn test_generate_payout_outputs_with_admin_fee() {
         use crate::test_utils::create_valid_regtest_address;
-        
+
         let bets = vec![
             Bet {
                 payout_address: create_valid_regtest_address(1),
@@ -510,40 +601,55 @@ mod tests {
                 vout: 0,
             },
         ];
-        
+
         let fees = MarketFees {
-            fee_per_deposit_output: 500,
+            fee_per_deposit_input: 500,
             fee_per_withdraw_output: 600,
             administrator_fee: 5000,
             administrator_address: Some(create_valid_regtest_address(3)),
         };
-        
+
         let result = generate_payout_outputs(&bets, 300000, Network::Regtest, &fees);
-        assert!(result.is_ok(), "Should generate outputs with admin fee: {:?}", result.err());
-        
+        assert!(
+            result.is_ok(),
+            "Should generate outputs with admin fee: {:?}",
+            result.err()
+        );
+
         let outputs = result.unwrap();
         // Should have 3 outputs: 2 for winners + 1 for admin
-        assert_eq!(outputs.len(), 3, "Should have 3 outputs including admin fee");
-        
+        assert_eq!(
+            outputs.len(),
+            3,
+            "Should have 3 outputs including admin fee"
+        );
+
         // Check admin fee output (should be last)
         let admin_output = &outputs[2];
-        assert_eq!(admin_output.value.to_sat(), 5000, "Admin fee should be 5000 sats");
-        
+        assert_eq!(
+            admin_output.value.to_sat(),
+            5000,
+            "Admin fee should be 5000 sats"
+        );
+
         // Verify the admin address matches what we configured
         use std::str::FromStr;
         let expected_admin_address = Address::from_str(&create_valid_regtest_address(3))
             .unwrap()
             .require_network(Network::Regtest)
             .unwrap();
-        assert_eq!(admin_output.script_pubkey, expected_admin_address.script_pubkey());
-        
+        assert_eq!(
+            admin_output.script_pubkey,
+            expected_admin_address.script_pubkey()
+        );
+
         // Verify winner payouts are calculated correctly
         let total_winning = 150000u64;
         let pool_after_fees = fees.pool_after_fees(300000, 2); // 300000 - (2*600) - 5000 = 293800
-        
+
         let expected_amount_1 = (100000 * pool_after_fees) / total_winning;
         let expected_amount_2 = (50000 * pool_after_fees) / total_winning;
-        
+
         assert_eq!(outputs[0].value.to_sat(), expected_amount_1);
         assert_eq!(outputs[1].value.to_sat(), expected_amount_2);
     }
@@ -4,7 +4,9 @@
 //! This module provides WASM-compatible versions of the core Rust functionality
 //! for use in web applications.
 
-use markstr_core::{utils::*, PredictionMarket};
+use markstr_core::{
+    market::outcome_index, nip19, utils::*, AddressScheme, Bet, MarketState, PredictionMarket,
+};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
@@ -87,10 +89,10 @@ pub struct WasmPredictionMarket {
     network: u8,
     /// Total amount in the market (in satoshis) (private field)
     total_amount: u64,
-    /// Whether the market has been settled (private field)
-    settled: bool,
-    /// Winning outcome ('A' or 'B') (private field)
-    winning_outcome: Option<String>,
+    /// Lifecycle state of the market (private field)
+    state: MarketState,
+    /// Bets placed on outcome A and B (private field)
+    bets: Vec<Vec<WasmBet>>,
 }
 
 #[wasm_bindgen]
@@ -106,6 +108,8 @@ impl WasmPredictionMarket {
         settlement_timestamp: u64,
         network: u8,
     ) -> WasmPredictionMarket {
+        // Store npub keys as hex, invalid keys are reported by the market methods
+        let oracle_pubkey = nip19::parse_public_key(&oracle_pubkey).unwrap_or(oracle_pubkey);
         WasmPredictionMarket {
             market_id,
             question,
@@ -115,28 +119,146 @@ impl WasmPredictionMarket {
             settlement_timestamp,
             network,
             total_amount: 0,
-            settled: false,
-            winning_outcome: None,
+            state: MarketState::Draft,
+            bets: vec![Vec::new(), Vec::new()],
         }
     }
 
-    /// Get the market's Bitcoin address
-    #[wasm_bindgen]
-    pub fn get_market_address(&self) -> Result<String, JsValue> {
-        let market = PredictionMarket::new(
+    /// Builds the core market holding the registered bets
+    fn to_core_market(&self) -> Result<PredictionMarket, JsValue> {
+        let mut market = PredictionMarket::new(
             self.question.clone(),
-            self.outcome_a.clone(),
-            self.outcome_b.clone(),
+            vec![self.outcome_a.clone(), self.outcome_b.clone()],
             self.oracle_pubkey.clone(),
             self.settlement_timestamp,
         )
         .map_err(|e| JsValue::from_str(&format!("Failed to create market: {}", e)))?;
 
-        market
+        market.network = u8_to_network(self.network)
+            .map_err(|e| JsValue::from_str(&format!("Failed to create market: {}", e)))?;
+        market.bets = self
+            .bets
+            .iter()
+            .map(|bets| {
+                bets.iter()
+                    .map(|bet| Bet {
+                        payout_address: bet.payout_address.clone(),
+                        amount: bet.amount,
+                        txid: bet.txid.clone(),
+                        vout: bet.vout,
+                    })
+                    .collect()
+            })
+            .collect();
+        market.total_amount = self.total_amount;
+        market.state = self.state;
+        Ok(market)
+    }
+
+    /// Registers a bet on outcome 'A' or 'B' while registration is open
+    #[wasm_bindgen]
+    pub fn place_bet(&mut self, outcome: String, bet: WasmBet) -> Result<(), JsValue> {
+        if outcome != "A" && outcome != "B" {
+            return Err(JsValue::from_str("Outcome must be 'A' or 'B'"));
+        }
+        if !self.state.accepts_bets() {
+            return Err(JsValue::from_str(&format!(
+                "Market is not open for bets ({})",
+                self.state
+            )));
+        }
+
+        let index = outcome_index(&outcome).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.total_amount += bet.amount;
+        self.bets[index].push(bet);
+        Ok(())
+    }
+
+    /// Moves the market to `next`, refusing illegal transitions
+    fn transition(&mut self, next: MarketState) -> Result<(), JsValue> {
+        self.state = self
+            .state
+            .transition(next)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(())
+    }
+
+    /// Opens the market for bet registration
+    #[wasm_bindgen]
+    pub fn open_registration(&mut self) -> Result<(), JsValue> {
+        self.transition(MarketState::Open)
+    }
+
+    /// Freezes the bet set and starts the deposit signing round
+    #[wasm_bindgen]
+    pub fn freeze_bets(&mut self) -> Result<(), JsValue> {
+        self.transition(MarketState::DepositSigning)
+    }
+
+    /// Marks the pool deposit as confirmed
+    #[wasm_bindgen]
+    pub fn mark_funded(&mut self) -> Result<(), JsValue> {
+        self.transition(MarketState::Funded)
+    }
+
+    /// Marks the winnings as paid out
+    #[wasm_bindgen]
+    pub fn mark_paid_out(&mut self) -> Result<(), JsValue> {
+        let winning_outcome = self
+            .state
+            .winning_outcome()
+            .ok_or_else(|| JsValue::from_str("Market is not settled"))?;
+        self.transition(MarketState::PaidOut { winning_outcome })
+    }
+
+    /// Marks the bets as refunded through the escape path
+    #[wasm_bindgen]
+    pub fn mark_escaped(&mut self) -> Result<(), JsValue> {
+        self.transition(MarketState::Escaped)
+    }
+
+    /// Get the market's pool address (requires registered bets)
+    #[wasm_bindgen]
+    pub fn get_market_address(&self) -> Result<String, JsValue> {
+        self.to_core_market()?
             .get_market_address()
             .map_err(|e| JsValue::from_str(&format!("Failed to get address: {}", e)))
     }
 
+    /// Get the market announcement as `nevent`
+    #[wasm_bindgen]
+    pub fn get_nevent(&self) -> Result<String, JsValue> {
+        self.to_core_market()?
+            .nevent()
+            .map_err(|e| JsValue::from_str(&format!("Failed to encode nevent: {}", e)))
+    }
+
+    /// Get the market announcement address as `naddr`
+    #[wasm_bindgen]
+    pub fn get_naddr(&self) -> Result<String, JsValue> {
+        self.to_core_market()?
+            .naddr()
+            .map_err(|e| JsValue::from_str(&format!("Failed to encode naddr: {}", e)))
+    }
+
+    /// Get the pool descriptor (address, internal key and leaf scripts).
+    ///
+    /// With `legacy` set, describes the `OP_CHECKSIGFROMSTACK` address
+    /// advertised by earlier releases instead of the spendable pool.
+    #[wasm_bindgen]
+    pub fn get_pool_descriptor(&self, legacy: bool) -> Result<JsValue, JsValue> {
+        let scheme = if legacy {
+            AddressScheme::Legacy
+        } else {
+            AddressScheme::Pool
+        };
+        let descriptor = self
+            .to_core_market()?
+            .pool_descriptor(scheme)
+            .map_err(|e| JsValue::from_str(&format!("Failed to get pool descriptor: {}", e)))?;
+        serde_wasm_bindgen::to_value(&descriptor).map_err(JsValue::from)
+    }
+
     /// Calculates odds for outcome A as a percentage (0-100)
     #[wasm_bindgen]
     pub fn get_odds_a(&self, bets_a_total: u64, bets_b_total: u64) -> f64 {
@@ -183,9 +305,9 @@ impl WasmPredictionMarket {
             return Err(JsValue::from_str("Winning outcome must be 'A' or 'B'"));
         }
 
-        self.settled = true;
-        self.winning_outcome = Some(winning_outcome);
-        Ok(())
+        let winning_outcome =
+            outcome_index(&winning_outcome).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.transition(MarketState::Settled { winning_outcome })
     }
 
     /// Generates a simple market message for outcome verification
@@ -227,6 +349,11 @@ impl WasmPredictionMarket {
         self.oracle_pubkey.clone()
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn oracle_npub(&self) -> Option<String> {
+        nip19::encode_public_key(&self.oracle_pubkey).ok()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn settlement_timestamp(&self) -> u64 {
         self.settlement_timestamp
@@ -242,14 +369,28 @@ impl WasmPredictionMarket {
         self.total_amount
     }
 
+    /// Human readable lifecycle state, as rendered by every frontend
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> String {
+        self.state.to_string()
+    }
+
+    /// Stable lifecycle state name (`draft`, `open`, `deposit_signing`, ...)
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        self.state.name().to_string()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn settled(&self) -> bool {
-        self.settled
+        self.state.winning_outcome().is_some()
     }
 
     #[wasm_bindgen(getter)]
     pub fn winning_outcome(&self) -> Option<String> {
-        self.winning_outcome.clone()
+        self.state
+            .winning_outcome()
+            .map(markstr_core::market::outcome_label)
     }
 }
 
@@ -288,7 +429,32 @@ pub fn btc_to_satoshi(btc: f64) -> u64 {
     markstr_core::utils::btc_to_satoshi(btc)
 }
 
-/// Simplified signature verification function (placeholder)
+/// Converts a public key given as `npub` or hex to hex
+#[wasm_bindgen]
+pub fn decode_public_key(pubkey: &str) -> Result<String, JsValue> {
+    nip19::parse_public_key(pubkey).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Encodes a hex public key as `npub`
+#[wasm_bindgen]
+pub fn encode_npub(pubkey: &str) -> Result<String, JsValue> {
+    nip19::encode_public_key(pubkey).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Converts an event id given as `note`, `nevent` or hex to hex
+#[wasm_bindgen]
+pub fn decode_event_id(id: &str) -> Result<String, JsValue> {
+    nip19::parse_event_id(id).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Encodes a hex event id as `note`
+#[wasm_bindgen]
+pub fn encode_note(id: &str) -> Result<String, JsValue> {
+    nip19::encode_event_id(id).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Verifies a BIP-340 signature of a 32-byte message, hex-encoded except for the
+/// public key which may also be an `npub`
 #[wasm_bindgen]
 pub fn verify_signature(message: &str, signature: &str, pubkey: &str) -> Result<bool, JsValue> {
     markstr_core::utils::verify_signature(message, signature, pubkey)
@@ -395,6 +561,12 @@ impl MarketAnalytics {
     }
 }
 
+impl Default for MarketAnalytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Console logging for debugging
 #[wasm_bindgen]
 extern "C" {